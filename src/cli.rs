@@ -0,0 +1,322 @@
+use crate::{
+    engine::voxel::{
+        chunk::CHUNK_SIZE,
+        generator::{
+            noise::NoiseGenerator,
+            structure::{StructureTemplate, STRUCTURE_DIRECTORY},
+            TerrainGenerator,
+        },
+    },
+    game::{gradient::NaturalGradient, preset::WorldPreset},
+    io::VoxelWriter,
+};
+use cgmath::Vector3;
+use crossbeam::channel::bounded;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+const USAGE: &str = "usage: game gen --seed N --from X,Z --to X,Z --out FILE \
+[--preset FILE] [--min-y CHUNK] [--max-y CHUNK]
+
+Generates the terrain between the world positions FROM and TO without opening a window.
+FILE ending in .pgm gets a grayscale heightmap, every other FILE gets VOXELSRS voxels.";
+
+/// Options of the `gen` subcommand
+struct GenOptions {
+    seed: u32,
+    from: (i32, i32),
+    to: (i32, i32),
+    out: PathBuf,
+    preset: WorldPreset,
+    /// Vertical chunk range that is generated
+    min_y: i32,
+    max_y: i32,
+}
+
+/// Runs the `gen` subcommand, `args` excludes the program name and the subcommand
+pub fn gen(args: &[String]) {
+    let options = match parse(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let generator = NoiseGenerator::new(
+        options.seed,
+        options.preset.noise.clone(),
+        Box::new(NaturalGradient::new(
+            options.seed,
+            options.preset.bands.clone(),
+        )),
+    )
+    .with_structures(StructureTemplate::load_all(STRUCTURE_DIRECTORY).into())
+    .with_surface(options.preset.surface_lines());
+
+    let min = (
+        options.from.0.min(options.to.0),
+        options.from.1.min(options.to.1),
+    );
+    let max = (
+        options.from.0.max(options.to.0),
+        options.from.1.max(options.to.1),
+    );
+
+    let mut columns = Vec::new();
+
+    for x in min.0.div_euclid(CHUNK_SIZE as i32)..=max.0.div_euclid(CHUNK_SIZE as i32) {
+        for z in min.1.div_euclid(CHUNK_SIZE as i32)..=max.1.div_euclid(CHUNK_SIZE as i32) {
+            columns.push((x, z));
+        }
+    }
+
+    let contains = |x: i32, z: i32| x >= min.0 && x <= max.0 && z >= min.1 && z <= max.1;
+
+    let result = if options.out.extension().is_some_and(|e| e == "pgm") {
+        let width = (max.0 - min.0 + 1) as usize;
+        let depth = (max.1 - min.1 + 1) as usize;
+
+        let mut heights = vec![None; width * depth];
+
+        generate_parallel(
+            &columns,
+            |&(x, z)| column_heights(&generator, x, z, options.min_y, options.max_y),
+            |i, column| {
+                let (chunk_x, chunk_z) = columns[i];
+
+                for z in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let world_x = chunk_x * CHUNK_SIZE as i32 + x as i32;
+                        let world_z = chunk_z * CHUNK_SIZE as i32 + z as i32;
+
+                        if contains(world_x, world_z) {
+                            heights
+                                [(world_z - min.1) as usize * width + (world_x - min.0) as usize] =
+                                column[z * CHUNK_SIZE + x];
+                        }
+                    }
+                }
+
+                Ok(())
+            },
+        )
+        .and_then(|()| store_heightmap(&options.out, width, depth, &heights))
+    } else {
+        let chunks: Vec<Vector3<i32>> = columns
+            .iter()
+            .flat_map(|&(x, z)| (options.min_y..=options.max_y).map(move |y| Vector3::new(x, y, z)))
+            .collect();
+
+        VoxelWriter::create(&options.out).and_then(|mut writer| {
+            generate_parallel(
+                &chunks,
+                |&chunk_pos| chunk_voxels(&generator, chunk_pos, contains),
+                |_, voxels| writer.write(&voxels),
+            )?;
+
+            writer.finish()
+        })
+    };
+
+    if let Err(err) = result {
+        eprintln!("couldn't write '{}': {}", options.out.display(), err);
+        std::process::exit(1);
+    }
+}
+
+fn parse(args: &[String]) -> Result<GenOptions, String> {
+    let mut seed = None;
+    let mut from = None;
+    let mut to = None;
+    let mut out = None;
+    let mut preset = None;
+    let mut min_y = -4;
+    let mut max_y = 16;
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for '{}'", arg))
+        };
+
+        match arg.as_str() {
+            "--seed" => seed = Some(parse_number(value()?)?),
+            "--from" => from = Some(parse_position(value()?)?),
+            "--to" => to = Some(parse_position(value()?)?),
+            "--out" => out = Some(PathBuf::from(value()?)),
+            "--preset" => {
+                let path = value()?;
+                preset = Some(
+                    WorldPreset::load(path).ok_or_else(|| format!("invalid preset '{}'", path))?,
+                );
+            }
+            "--min-y" => min_y = parse_number(value()?)?,
+            "--max-y" => max_y = parse_number(value()?)?,
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+
+    Ok(GenOptions {
+        seed: seed.ok_or("missing --seed")?,
+        from: from.ok_or("missing --from")?,
+        to: to.ok_or("missing --to")?,
+        out: out.ok_or("missing --out")?,
+        preset: preset.unwrap_or_default(),
+        min_y,
+        max_y,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", value))
+}
+
+fn parse_position(value: &str) -> Result<(i32, i32), String> {
+    let (x, z) = value
+        .split_once(',')
+        .ok_or_else(|| format!("invalid position '{}', expected X,Z", value))?;
+
+    Ok((parse_number(x.trim())?, parse_number(z.trim())?))
+}
+
+/// Runs `generate` for every job on all cores and hands the results to `consume` on the
+/// calling thread as they finish, so only a few results are held at a time
+fn generate_parallel<J: Sync, T: Send>(
+    jobs: &[J],
+    generate: impl Fn(&J) -> T + Sync,
+    mut consume: impl FnMut(usize, T) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let next = AtomicUsize::new(0);
+
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = bounded(workers * 2);
+
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (next, generate) = (&next, &generate);
+
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+
+                let Some(job) = jobs.get(i) else {
+                    break;
+                };
+
+                // The receiver is gone after an error
+                if sender.send((i, generate(job))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        drop(sender);
+
+        let result = receiver
+            .iter()
+            .try_for_each(|(i, result)| consume(i, result));
+
+        // Unblocks the workers if `consume` failed
+        drop(receiver);
+
+        result
+    })
+}
+
+/// Surface heights of a chunk column indexed by `z * CHUNK_SIZE + x`, taken from the
+/// heightfield of the generator without generating the voxels
+fn column_heights(
+    generator: &dyn TerrainGenerator,
+    chunk_x: i32,
+    chunk_z: i32,
+    min_y: i32,
+    max_y: i32,
+) -> Vec<Option<i32>> {
+    // Only one chunk of the column carries the heightfield
+    let Some(heights) = (min_y..=max_y)
+        .find_map(|chunk_y| generator.heights(Vector3::new(chunk_x, chunk_y, chunk_z)))
+    else {
+        return vec![None; CHUNK_SIZE * CHUNK_SIZE];
+    };
+
+    (0..CHUNK_SIZE * CHUNK_SIZE)
+        .map(|i| Some(heights[(i / CHUNK_SIZE, i % CHUNK_SIZE)] as i32))
+        .collect()
+}
+
+/// Voxels of a chunk in world positions, only the columns where `contains` holds
+fn chunk_voxels(
+    generator: &dyn TerrainGenerator,
+    chunk_pos: Vector3<i32>,
+    contains: impl Fn(i32, i32) -> bool,
+) -> Vec<([i32; 3], [u8; 4])> {
+    let mut voxels = Vec::new();
+
+    let Some(chunk) = generator.generate(chunk_pos) else {
+        return voxels;
+    };
+
+    for z in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                if !chunk.get_occupied(x, y, z) {
+                    continue;
+                }
+
+                let world = [
+                    chunk_pos.x * CHUNK_SIZE as i32 + x as i32,
+                    chunk_pos.y * CHUNK_SIZE as i32 + y as i32,
+                    chunk_pos.z * CHUNK_SIZE as i32 + z as i32,
+                ];
+
+                if contains(world[0], world[2]) {
+                    voxels.push((world, chunk.get_color(x, y, z).unwrap_or([255; 4])));
+                }
+            }
+        }
+    }
+
+    voxels
+}
+
+/// Writes a binary PGM with the heights normalized to the full gray range, holes are black
+fn store_heightmap(
+    path: &Path,
+    width: usize,
+    depth: usize,
+    heights: &[Option<i32>],
+) -> std::io::Result<()> {
+    let min = heights.iter().flatten().min().copied().unwrap_or(0);
+    let max = heights.iter().flatten().max().copied().unwrap_or(0);
+
+    let range = (max - min).max(1) as f32;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write!(writer, "P5\n{} {}\n255\n", width, depth)?;
+
+    let pixels: Vec<u8> = heights
+        .iter()
+        .map(|height| match height {
+            Some(height) => ((height - min) as f32 / range * 255.0).round() as u8,
+            None => 0,
+        })
+        .collect();
+
+    writer.write_all(&pixels)?;
+
+    writer.flush()
+}
@@ -3,7 +3,10 @@ use std::sync::{
     Arc,
 };
 
-use crate::engine::renderer::{backend::Backend, camera::Camera, frame::Frame, renderer::Renderer};
+use crate::engine::renderer::{
+    antialiasing::AntiAliasing, backend::Backend, camera::Camera, frame::Frame, renderer::Renderer,
+    shadow::ShadowSettings, ssao::SsaoQuality,
+};
 use crate::engine::ui::renderer::UiRenderer;
 use crossbeam::channel::Receiver;
 use wgpu::{Device, SurfaceTexture};
@@ -19,8 +22,20 @@ pub struct Engine<'a> {
 }
 
 impl<'a> Engine<'a> {
-    pub fn new(window: &'static Window, backend: Backend<'a>) -> Self {
-        let renderer = Renderer::new(backend, window.dimension());
+    pub fn new(
+        window: &'static Window,
+        backend: Backend<'a>,
+        shadow_settings: ShadowSettings,
+        ssao_quality: SsaoQuality,
+        anti_aliasing: AntiAliasing,
+    ) -> Self {
+        let renderer = Renderer::new(
+            backend,
+            window.dimension(),
+            shadow_settings,
+            ssao_quality,
+            anti_aliasing,
+        );
 
         let ui_renderer = UiRenderer::new(window.window(), renderer.backend(), 1);
 
@@ -43,6 +58,8 @@ impl<'a> Engine<'a> {
     pub fn start_frame(&self) -> Frame {
         let output: SurfaceTexture;
 
+        self.renderer().apply_anti_aliasing();
+
         loop {
             match self.renderer().backend().surface().get_current_texture() {
                 Ok(o) => {
@@ -1,4 +1,4 @@
-use cgmath::{InnerSpace, Vector3};
+use cgmath::{InnerSpace, Vector3, Vector4};
 
 pub struct Plane {
     point: Vector3<f32>,
@@ -11,6 +11,19 @@ impl Plane {
         Self { point, normal }
     }
 
+    /// Plane of the points `p` with `a * p.x + b * p.y + c * p.z + d = 0`, the normal
+    /// points towards the positive side
+    pub fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let normal = coefficients.truncate();
+
+        Self::new(-coefficients.w * normal / normal.magnitude2(), normal)
+    }
+
+    /// Coefficients `(a, b, c, d)` of the plane, the inverse of `from_coefficients`
+    pub fn coefficients(&self) -> Vector4<f32> {
+        self.normal.extend(-self.normal.dot(self.point))
+    }
+
     pub fn distance(&self, point: &Vector3<f32>) -> f32 {
         (point - self.point).dot(self.normal)
     }
@@ -0,0 +1,102 @@
+use super::{
+    graph::{fullscreen_pass, GraphPass, Resource, Scale, Targets},
+    pipeline::fxaa::fxaa_pipeline,
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, Device, RenderPipeline, Sampler, TextureFormat,
+    TextureView,
+};
+
+/// Color after the FXAA pass
+pub const ANTIALIASED: Resource =
+    Resource::new("antialiased", TextureFormat::Rgba16Float, Scale::Full);
+
+/// Anti-aliasing of the scene, can be changed at runtime with
+/// `Renderer::set_anti_aliasing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AntiAliasing {
+    /// Samples per pixel of the scene pass, 1 to disable MSAA, otherwise 2, 4 or 8
+    pub msaa: u32,
+    /// Smooths the remaining edges in a post-process pass
+    pub fxaa: bool,
+}
+
+impl AntiAliasing {
+    pub const OFF: AntiAliasing = AntiAliasing {
+        msaa: 1,
+        fxaa: false,
+    };
+}
+
+/// Fast approximate anti-aliasing of a color target
+pub struct Fxaa {
+    source: Resource,
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+    sampler: Sampler,
+    bind_group: Option<BindGroup>,
+}
+
+impl Fxaa {
+    pub fn new(device: &Device, source: Resource) -> Self {
+        let (layout, pipeline) = fxaa_pipeline(device);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("vengine::fxaa_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            source,
+            layout,
+            pipeline,
+            sampler,
+            bind_group: None,
+        }
+    }
+}
+
+impl GraphPass for Fxaa {
+    fn name(&self) -> &'static str {
+        "fxaa"
+    }
+
+    fn inputs(&self) -> Vec<Resource> {
+        vec![self.source]
+    }
+
+    fn outputs(&self) -> Vec<Resource> {
+        vec![ANTIALIASED]
+    }
+
+    fn prepare(&mut self, device: &Device, targets: &Targets) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vengine::fxaa_bind_group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(targets.view(self.source)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        }));
+    }
+
+    fn record(&self, encoder: &mut CommandEncoder, targets: &Targets, _surface: &TextureView) {
+        fullscreen_pass(
+            encoder,
+            "vengine::fxaa_pass",
+            targets.view(ANTIALIASED),
+            &self.pipeline,
+            self.bind_group.as_ref().unwrap(),
+        );
+    }
+}
@@ -4,7 +4,14 @@ use wgpu::{
     TextureFormat,
 };
 
-use crate::engine::core::window::window::Window;
+use crate::engine::{core::window::window::Window, renderer::graph::SCENE_OUTPUTS};
+
+/// Features needed to draw chunks with GPU culling and `multi_draw_indirect`
+const INDIRECT_FEATURES: wgpu::Features =
+    wgpu::Features::MULTI_DRAW_INDIRECT.union(wgpu::Features::INDIRECT_FIRST_INSTANCE);
+
+/// MSAA sample counts of the scene pass, every adapter supports 1 and 4
+const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
 
 pub struct Backend<'a> {
     surface: Surface<'a>,
@@ -12,6 +19,8 @@ pub struct Backend<'a> {
     queue: Arc<Queue>,
     config: Mutex<SurfaceConfiguration>,
     format: TextureFormat,
+    // Sample counts supported by every output of the scene pass
+    sample_counts: Vec<u32>,
 }
 
 impl<'a> Backend<'a> {
@@ -32,10 +41,38 @@ impl<'a> Backend<'a> {
             .await
             .unwrap();
 
+        // Chunks are drawn with indirect draws where supported
+        let indirect = if adapter.features().contains(INDIRECT_FEATURES) {
+            INDIRECT_FEATURES
+        } else {
+            wgpu::Features::empty()
+        };
+
+        // Sample counts other than 1 and 4 depend on the adapter
+        let format_features = wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+
+        let (format_features, sample_counts) = if adapter.features().contains(format_features) {
+            let sample_counts = SAMPLE_COUNTS
+                .into_iter()
+                .filter(|&count| {
+                    SCENE_OUTPUTS.iter().all(|output| {
+                        adapter
+                            .get_texture_format_features(output.format)
+                            .flags
+                            .sample_count_supported(count)
+                    })
+                })
+                .collect();
+
+            (format_features, sample_counts)
+        } else {
+            (wgpu::Features::empty(), vec![1, 4])
+        };
+
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::PUSH_CONSTANTS,
+                    required_features: wgpu::Features::PUSH_CONSTANTS | indirect | format_features,
                     // WebGL doesn't support all of wgpu's features, so if
                     // we're building for the web, we'll have to disable some.
                     required_limits: wgpu::Limits {
@@ -82,6 +119,7 @@ impl<'a> Backend<'a> {
             surface,
             config: Mutex::new(config),
             format: surface_format,
+            sample_counts,
         }
     }
 
@@ -89,6 +127,16 @@ impl<'a> Backend<'a> {
         &self.device
     }
 
+    /// Returns if chunks can be drawn with indirect draws
+    pub fn supports_indirect(&self) -> bool {
+        self.device.features().contains(INDIRECT_FEATURES)
+    }
+
+    /// Returns if the scene pass can be drawn with `count` samples per pixel
+    pub fn supports_samples(&self, count: u32) -> bool {
+        self.sample_counts.contains(&count)
+    }
+
     pub fn queue(&self) -> &Arc<Queue> {
         &self.queue
     }
@@ -1,4 +1,5 @@
-use cgmath::{Point3, Vector3};
+use crate::engine::geometry::plane::Plane;
+use cgmath::{InnerSpace, Matrix, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
 use crossbeam::atomic::AtomicCell;
 use std::sync::Arc;
 use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, Buffer, Device, Queue};
@@ -11,6 +12,11 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.0, 1.0,
 );
 
+/// Vertical field of view in degrees
+pub const FOVY: f32 = 45.0;
+pub const ZNEAR: f32 = 0.1;
+pub const ZFAR: f32 = 100.0;
+
 pub struct Camera {
     eye: AtomicCell<Point3<f32>>,
     target: AtomicCell<Point3<f32>>,
@@ -65,9 +71,9 @@ impl Camera {
             target: AtomicCell::new((0.0, 0.0, 0.0).into()),
             up: cgmath::Vector3::unit_y(),
             aspect: AtomicCell::new(aspect),
-            fovy: 45.0,
-            znear: 0.1,
-            zfar: 100.0,
+            fovy: FOVY,
+            znear: ZNEAR,
+            zfar: ZFAR,
             camera_uniform: AtomicCell::new(camera_uniform),
             camera_bind_group,
             camera_buffer,
@@ -130,21 +136,92 @@ impl Camera {
         &self.camera_bind_group_layout
     }
 
+    /// Uniform buffer holding the view projection
+    pub fn buffer(&self) -> &Buffer {
+        &self.camera_buffer
+    }
+
+    pub fn near(&self) -> f32 {
+        self.znear
+    }
+
+    /// Normalized view direction
+    pub fn forward(&self) -> Vector3<f32> {
+        (self.target.load() - self.eye.load()).normalize()
+    }
+
+    /// Corners of the part of the view between the distances `near` and `far` along the
+    /// view direction, near corners first
+    pub fn slice_corners(&self, near: f32, far: f32) -> [Point3<f32>; 8] {
+        slice_corners(self.view(), self.projection(), near, far)
+    }
+
     pub fn up(&self) -> Vector3<f32> {
         self.up
     }
 
-    fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_at_rh(self.eye.load(), self.target.load(), self.up);
-        let proj = cgmath::perspective(
-            cgmath::Deg(self.fovy),
-            self.aspect.load(),
-            self.znear,
-            self.zfar,
-        );
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.build_view_projection_matrix()
+    }
+
+    /// Planes bounding the view, in world space with the normals pointing inside
+    pub fn frustum_planes(&self) -> [Plane; 6] {
+        frustum_planes(self.build_view_projection_matrix())
+    }
 
-        OPENGL_TO_WGPU_MATRIX * proj * view
+    /// Projection from view space, without the view
+    pub fn projection(&self) -> Matrix4<f32> {
+        projection(self.fovy, self.aspect.load(), self.znear, self.zfar)
     }
+
+    /// View from world space, without the projection
+    pub fn view(&self) -> Matrix4<f32> {
+        cgmath::Matrix4::look_at_rh(self.eye.load(), self.target.load(), self.up)
+    }
+
+    fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.projection() * self.view()
+    }
+}
+
+/// Perspective projection of a camera with a vertical field of view of `fovy` degrees
+pub fn projection(fovy: f32, aspect: f32, znear: f32, zfar: f32) -> Matrix4<f32> {
+    OPENGL_TO_WGPU_MATRIX * cgmath::perspective(cgmath::Deg(fovy), aspect, znear, zfar)
+}
+
+/// Corners of the part of a view between the distances `near` and `far` along the view
+/// direction, near corners first. The corners are unprojected from the edges of the screen,
+/// so they match whatever the projection covers
+pub fn slice_corners(
+    view: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    near: f32,
+    far: f32,
+) -> [Point3<f32>; 8] {
+    let inverse = (projection * view).invert().unwrap();
+
+    [near, far]
+        .map(|distance| {
+            let clip = projection * Vector4::new(0.0, 0.0, -distance, 1.0);
+            let depth = clip.z / clip.w;
+
+            [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+                .map(|(x, y)| Point3::from_homogeneous(inverse * Vector4::new(x, y, depth, 1.0)))
+        })
+        .as_flattened()
+        .try_into()
+        .unwrap()
+}
+
+/// Left, right, bottom, top, near and far planes of a view projection with a depth range
+/// of `[0, 1]`
+pub fn frustum_planes(view_proj: Matrix4<f32>) -> [Plane; 6] {
+    let x = view_proj.row(0);
+    let y = view_proj.row(1);
+    let z = view_proj.row(2);
+    let w = view_proj.row(3);
+
+    [w + x, w - x, w + y, w - y, z, w - z].map(Plane::from_coefficients)
 }
 
 // We need this for Rust to store our data correctly for the shaders
@@ -170,3 +247,33 @@ impl CameraUniform {
         self.view_proj = camera.build_view_projection_matrix().into();
     }
 }
+
+#[test]
+fn test_frustum_planes() {
+    let view = Matrix4::look_at_rh(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(0.0, 0.0, -1.0),
+        Vector3::unit_y(),
+    );
+    let proj = cgmath::perspective(cgmath::Deg(90.0), 1.0, 0.1, 100.0);
+
+    // Maps the depth range from [-1, 1] to [0, 1]
+    #[rustfmt::skip]
+    let depth = Matrix4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    );
+
+    let planes = frustum_planes(depth * proj * view);
+
+    let inside = |point: Vector3<f32>| planes.iter().all(|plane| plane.side(&point));
+
+    assert!(inside(Vector3::new(0.0, 0.0, -10.0)));
+    assert!(inside(Vector3::new(9.0, -9.0, -10.0)));
+    assert!(!inside(Vector3::new(11.0, 0.0, -10.0)));
+    assert!(!inside(Vector3::new(0.0, 0.0, 10.0)));
+    assert!(!inside(Vector3::new(0.0, 0.0, -0.05)));
+    assert!(!inside(Vector3::new(0.0, 0.0, -101.0)));
+}
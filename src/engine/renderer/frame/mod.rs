@@ -1,4 +1,11 @@
-use crate::engine::core::engine::Engine;
+use crate::engine::{
+    core::engine::Engine,
+    renderer::{
+        camera::frustum_planes,
+        graph::{COLOR, DEPTH, NORMAL},
+    },
+};
+use cgmath::EuclideanSpace;
 use egui_wgpu::ScreenDescriptor;
 use std::sync::Mutex;
 use ui_pass::UiPass;
@@ -19,6 +26,13 @@ impl<'a> Frame<'a> {
     pub fn new(engine: &'a Engine, output: SurfaceTexture) -> Self {
         let dimensions = engine.renderer().dimensions();
 
+        engine.renderer().shadows().update(engine.camera());
+        engine
+            .renderer()
+            .sky()
+            .update(engine.camera(), engine.renderer().shadows().sun());
+        engine.renderer().graph().update(engine.camera());
+
         Self {
             engine,
             output,
@@ -27,11 +41,22 @@ impl<'a> Frame<'a> {
         }
     }
 
+    /// Starts the scene pass, drawing into the scene outputs of the render graph. Its
+    /// post-process passes are recorded when the pass is finished
     pub fn start_voxel_render_pass(&self) -> Result<VoxelPass, wgpu::SurfaceError> {
-        let view = self
-            .output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        // With MSAA the pass draws into multisampled targets resolved into the outputs
+        let (color, normal, depth_view) = {
+            let graph = self.engine.renderer().graph();
+            let targets = graph.targets();
+
+            let attachment = |resource| {
+                let (view, resolve) = targets.attachment(resource);
+
+                (view.clone(), resolve.cloned())
+            };
+
+            (attachment(COLOR), attachment(NORMAL), attachment(DEPTH).0)
+        };
 
         let mut encoder = self
             .engine
@@ -42,30 +67,31 @@ impl<'a> Frame<'a> {
                 label: Some("vengine::render_scene_encoder"),
             });
 
-        let depth_view = self
-            .engine
-            .renderer()
-            .depth_texture
-            .lock()
-            .unwrap()
-            .view
-            .clone();
-
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0,
-                    }),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &color.0,
+                    resolve_target: color.1.as_ref(),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.1,
+                            g: 0.2,
+                            b: 0.3,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &normal.0,
+                    resolve_target: normal.1.as_ref(),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+            ],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: &depth_view,
                 depth_ops: Some(wgpu::Operations {
@@ -78,13 +104,68 @@ impl<'a> Frame<'a> {
             timestamp_writes: None,
         });
 
-        pass.set_pipeline(&self.engine.renderer().voxel_pipeline);
-        pass.set_bind_group(0, self.engine.renderer().camera().bind_group(), &[]);
+        // Sky behind everything
+        pass.set_pipeline(&self.engine.renderer().scene_pipelines().sky);
+        pass.set_bind_group(0, self.engine.renderer().sky().bind_group(), &[]);
+        pass.draw(0..3, 0..1);
+
+        pass.set_pipeline(&self.engine.renderer().scene_pipelines().voxel);
+        pass.set_bind_group(0, self.engine.renderer().scene_bind_group(), &[]);
 
         // Quad buffer (bleibt für alle Chunks gleich)
         pass.set_vertex_buffer(0, self.engine.renderer().quad.slice(..));
 
-        Ok(VoxelPass::new(pass.forget_lifetime(), encoder))
+        Ok(VoxelPass::new(
+            pass.forget_lifetime(),
+            encoder,
+            self.engine.renderer().camera().frustum_planes(),
+            self.engine.renderer().camera().get_eye().to_vec(),
+            None,
+        ))
+    }
+
+    /// Starts a pass drawing into the shadow map of a cascade, finished like the scene
+    /// pass and before it, so the scene pass sees the shadows of this frame
+    pub fn start_shadow_pass(&self, cascade: u32) -> VoxelPass<'_> {
+        let shadows = self.engine.renderer().shadows();
+
+        let mut encoder = self
+            .engine
+            .renderer()
+            .backend()
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("vengine::render_shadow_encoder"),
+            });
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("vengine::shadow_pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: shadows.layer(cascade),
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.engine.renderer().shadow_pipeline);
+        pass.set_bind_group(0, shadows.cascade_bind_group(cascade), &[]);
+        pass.set_vertex_buffer(0, self.engine.renderer().quad.slice(..));
+
+        let light = shadows.cascade(cascade);
+
+        VoxelPass::new(
+            pass.forget_lifetime(),
+            encoder,
+            frustum_planes(light.view_proj),
+            light.eye.to_vec(),
+            Some(cascade),
+        )
     }
 
     pub fn start_ui_render_pass(&self) -> UiPass {
@@ -140,10 +221,21 @@ impl<'a> Frame<'a> {
     }
 
     pub fn finish_voxel_render_pass(&self, pass: VoxelPass) {
-        let (encoder, pass) = pass.into_inner();
+        let scene = pass.cascade().is_none();
+
+        let (mut encoder, pass) = pass.into_inner();
 
         drop(pass);
 
+        if scene {
+            let view = self
+                .output
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+
+            self.engine.renderer().graph().execute(&mut encoder, &view);
+        }
+
         let mut lock = self.encoders.lock().unwrap();
 
         lock.push(encoder);
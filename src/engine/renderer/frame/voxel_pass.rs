@@ -1,5 +1,13 @@
-use crate::engine::voxel::{chunk_mesh::ChunkMesh, object::Object};
-use cgmath::{Array, Matrix, Matrix4, Vector3};
+use crate::engine::{
+    geometry::plane::Plane,
+    renderer::{indirect::ChunkBuffer, renderer::Renderer},
+    voxel::{
+        chunk::{CHUNK_SIZE, VOXEL_SIZE},
+        chunk_mesh::ChunkMesh,
+        object::Object,
+    },
+};
+use cgmath::{Array, Matrix, Matrix4, SquareMatrix, Vector3, Vector4};
 use wgpu::{CommandEncoder, RenderPass};
 
 #[repr(C)]
@@ -12,70 +20,156 @@ struct PushConstant {
 pub struct VoxelPass<'a> {
     encoder: CommandEncoder,
     pass: RenderPass<'a>,
+    frustum: [Plane; 6],
+    eye: Vector3<f32>,
+    drawn: usize,
+    culled: usize,
+    quads: usize,
+    occluded: usize,
+    /// Cascade whose shadow map is drawn, `None` for the scene pass
+    cascade: Option<u32>,
 }
 
 impl<'a> VoxelPass<'a> {
-    pub fn new(pass: RenderPass<'a>, encoder: CommandEncoder) -> VoxelPass<'a> {
-        VoxelPass { encoder, pass }
+    pub fn new(
+        pass: RenderPass<'a>,
+        encoder: CommandEncoder,
+        frustum: [Plane; 6],
+        eye: Vector3<f32>,
+        cascade: Option<u32>,
+    ) -> VoxelPass<'a> {
+        VoxelPass {
+            encoder,
+            pass,
+            frustum,
+            eye,
+            drawn: 0,
+            culled: 0,
+            quads: 0,
+            occluded: 0,
+            cascade,
+        }
+    }
+
+    pub fn cascade(&self) -> Option<u32> {
+        self.cascade
+    }
+
+    pub fn frustum(&self) -> &[Plane; 6] {
+        &self.frustum
+    }
+
+    pub fn eye(&self) -> Vector3<f32> {
+        self.eye
+    }
+
+    /// Number of chunks drawn so far
+    pub fn drawn(&self) -> usize {
+        self.drawn
+    }
+
+    /// Number of chunks skipped so far for being outside the view
+    pub fn culled(&self) -> usize {
+        self.culled
+    }
+
+    /// Number of quads drawn so far
+    pub fn quads(&self) -> usize {
+        self.quads
+    }
+
+    /// Number of chunks skipped so far for being hidden behind the previous frame
+    pub fn occluded(&self) -> usize {
+        self.occluded
+    }
+
+    /// Returns if the chunk at `offset` may be visible, counts it as drawn or culled
+    fn cull(&mut self, transform: &Matrix4<f32>, offset: Vector3<i32>) -> bool {
+        let size = CHUNK_SIZE as f32 * VOXEL_SIZE;
+        let min = offset.map(|x| x as f32) * size;
+
+        // Voxels span [z - 1, z] on the z axis
+        let corners = [0b000, 0b001, 0b010, 0b011, 0b100, 0b101, 0b110, 0b111].map(|i| {
+            let corner = Vector4::new(
+                min.x + (i & 1) as f32 * size,
+                min.y + (i >> 1 & 1) as f32 * size,
+                min.z + (i >> 2 & 1) as f32 * size - VOXEL_SIZE,
+                1.0,
+            );
+
+            (transform * corner).truncate()
+        });
+
+        let visible = self
+            .frustum
+            .iter()
+            .all(|plane| corners.iter().any(|corner| plane.side(corner)));
+
+        if visible {
+            self.drawn += 1;
+        } else {
+            self.culled += 1;
+        }
+
+        visible
     }
 
     pub fn render_object(&mut self, object: &Object) {
-        let mut pc = PushConstant {
-            transform: [0f32; 4 * 4],
-            offset: [0i32; 3],
+        let eye = local_eye(object.transform(), self.eye);
+
+        for (offset, chunk) in object.chunks() {
+            self.draw(*object.transform(), eye, *offset, chunk);
+        }
+    }
+
+    /// Draws all chunks of the buffer with one indirect draw, `ChunkBuffer::cull` or
+    /// `ChunkBuffer::cull_shadow` for the cascade must have been called for this frame
+    pub fn render_indirect(&mut self, renderer: &Renderer, chunks: &ChunkBuffer) {
+        let Some(pipeline) = &renderer.indirect_pipeline else {
+            return;
         };
 
-        let tmp = unsafe { std::slice::from_raw_parts(object.transform().as_ptr(), 4 * 4) };
+        let scene_pipelines = renderer.scene_pipelines();
 
-        pc.transform[..].copy_from_slice(tmp);
+        let (indirect, draws, regular) = match self.cascade {
+            Some(cascade) => {
+                let Some(draws) = chunks.shadow_draws(cascade) else {
+                    return;
+                };
 
-        for (offset, chunk) in object.chunks() {
-            if let Some(buffer) = chunk.buffer() {
-                pc.offset = [offset.x, offset.y, offset.z];
-                self.pass.set_push_constants(
-                    wgpu::ShaderStages::VERTEX,
-                    0,
-                    bytemuck::cast_slice(&[pc]),
-                );
-
-                /*
-                let position = object.transform().w.truncate()
-                    + offset.cast::<f32>().unwrap() * CHUNK_SIZE as f32 * VOXEL_SIZE;
-
-                let visible = chunk.visible(
-                    &self.renderer.camera().get_eye().to_vec(),
-                    position,
-                    Vector3::zero(),
-                );
-
-                let offsets = chunk.offsets();
-
-                for idx in 0..6 {
-                    if !visible[idx] {
-                        continue;
-                    }
-
-                    let start = offsets[idx] as u32;
-                    let end = *(offsets
-                        .get(idx + 1)
-                        .unwrap_or(&(chunk.quads().unwrap().len() as u16)))
-                        as u32;
-
-                    // Set instance buffer
-                    self.pass.set_vertex_buffer(1, buffer.slice(..));
-
-                    // Draw chunk
-                    self.pass.draw(0..4, start..end);
-                }
-                */
-
-                // Set instance buffer
-                self.pass.set_vertex_buffer(1, buffer.slice(..));
-
-                // Draw chunk
-                self.pass.draw(0..4, 0..chunk.quads().unwrap().len() as u32);
+                (&pipeline.shadow, draws, &renderer.shadow_pipeline)
             }
+            None => {
+                let (drawn, culled, quads, occluded) = chunks.stats();
+
+                self.drawn += drawn;
+                self.culled += culled;
+                self.quads += quads;
+                self.occluded += occluded;
+
+                (
+                    scene_pipelines.indirect.as_ref().unwrap(),
+                    chunks.draws(),
+                    &scene_pipelines.voxel,
+                )
+            }
+        };
+
+        if chunks.draw_count() == 0 {
+            return;
         }
+
+        self.pass.set_pipeline(indirect);
+        self.pass.set_bind_group(1, chunks.render_bind_group(), &[]);
+
+        self.pass.set_vertex_buffer(1, chunks.quads().slice(..));
+        self.pass
+            .set_vertex_buffer(2, chunks.quad_slots().slice(..));
+
+        self.pass.multi_draw_indirect(draws, 0, chunks.draw_count());
+
+        // Objects are drawn with the regular pipeline
+        self.pass.set_pipeline(regular);
     }
 
     pub fn render_chunk(
@@ -84,6 +178,27 @@ impl<'a> VoxelPass<'a> {
         offset: Vector3<i32>,
         chunk: &ChunkMesh,
     ) {
+        let eye = local_eye(&transform, self.eye);
+
+        self.draw(transform, eye, offset, chunk);
+    }
+
+    /// Draws the face groups of a chunk facing the eye, `eye` is in the space of the transform
+    fn draw(
+        &mut self,
+        transform: Matrix4<f32>,
+        eye: Vector3<f32>,
+        offset: Vector3<i32>,
+        chunk: &ChunkMesh,
+    ) {
+        let Some(buffer) = chunk.buffer() else {
+            return;
+        };
+
+        if !self.cull(&transform, offset) {
+            return;
+        }
+
         let mut pc = PushConstant {
             transform: [0f32; 4 * 4],
             offset: [0i32; 3],
@@ -95,18 +210,25 @@ impl<'a> VoxelPass<'a> {
         let tmp = unsafe { std::slice::from_raw_parts(offset.as_ptr(), 3) };
         pc.offset[..].copy_from_slice(tmp);
 
-        if let Some(buffer) = chunk.buffer() {
-            self.pass.set_push_constants(
-                wgpu::ShaderStages::VERTEX,
-                0,
-                bytemuck::cast_slice(&[pc]),
-            );
+        self.pass
+            .set_push_constants(wgpu::ShaderStages::VERTEX, 0, bytemuck::cast_slice(&[pc]));
+
+        // Set instance buffer
+        self.pass.set_vertex_buffer(1, buffer.slice(..));
 
-            // Set instance buffer
-            self.pass.set_vertex_buffer(1, buffer.slice(..));
+        let visible = chunk.visible(&eye, offset);
 
-            // Draw chunk
-            self.pass.draw(0..4, 0..chunk.quads().unwrap().len() as u32);
+        for (direction, visible) in visible.into_iter().enumerate() {
+            let faces = chunk.faces(direction);
+
+            if !visible || faces.is_empty() {
+                continue;
+            }
+
+            self.quads += faces.len();
+
+            // Draw face group
+            self.pass.draw(0..4, faces);
         }
     }
 
@@ -114,3 +236,11 @@ impl<'a> VoxelPass<'a> {
         (self.encoder, self.pass)
     }
 }
+
+/// Eye position in the space of an object transform
+fn local_eye(transform: &Matrix4<f32>, eye: Vector3<f32>) -> Vector3<f32> {
+    let inverse = transform.invert().unwrap_or(Matrix4::identity());
+    let eye = inverse * eye.extend(1.0);
+
+    eye.truncate() / eye.w
+}
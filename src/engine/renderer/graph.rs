@@ -0,0 +1,307 @@
+use super::{camera::Camera, pipeline::resolve::resolve_pipeline};
+use ahash::{HashMap, HashMapExt};
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, Device, RenderPipeline, TextureFormat, TextureView,
+};
+
+/// Size of a transient texture relative to the surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    Full,
+    Half,
+}
+
+/// Texture read or written by the passes of the graph, identified by its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resource {
+    pub name: &'static str,
+    pub format: TextureFormat,
+    pub scale: Scale,
+}
+
+impl Resource {
+    pub const fn new(name: &'static str, format: TextureFormat, scale: Scale) -> Self {
+        Self {
+            name,
+            format,
+            scale,
+        }
+    }
+
+    fn size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        match self.scale {
+            Scale::Full => (width.max(1), height.max(1)),
+            Scale::Half => (width.div_ceil(2).max(1), height.div_ceil(2).max(1)),
+        }
+    }
+}
+
+/// HDR color written by the scene pass
+pub const COLOR: Resource = Resource::new("color", TextureFormat::Rgba16Float, Scale::Full);
+/// Depth written by the scene pass
+pub const DEPTH: Resource = Resource::new("depth", TextureFormat::Depth32Float, Scale::Full);
+/// World space normal written by the scene pass, zero for the sky
+pub const NORMAL: Resource = Resource::new("normal", TextureFormat::Rgba16Float, Scale::Full);
+
+/// Outputs of the scene pass, in the order of its attachments
+pub const SCENE_OUTPUTS: [Resource; 3] = [COLOR, NORMAL, DEPTH];
+/// Color attachments of the scene pass, for its pipelines
+pub const SCENE_TARGETS: [TextureFormat; 2] = [COLOR.format, NORMAL.format];
+
+/// Post-process pass, recorded after the scene pass in the order the passes were added.
+/// The last pass writes the surface
+pub trait GraphPass: Send {
+    fn name(&self) -> &'static str;
+
+    /// Resources read, written by the scene pass or an earlier pass
+    fn inputs(&self) -> Vec<Resource>;
+
+    /// Transient resources written, allocated by the graph
+    fn outputs(&self) -> Vec<Resource>;
+
+    /// Writes the per frame state of the pass, called when a frame starts
+    fn update(&self, _camera: &Camera) {}
+
+    /// Recreates the bind groups of the pass, called whenever the targets were allocated
+    fn prepare(&mut self, device: &Device, targets: &Targets);
+
+    fn record(&self, encoder: &mut CommandEncoder, targets: &Targets, surface: &TextureView);
+}
+
+/// Transient textures of the graph, recreated with the size of the surface
+pub struct Targets {
+    size: (u32, u32),
+    /// Samples per pixel of the scene pass
+    samples: u32,
+    views: HashMap<&'static str, TextureView>,
+    /// Scene outputs drawn with MSAA, resolved into `views` after the scene pass
+    multisampled: HashMap<&'static str, TextureView>,
+}
+
+impl Targets {
+    pub fn view(&self, resource: Resource) -> &TextureView {
+        &self.views[resource.name]
+    }
+
+    pub fn size(&self, resource: Resource) -> (u32, u32) {
+        resource.size(self.size)
+    }
+
+    /// View the scene pass draws an output into, and the view it is resolved to with MSAA
+    pub fn attachment(&self, resource: Resource) -> (&TextureView, Option<&TextureView>) {
+        match self.multisampled.get(resource.name) {
+            Some(view) => (view, Some(self.view(resource))),
+            None => (self.view(resource), None),
+        }
+    }
+
+    fn allocate(&mut self, device: &Device, resource: Resource) {
+        let view = self.create_view(device, resource, 1);
+
+        self.views.insert(resource.name, view);
+
+        if self.samples > 1 && SCENE_OUTPUTS.contains(&resource) {
+            let view = self.create_view(device, resource, self.samples);
+
+            self.multisampled.insert(resource.name, view);
+        }
+    }
+
+    fn create_view(&self, device: &Device, resource: Resource, samples: u32) -> TextureView {
+        let (width, height) = self.size(resource);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("vengine::graph_{}_texture", resource.name)),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: samples,
+            dimension: wgpu::TextureDimension::D2,
+            format: resource.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+}
+
+/// Writes the multisampled depth of the scene pass into `DEPTH`, color outputs are
+/// resolved by the scene pass itself
+struct DepthResolve {
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+    bind_group: Option<BindGroup>,
+}
+
+impl DepthResolve {
+    fn prepare(&mut self, device: &Device, targets: &Targets) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vengine::resolve_bind_group"),
+            layout: &self.layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(targets.attachment(DEPTH).0),
+            }],
+        }));
+    }
+
+    fn record(&self, encoder: &mut CommandEncoder, targets: &Targets) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("vengine::resolve_pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: targets.view(DEPTH),
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, self.bind_group.as_ref().unwrap(), &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// The scene pass followed by a chain of post-process passes, with the textures passed
+/// between them
+pub struct RenderGraph {
+    resources: Vec<Resource>,
+    targets: Targets,
+    /// Resolves the scene depth when the scene pass uses MSAA
+    resolve: Option<DepthResolve>,
+    passes: Vec<Box<dyn GraphPass>>,
+}
+
+impl RenderGraph {
+    /// Graph whose scene pass draws with `samples` samples per pixel
+    pub fn new(device: &Device, size: (u32, u32), samples: u32) -> Self {
+        let mut targets = Targets {
+            size,
+            samples,
+            views: HashMap::new(),
+            multisampled: HashMap::new(),
+        };
+
+        for resource in SCENE_OUTPUTS {
+            targets.allocate(device, resource);
+        }
+
+        let resolve = (samples > 1).then(|| {
+            let (layout, pipeline) = resolve_pipeline(device);
+
+            let mut resolve = DepthResolve {
+                layout,
+                pipeline,
+                bind_group: None,
+            };
+            resolve.prepare(device, &targets);
+
+            resolve
+        });
+
+        Self {
+            resources: SCENE_OUTPUTS.to_vec(),
+            targets,
+            resolve,
+            passes: Vec::new(),
+        }
+    }
+
+    /// Appends a pass to the chain, its inputs must be written by the passes before it
+    pub fn add_pass(&mut self, device: &Device, mut pass: Box<dyn GraphPass>) {
+        for input in pass.inputs() {
+            assert!(
+                self.resources.contains(&input),
+                "{} reads {} before it is written",
+                pass.name(),
+                input.name
+            );
+        }
+
+        for output in pass.outputs() {
+            if !self.resources.contains(&output) {
+                self.targets.allocate(device, output);
+                self.resources.push(output);
+            }
+        }
+
+        pass.prepare(device, &self.targets);
+
+        self.passes.push(pass);
+    }
+
+    /// Reallocates every transient texture with the new size of the surface
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.targets.size = (width, height);
+
+        for resource in &self.resources {
+            self.targets.allocate(device, *resource);
+        }
+
+        if let Some(resolve) = &mut self.resolve {
+            resolve.prepare(device, &self.targets);
+        }
+
+        for pass in &mut self.passes {
+            pass.prepare(device, &self.targets);
+        }
+    }
+
+    pub fn targets(&self) -> &Targets {
+        &self.targets
+    }
+
+    pub fn update(&self, camera: &Camera) {
+        for pass in &self.passes {
+            pass.update(camera);
+        }
+    }
+
+    /// Records the post-process passes, call after the scene pass
+    pub fn execute(&self, encoder: &mut CommandEncoder, surface: &TextureView) {
+        if let Some(resolve) = &self.resolve {
+            resolve.record(encoder, &self.targets);
+        }
+
+        for pass in &self.passes {
+            pass.record(encoder, &self.targets, surface);
+        }
+    }
+}
+
+/// Draws a fullscreen triangle into `view`
+pub fn fullscreen_pass(
+    encoder: &mut CommandEncoder,
+    label: &str,
+    view: &TextureView,
+    pipeline: &RenderPipeline,
+    bind_group: &BindGroup,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
@@ -0,0 +1,150 @@
+use super::pipeline::voxels::IndirectPipeline;
+use wgpu::{BindGroup, CommandEncoder, Device, Texture, TextureView};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Hierarchical depth pyramid, every level holds the farthest depth of the texels of the
+/// level before it. Built from the depth texture to test bounding boxes for occlusion
+pub struct HiZ {
+    texture: Texture,
+    /// All levels, read by the cull pass
+    view: TextureView,
+    /// Writes level 0, bound to the depth texture it was built with
+    copy: Option<(TextureView, BindGroup)>,
+    /// Writes level `n + 1` from level `n`
+    reduce: Vec<BindGroup>,
+}
+
+impl HiZ {
+    pub fn new(device: &Device, pipeline: &IndirectPipeline, width: u32, height: u32) -> Self {
+        let levels = width.max(height).max(1).ilog2() + 1;
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("vengine::hiz_texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let level = |level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        };
+
+        let reduce = (1..levels)
+            .map(|n| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("vengine::hiz_reduce_bind_group"),
+                    layout: &pipeline.hiz_reduce_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&level(n)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&level(n - 1)),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        Self {
+            texture,
+            view,
+            copy: None,
+            reduce,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.texture.width(), self.texture.height())
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Binds the depth texture the pyramid is built from, returns if it changed since the
+    /// last call. The render graph recreates it on resize and when anti aliasing changes
+    pub fn bind(
+        &mut self,
+        device: &Device,
+        pipeline: &IndirectPipeline,
+        depth: &TextureView,
+    ) -> bool {
+        let changed = self.copy.as_ref().is_none_or(|(view, _)| view != depth);
+
+        if changed {
+            let level = self.texture.create_view(&wgpu::TextureViewDescriptor {
+                mip_level_count: Some(1),
+                ..Default::default()
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("vengine::hiz_copy_bind_group"),
+                layout: &pipeline.hiz_copy_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(depth),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&level),
+                    },
+                ],
+            });
+
+            self.copy = Some((depth.clone(), bind_group));
+        }
+
+        changed
+    }
+
+    /// Records the passes building the pyramid from the bound depth texture
+    pub fn build(&self, pipeline: &IndirectPipeline, encoder: &mut CommandEncoder) {
+        let (width, height) = self.size();
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("vengine::hiz_pass"),
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&pipeline.hiz_copy);
+        pass.set_bind_group(0, &self.copy.as_ref().unwrap().1, &[]);
+        pass.dispatch_workgroups(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+
+        pass.set_pipeline(&pipeline.hiz_reduce);
+
+        for (n, bind_group) in self.reduce.iter().enumerate() {
+            let width = (width >> (n + 1)).max(1);
+            let height = (height >> (n + 1)).max(1);
+
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch_workgroups(
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+    }
+}
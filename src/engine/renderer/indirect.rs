@@ -0,0 +1,683 @@
+use super::{hiz::HiZ, pipeline::voxels::IndirectPipeline};
+use crate::engine::{
+    geometry::plane::Plane,
+    voxel::{chunk_mesh::ChunkMesh, quad::Quad},
+};
+use ahash::HashMap;
+use cgmath::{Matrix4, SquareMatrix, Vector3};
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use wgpu::{BindGroup, BindGroupLayout, Buffer, BufferUsages, Device, Queue, TextureView};
+
+/// Quads the quad buffer initially holds
+const INITIAL_QUADS: u32 = 1 << 20;
+/// Chunks the chunk table initially holds
+const INITIAL_SLOTS: u32 = 4096;
+/// Face groups of a chunk, each gets its own indirect draw
+const GROUPS: u32 = 6;
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Chunk table entry, see `shaders/cull.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct ChunkEntry {
+    position: [i32; 3],
+    first: u32,
+    faces: [u32; 7],
+    used: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullUniform {
+    planes: [[f32; 4]; 6],
+    eye: [f32; 4],
+    view_proj: [[f32; 4]; 4],
+    count: u32,
+    occlusion: u32,
+    _padding: [u32; 2],
+}
+
+/// Indirect draws of the chunks inside the light view of a cascade
+struct ShadowView {
+    uniform: Buffer,
+    draws: Buffer,
+    bind_group: BindGroup,
+}
+
+/// First fit allocator of quad ranges
+struct Ranges {
+    /// Free ranges sorted by start, never adjacent
+    free: Vec<Range<u32>>,
+    capacity: u32,
+}
+
+impl Ranges {
+    fn new(capacity: u32) -> Self {
+        Self {
+            free: std::iter::once(0..capacity).collect(),
+            capacity,
+        }
+    }
+
+    fn allocate(&mut self, len: u32) -> Option<Range<u32>> {
+        let i = self
+            .free
+            .iter()
+            .position(|range| range.len() >= len as usize)?;
+
+        let start = self.free[i].start;
+        self.free[i].start += len;
+
+        if self.free[i].is_empty() {
+            self.free.remove(i);
+        }
+
+        Some(start..start + len)
+    }
+
+    fn free(&mut self, range: Range<u32>) {
+        let i = self.free.partition_point(|free| free.start < range.start);
+
+        self.free.insert(i, range);
+
+        // Merge with the following and the preceding range
+        if i + 1 < self.free.len() && self.free[i].end == self.free[i + 1].start {
+            self.free[i].end = self.free.remove(i + 1).end;
+        }
+
+        if i > 0 && self.free[i - 1].end == self.free[i].start {
+            self.free[i - 1].end = self.free.remove(i).end;
+        }
+    }
+
+    fn grow(&mut self, capacity: u32) {
+        self.free(self.capacity..capacity);
+        self.capacity = capacity;
+    }
+}
+
+/// Chunk quads suballocated from one large buffer and drawn with a single
+/// `multi_draw_indirect`, after a compute pass culled the chunks and their face groups
+pub struct ChunkBuffer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    chunk_layout: BindGroupLayout,
+    cull_layout: BindGroupLayout,
+    /// Quads of all chunks
+    quads: Buffer,
+    /// Chunk slot of every quad
+    quad_slots: Buffer,
+    /// Chunk entry of every slot
+    table: Buffer,
+    /// Indirect draws written by the compute pass, `GROUPS` per slot
+    draws: Buffer,
+    uniform: Buffer,
+    stats: Buffer,
+    readback: Buffer,
+    render_bind_group: BindGroup,
+    cull_bind_group: BindGroup,
+    /// Depth pyramid of the previous frame
+    hiz: HiZ,
+    /// View projection of the frame the pyramid is built from
+    previous: Option<Matrix4<f32>>,
+    /// Created when a cascade is first culled, dropped when the slots grow
+    shadow_views: Vec<ShadowView>,
+    /// Stats of the shadow views, never read
+    shadow_stats: Buffer,
+    ranges: Ranges,
+    /// Slots in use or freed, the compute pass runs over all of them
+    slots: u32,
+    slot_capacity: u32,
+    free_slots: Vec<u32>,
+    chunks: HashMap<Vector3<i32>, (u32, Range<u32>)>,
+    readback_ready: Arc<AtomicBool>,
+    readback_pending: bool,
+    /// Drawn chunks, culled chunks, drawn quads and occluded chunks of a recent frame
+    last_stats: [u32; 4],
+}
+
+impl ChunkBuffer {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, pipeline: &IndirectPipeline) -> Self {
+        let buffer = |label, size, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+
+        let quads = buffer(
+            "vengine::indirect_quads",
+            INITIAL_QUADS as u64 * size_of::<Quad>() as u64,
+            BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        );
+        let quad_slots = buffer(
+            "vengine::indirect_quad_slots",
+            INITIAL_QUADS as u64 * size_of::<u32>() as u64,
+            BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        );
+        let table = buffer(
+            "vengine::indirect_table",
+            INITIAL_SLOTS as u64 * size_of::<ChunkEntry>() as u64,
+            BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        );
+        let draws = draws_buffer(&device, INITIAL_SLOTS);
+        let uniform = buffer(
+            "vengine::indirect_cull",
+            size_of::<CullUniform>() as u64,
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        );
+        let stats = buffer(
+            "vengine::indirect_stats",
+            size_of::<[u32; 4]>() as u64,
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        );
+        let shadow_stats = buffer(
+            "vengine::indirect_shadow_stats",
+            size_of::<[u32; 4]>() as u64,
+            BufferUsages::STORAGE,
+        );
+        let readback = buffer(
+            "vengine::indirect_readback",
+            size_of::<[u32; 4]>() as u64,
+            BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        );
+
+        let hiz = HiZ::new(&device, pipeline, 1, 1);
+
+        let (render_bind_group, cull_bind_group) = bind_groups(
+            &device,
+            &pipeline.chunk_layout,
+            &pipeline.cull_layout,
+            [&uniform, &table, &draws, &stats],
+            hiz.view(),
+        );
+
+        Self {
+            device,
+            queue,
+            chunk_layout: pipeline.chunk_layout.clone(),
+            cull_layout: pipeline.cull_layout.clone(),
+            quads,
+            quad_slots,
+            table,
+            draws,
+            uniform,
+            stats,
+            readback,
+            render_bind_group,
+            cull_bind_group,
+            hiz,
+            previous: None,
+            shadow_views: Vec::new(),
+            shadow_stats,
+            ranges: Ranges::new(INITIAL_QUADS),
+            slots: 0,
+            slot_capacity: INITIAL_SLOTS,
+            free_slots: Vec::new(),
+            chunks: HashMap::default(),
+            readback_ready: Arc::new(AtomicBool::new(false)),
+            readback_pending: false,
+            last_stats: [0; 4],
+        }
+    }
+
+    /// Uploads the quads of a chunk, replacing earlier ones
+    pub fn insert(&mut self, chunk_pos: Vector3<i32>, mesh: &ChunkMesh) {
+        self.remove(chunk_pos);
+
+        let quads = mesh.quads().unwrap_or_default();
+
+        if quads.is_empty() {
+            return;
+        }
+
+        let len = quads.len() as u32;
+
+        let range = match self.ranges.allocate(len) {
+            Some(range) => range,
+            None => {
+                self.grow_quads(len);
+                self.ranges.allocate(len).unwrap()
+            }
+        };
+
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None => {
+                if self.slots == self.slot_capacity {
+                    self.grow_slots();
+                }
+
+                self.slots += 1;
+                self.slots - 1
+            }
+        };
+
+        let offsets = mesh.offsets();
+
+        let entry = ChunkEntry {
+            position: chunk_pos.into(),
+            first: range.start,
+            faces: [
+                offsets[0], offsets[1], offsets[2], offsets[3], offsets[4], offsets[5], len,
+            ],
+            used: 1,
+        };
+
+        self.queue.write_buffer(
+            &self.quads,
+            range.start as u64 * size_of::<Quad>() as u64,
+            bytemuck::cast_slice(quads),
+        );
+        self.queue.write_buffer(
+            &self.quad_slots,
+            range.start as u64 * size_of::<u32>() as u64,
+            bytemuck::cast_slice(&vec![slot; quads.len()]),
+        );
+        self.queue.write_buffer(
+            &self.table,
+            slot as u64 * size_of::<ChunkEntry>() as u64,
+            bytemuck::bytes_of(&entry),
+        );
+
+        self.chunks.insert(chunk_pos, (slot, range));
+    }
+
+    pub fn remove(&mut self, chunk_pos: Vector3<i32>) {
+        let Some((slot, range)) = self.chunks.remove(&chunk_pos) else {
+            return;
+        };
+
+        self.queue.write_buffer(
+            &self.table,
+            slot as u64 * size_of::<ChunkEntry>() as u64,
+            bytemuck::bytes_of(&ChunkEntry::default()),
+        );
+
+        self.ranges.free(range);
+        self.free_slots.push(slot);
+    }
+
+    pub fn clear(&mut self) {
+        let chunks: Vec<Vector3<i32>> = self.chunks.keys().copied().collect();
+
+        for chunk_pos in chunks {
+            self.remove(chunk_pos);
+        }
+    }
+
+    /// Device memory used by the uploaded chunks, in bytes
+    pub fn memory_usage(&self) -> usize {
+        let quads: usize = self.chunks.values().map(|(_, range)| range.len()).sum();
+
+        quads * (size_of::<Quad>() + size_of::<u32>())
+            + self.chunks.len() * (size_of::<ChunkEntry>() + GROUPS as usize * 16)
+    }
+
+    /// Writes the indirect draws of the chunks inside the frustum and not hidden behind
+    /// the previous frame, run before the render pass drawing them is submitted.
+    /// `depth` still holds the depth of the previous frame
+    pub fn cull(
+        &mut self,
+        pipeline: &IndirectPipeline,
+        frustum: &[Plane; 6],
+        eye: Vector3<f32>,
+        view_proj: Matrix4<f32>,
+        depth: &TextureView,
+        depth_size: (u32, u32),
+    ) {
+        let _ = self.device.poll(wgpu::Maintain::Poll);
+
+        if self.readback_pending && self.readback_ready.load(Ordering::Acquire) {
+            {
+                let data = self.readback.slice(..).get_mapped_range();
+                let stats: &[u32] = bytemuck::cast_slice(&data);
+
+                self.last_stats.copy_from_slice(&stats[..4]);
+            }
+
+            self.readback.unmap();
+            self.readback_ready.store(false, Ordering::Release);
+            self.readback_pending = false;
+        }
+
+        // The pyramid is rebuilt when the window was resized
+        if self.hiz.size() != depth_size {
+            self.hiz = HiZ::new(&self.device, pipeline, depth_size.0, depth_size.1);
+            self.shadow_views.clear();
+
+            (self.render_bind_group, self.cull_bind_group) = bind_groups(
+                &self.device,
+                &self.chunk_layout,
+                &self.cull_layout,
+                [&self.uniform, &self.table, &self.draws, &self.stats],
+                self.hiz.view(),
+            );
+        }
+
+        // A new depth texture is still empty, occlusion is skipped until a frame was drawn
+        // into it
+        if self.hiz.bind(&self.device, pipeline, depth) {
+            self.previous = None;
+        }
+
+        let occlusion = self.previous.is_some();
+
+        let uniform = CullUniform {
+            planes: frustum.each_ref().map(|plane| plane.coefficients().into()),
+            eye: eye.extend(1.0).into(),
+            view_proj: self.previous.unwrap_or(view_proj).into(),
+            count: self.slots,
+            occlusion: occlusion as u32,
+            _padding: [0; 2],
+        };
+
+        self.previous = Some(view_proj);
+
+        self.queue
+            .write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("vengine::indirect_cull_encoder"),
+            });
+
+        encoder.clear_buffer(&self.stats, 0, None);
+
+        if occlusion {
+            self.hiz.build(pipeline, &mut encoder);
+        }
+
+        self.dispatch(pipeline, &mut encoder, &self.cull_bind_group);
+
+        // Only one readback is in flight, the stats lag a few frames behind
+        let read = !self.readback_pending;
+
+        if read {
+            encoder.copy_buffer_to_buffer(&self.stats, 0, &self.readback, 0, self.stats.size());
+        }
+
+        self.queue.submit([encoder.finish()]);
+
+        if read {
+            let ready = self.readback_ready.clone();
+
+            self.readback
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    if result.is_ok() {
+                        ready.store(true, Ordering::Release);
+                    }
+                });
+
+            self.readback_pending = true;
+        }
+    }
+
+    /// Writes the indirect draws of the chunks inside the light view of a cascade
+    pub fn cull_shadow(
+        &mut self,
+        pipeline: &IndirectPipeline,
+        cascade: u32,
+        frustum: &[Plane; 6],
+        eye: Vector3<f32>,
+    ) {
+        while self.shadow_views.len() <= cascade as usize {
+            let uniform = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("vengine::indirect_shadow_cull"),
+                size: size_of::<CullUniform>() as u64,
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let draws = draws_buffer(&self.device, self.slot_capacity);
+
+            let bind_group = cull_bind_group(
+                &self.device,
+                &self.cull_layout,
+                [&uniform, &self.table, &draws, &self.shadow_stats],
+                self.hiz.view(),
+            );
+
+            self.shadow_views.push(ShadowView {
+                uniform,
+                draws,
+                bind_group,
+            });
+        }
+
+        let view = &self.shadow_views[cascade as usize];
+
+        let uniform = CullUniform {
+            planes: frustum.each_ref().map(|plane| plane.coefficients().into()),
+            eye: eye.extend(1.0).into(),
+            view_proj: Matrix4::identity().into(),
+            count: self.slots,
+            occlusion: 0,
+            _padding: [0; 2],
+        };
+
+        self.queue
+            .write_buffer(&view.uniform, 0, bytemuck::bytes_of(&uniform));
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("vengine::indirect_shadow_cull_encoder"),
+            });
+
+        self.dispatch(pipeline, &mut encoder, &view.bind_group);
+
+        self.queue.submit([encoder.finish()]);
+    }
+
+    /// Drawn chunks, culled chunks, drawn quads and occluded chunks of a recent frame
+    pub fn stats(&self) -> (usize, usize, usize, usize) {
+        (
+            self.last_stats[0] as usize,
+            self.last_stats[1] as usize,
+            self.last_stats[2] as usize,
+            self.last_stats[3] as usize,
+        )
+    }
+
+    pub fn quads(&self) -> &Buffer {
+        &self.quads
+    }
+
+    pub fn quad_slots(&self) -> &Buffer {
+        &self.quad_slots
+    }
+
+    pub fn draws(&self) -> &Buffer {
+        &self.draws
+    }
+
+    /// Indirect draws of a cascade, if it was culled
+    pub fn shadow_draws(&self, cascade: u32) -> Option<&Buffer> {
+        self.shadow_views
+            .get(cascade as usize)
+            .map(|view| &view.draws)
+    }
+
+    /// Number of indirect draws written by the compute pass
+    pub fn draw_count(&self) -> u32 {
+        self.slots * GROUPS
+    }
+
+    pub fn render_bind_group(&self) -> &BindGroup {
+        &self.render_bind_group
+    }
+
+    /// Records the compute pass writing the indirect draws of a view
+    fn dispatch(
+        &self,
+        pipeline: &IndirectPipeline,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &BindGroup,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("vengine::indirect_cull_pass"),
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&pipeline.cull);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(self.slots.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Replaces the quad buffers by larger ones holding at least `len` more quads
+    fn grow_quads(&mut self, len: u32) {
+        let capacity = (self.ranges.capacity * 2).max(self.ranges.capacity + len);
+
+        self.quads = self.grow(&self.quads, capacity as u64 * size_of::<Quad>() as u64);
+        self.quad_slots = self.grow(&self.quad_slots, capacity as u64 * size_of::<u32>() as u64);
+
+        self.ranges.grow(capacity);
+    }
+
+    /// Doubles the chunk table and the indirect draws
+    fn grow_slots(&mut self) {
+        self.slot_capacity *= 2;
+
+        self.table = self.grow(
+            &self.table,
+            self.slot_capacity as u64 * size_of::<ChunkEntry>() as u64,
+        );
+
+        // Rewritten by every compute pass
+        self.draws = draws_buffer(&self.device, self.slot_capacity);
+        self.shadow_views.clear();
+
+        (self.render_bind_group, self.cull_bind_group) = bind_groups(
+            &self.device,
+            &self.chunk_layout,
+            &self.cull_layout,
+            [&self.uniform, &self.table, &self.draws, &self.stats],
+            self.hiz.view(),
+        );
+    }
+
+    /// Creates a larger buffer with the same usage holding the contents of `buffer`
+    fn grow(&self, buffer: &Buffer, size: u64) -> Buffer {
+        let grown = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: buffer.usage(),
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("vengine::indirect_grow_encoder"),
+            });
+
+        encoder.copy_buffer_to_buffer(buffer, 0, &grown, 0, buffer.size());
+
+        self.queue.submit([encoder.finish()]);
+
+        grown
+    }
+}
+
+/// Bind groups of the vertex shader and of the compute pass
+fn bind_groups(
+    device: &Device,
+    chunk_layout: &BindGroupLayout,
+    cull_layout: &BindGroupLayout,
+    [uniform, table, draws, stats]: [&Buffer; 4],
+    hiz: &TextureView,
+) -> (BindGroup, BindGroup) {
+    let render = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("vengine::indirect_chunk_bind_group"),
+        layout: chunk_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: table.as_entire_binding(),
+        }],
+    });
+
+    let cull = cull_bind_group(device, cull_layout, [uniform, table, draws, stats], hiz);
+
+    (render, cull)
+}
+
+fn cull_bind_group(
+    device: &Device,
+    cull_layout: &BindGroupLayout,
+    [uniform, table, draws, stats]: [&Buffer; 4],
+    hiz: &TextureView,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("vengine::indirect_cull_bind_group"),
+        layout: cull_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: table.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: draws.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: stats.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(hiz),
+            },
+        ],
+    })
+}
+
+/// Indirect draws written by the compute pass, `GROUPS` per slot
+fn draws_buffer(device: &Device, slots: u32) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("vengine::indirect_draws"),
+        size: slots as u64 * GROUPS as u64 * size_of::<[u32; 4]>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::INDIRECT,
+        mapped_at_creation: false,
+    })
+}
+
+#[test]
+fn test_ranges() {
+    let mut ranges = Ranges::new(16);
+
+    let a = ranges.allocate(4).unwrap();
+    let b = ranges.allocate(8).unwrap();
+    let c = ranges.allocate(4).unwrap();
+
+    assert_eq!((a.clone(), b.clone(), c.clone()), (0..4, 4..12, 12..16));
+    assert_eq!(ranges.allocate(1), None);
+
+    ranges.free(a);
+    ranges.free(c);
+
+    // Neither free range is large enough
+    assert_eq!(ranges.allocate(6), None);
+
+    ranges.free(b);
+
+    assert_eq!(ranges.free.len(), 1);
+    assert_eq!(ranges.free[0], 0..16);
+
+    ranges.grow(32);
+
+    assert_eq!(ranges.allocate(24), Some(0..24));
+}
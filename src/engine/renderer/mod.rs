@@ -1,7 +1,14 @@
+pub mod antialiasing;
 pub mod backend;
 pub mod camera;
 pub mod frame;
+pub mod graph;
+pub mod hiz;
+pub mod indirect;
 pub mod pipeline;
+pub mod present;
 #[allow(clippy::module_inception)]
 pub mod renderer;
-pub mod texture;
+pub mod shadow;
+pub mod sky;
+pub mod ssao;
@@ -0,0 +1,41 @@
+use wgpu::{BindGroupLayout, Device, RenderPipeline};
+
+use crate::engine::renderer::{antialiasing::ANTIALIASED, pipeline::fullscreen_pipeline};
+
+/// Smooths the edges of a color target, see `shaders/fxaa.wgsl`
+pub fn fxaa_pipeline(device: &Device) -> (BindGroupLayout, RenderPipeline) {
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::fxaa_layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    });
+
+    let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/fxaa.wgsl"));
+
+    let pipeline = fullscreen_pipeline(
+        device,
+        "vengine::fxaa_pipeline",
+        &layout,
+        &shader,
+        "fs_main",
+        ANTIALIASED.format,
+    );
+
+    (layout, pipeline)
+}
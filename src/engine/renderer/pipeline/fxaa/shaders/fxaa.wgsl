@@ -0,0 +1,79 @@
+// Fast approximate anti-aliasing, blurs along the edges found in the luma of the color
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var filtering: sampler;
+
+// Contrast below which a pixel isn't treated as an edge, relative to the brightest luma
+const EDGE_THRESHOLD: f32 = 0.125;
+// Contrast below which a dark pixel isn't treated as an edge
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+const REDUCE_MUL: f32 = 0.125;
+const REDUCE_MIN: f32 = 0.0078125;
+// Longest blur along an edge, in texels
+const SPAN_MAX: f32 = 8.0;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Perceived brightness of the displayed color
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(sqrt(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0))), vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn color_at(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(source, filtering, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    let uv = in.position.xy * texel;
+
+    let center = color_at(uv);
+
+    let m = luma(center);
+    let nw = luma(color_at(uv + vec2<f32>(-1.0, -1.0) * texel));
+    let ne = luma(color_at(uv + vec2<f32>(1.0, -1.0) * texel));
+    let sw = luma(color_at(uv + vec2<f32>(-1.0, 1.0) * texel));
+    let se = luma(color_at(uv + vec2<f32>(1.0, 1.0) * texel));
+
+    let lowest = min(m, min(min(nw, ne), min(sw, se)));
+    let highest = max(m, max(max(nw, ne), max(sw, se)));
+
+    if (highest - lowest < max(EDGE_THRESHOLD_MIN, highest * EDGE_THRESHOLD)) {
+        return vec4<f32>(center, 1.0);
+    }
+
+    // Perpendicular to the gradient, along the edge
+    var direction = vec2<f32>(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+
+    let reduce = max((nw + ne + sw + se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+
+    direction = clamp(direction * scale, vec2<f32>(-SPAN_MAX), vec2<f32>(SPAN_MAX)) * texel;
+
+    let inner = 0.5 * (color_at(uv + direction * (1.0 / 3.0 - 0.5)) + color_at(uv + direction * (2.0 / 3.0 - 0.5)));
+    let outer = inner * 0.5 + 0.25 * (color_at(uv - direction * 0.5) + color_at(uv + direction * 0.5));
+
+    // The wider blur crossed another edge
+    let outer_luma = luma(outer);
+
+    if (outer_luma < lowest || outer_luma > highest) {
+        return vec4<f32>(inner, 1.0);
+    }
+
+    return vec4<f32>(outer, 1.0);
+}
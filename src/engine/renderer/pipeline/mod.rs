@@ -1 +1,76 @@
+use wgpu::{BindGroupLayout, Device, RenderPipeline, ShaderModule, TextureFormat};
+
+use crate::engine::renderer::sky::Sky;
+use sky::sky_pipeline;
+use voxels::{indirect_render_pipeline, voxel_pipeline, IndirectPipeline};
+
+pub mod fxaa;
+pub mod present;
+pub mod resolve;
+pub mod sky;
+pub mod ssao;
 pub mod voxels;
+
+/// Pipelines drawing into the scene pass, recreated when its sample count changes
+pub struct ScenePipelines {
+    pub voxel: RenderPipeline,
+    pub sky: RenderPipeline,
+    /// Indirect chunk pipeline, if supported by the adapter
+    pub indirect: Option<RenderPipeline>,
+}
+
+pub fn scene_pipelines(
+    device: &Device,
+    scene_layout: &BindGroupLayout,
+    sky: &Sky,
+    indirect: Option<&IndirectPipeline>,
+    samples: u32,
+) -> ScenePipelines {
+    ScenePipelines {
+        voxel: voxel_pipeline(device, scene_layout, samples),
+        sky: sky_pipeline(device, sky, samples),
+        indirect: indirect.map(|pipeline| indirect_render_pipeline(device, pipeline, samples)),
+    }
+}
+
+/// Pipeline drawing a fullscreen triangle without depth into one target
+pub fn fullscreen_pipeline(
+    device: &Device,
+    label: &str,
+    layout: &BindGroupLayout,
+    shader: &ShaderModule,
+    entry_point: &str,
+    format: TextureFormat,
+) -> RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(entry_point),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
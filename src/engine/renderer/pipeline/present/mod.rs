@@ -0,0 +1,36 @@
+use wgpu::{BindGroupLayout, Device, RenderPipeline, TextureFormat};
+
+use crate::engine::renderer::pipeline::fullscreen_pipeline;
+
+/// Copies an HDR target onto the surface, see `shaders/present.wgsl`
+pub fn present_pipeline(
+    device: &Device,
+    format: TextureFormat,
+) -> (BindGroupLayout, RenderPipeline) {
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::present_layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }],
+    });
+
+    let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/present.wgsl"));
+
+    let pipeline = fullscreen_pipeline(
+        device,
+        "vengine::present_pipeline",
+        &layout,
+        &shader,
+        "fs_main",
+        format,
+    );
+
+    (layout, pipeline)
+}
@@ -0,0 +1,36 @@
+// Copies the final HDR color onto the surface, tonemapped into the displayable range
+
+// Colors up to the knee are shown as they are, brighter ones are compressed towards 1,
+// so bright sun and sky keep some detail instead of clipping
+const KNEE: f32 = 0.8;
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    let over = max(color - KNEE, vec3<f32>(0.0)) / (1.0 - KNEE);
+    let shoulder = KNEE + (1.0 - KNEE) * (1.0 - exp(-over));
+
+    return select(max(color, vec3<f32>(0.0)), shoulder, color > vec3<f32>(KNEE));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(source, vec2<i32>(in.position.xy), 0);
+
+    return vec4<f32>(tonemap(color.rgb), 1.0);
+}
@@ -0,0 +1,32 @@
+// Resolves the multisampled depth of the scene pass to the farthest sample of each pixel,
+// read by the Hi-Z pyramid and the post-process passes
+
+@group(0) @binding(0)
+var depth: texture_depth_multisampled_2d;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @builtin(frag_depth) f32 {
+    let pixel = vec2<i32>(in.position.xy);
+
+    var farthest = 0.0;
+
+    for (var i = 0u; i < textureNumSamples(depth); i++) {
+        farthest = max(farthest, textureLoad(depth, pixel, i32(i)));
+    }
+
+    return farthest;
+}
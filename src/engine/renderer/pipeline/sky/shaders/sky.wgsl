@@ -0,0 +1,64 @@
+// Procedural sky, drawn as one triangle covering the screen before the voxels
+
+struct Sky {
+    // View direction and the offsets to the right and top edge of the view
+    forward: vec4<f32>,
+    right: vec4<f32>,
+    up: vec4<f32>,
+    eye: vec4<f32>,
+    // Direction towards the sun, or the moon at night
+    sun: vec4<f32>,
+    zenith: vec4<f32>,
+    horizon: vec4<f32>,
+    // Fog start and density, see `Fog`
+    fog: vec4<f32>,
+    // Color of the sun or moon light and the ambient light
+    light: vec4<f32>,
+    // Color of the sun or moon disc
+    disc: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> sky: Sky;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+
+    return out;
+}
+
+// Attachments of the scene pass, see `SCENE_OUTPUTS`
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let direction = normalize(sky.forward.xyz + in.ndc.x * sky.right.xyz + in.ndc.y * sky.up.xyz);
+
+    var color = mix(sky.horizon.rgb, sky.zenith.rgb, pow(clamp(direction.y, 0.0, 1.0), 0.6));
+
+    // Slightly darker below the horizon
+    color *= 1.0 - 0.3 * clamp(-direction.y * 4.0, 0.0, 1.0);
+
+    // Sun or moon disc with a glow around it
+    let sun = max(dot(direction, sky.sun.xyz), 0.0);
+
+    color += sky.disc.rgb * (smoothstep(0.9990, 0.9995, sun) + 0.25 * pow(sun, 64.0));
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(color, 1.0);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
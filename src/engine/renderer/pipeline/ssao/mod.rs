@@ -0,0 +1,111 @@
+use wgpu::{BindGroupLayout, Device, RenderPipeline};
+
+use crate::engine::renderer::{
+    pipeline::fullscreen_pipeline,
+    ssao::{OCCLUSION, SHADED},
+};
+
+/// Pipelines of the ambient occlusion, see `shaders/ssao.wgsl`
+pub struct SsaoPipeline {
+    /// Uniform, depth and normal texture
+    pub ao_layout: BindGroupLayout,
+    /// Unblurred occlusion
+    pub blur_layout: BindGroupLayout,
+    /// Uniform, scene color, blurred occlusion and its sampler
+    pub composite_layout: BindGroupLayout,
+    pub ao: RenderPipeline,
+    pub blur: RenderPipeline,
+    pub composite: RenderPipeline,
+}
+
+pub fn ssao_pipeline(device: &Device) -> SsaoPipeline {
+    let uniform = wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+
+    let ao_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::ssao_ao_layout"),
+        entries: &[
+            uniform,
+            texture(1, wgpu::TextureSampleType::Depth),
+            texture(2, wgpu::TextureSampleType::Float { filterable: false }),
+        ],
+    });
+
+    let blur_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::ssao_blur_layout"),
+        entries: &[texture(
+            3,
+            wgpu::TextureSampleType::Float { filterable: false },
+        )],
+    });
+
+    let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::ssao_composite_layout"),
+        entries: &[
+            uniform,
+            texture(4, wgpu::TextureSampleType::Float { filterable: false }),
+            texture(5, wgpu::TextureSampleType::Float { filterable: true }),
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    });
+
+    let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/ssao.wgsl"));
+
+    let ao = fullscreen_pipeline(
+        device,
+        "vengine::ssao_ao_pipeline",
+        &ao_layout,
+        &shader,
+        "fs_ao",
+        OCCLUSION.format,
+    );
+    let blur = fullscreen_pipeline(
+        device,
+        "vengine::ssao_blur_pipeline",
+        &blur_layout,
+        &shader,
+        "fs_blur",
+        OCCLUSION.format,
+    );
+    let composite = fullscreen_pipeline(
+        device,
+        "vengine::ssao_composite_pipeline",
+        &composite_layout,
+        &shader,
+        "fs_composite",
+        SHADED.format,
+    );
+
+    SsaoPipeline {
+        ao_layout,
+        blur_layout,
+        composite_layout,
+        ao,
+        blur,
+        composite,
+    }
+}
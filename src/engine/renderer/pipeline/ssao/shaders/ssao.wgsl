@@ -0,0 +1,135 @@
+// Screen-space ambient occlusion: occlusion at half resolution (`fs_ao`), a 4x4 blur
+// (`fs_blur`) and the composite over the scene color (`fs_composite`)
+
+struct Ssao {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    // Radius, depth bias, intensity and sample count
+    params: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> ssao: Ssao;
+// Read by `fs_ao`
+@group(0) @binding(1)
+var depth: texture_depth_2d;
+@group(0) @binding(2)
+var normals: texture_2d<f32>;
+// Read by `fs_blur`
+@group(0) @binding(3)
+var occlusion: texture_2d<f32>;
+// Read by `fs_composite`
+@group(0) @binding(4)
+var scene: texture_2d<f32>;
+@group(0) @binding(5)
+var blurred: texture_2d<f32>;
+@group(0) @binding(6)
+var filtering: sampler;
+
+const TAU: f32 = 6.28318530718;
+const GOLDEN_ANGLE: f32 = 2.39996322973;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// Fullscreen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Position in view space of a pixel of the depth texture
+fn view_position(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(depth));
+    let clamped = clamp(pixel, vec2<i32>(0), size - 1);
+
+    let uv = (vec2<f32>(clamped) + 0.5) / vec2<f32>(size);
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, textureLoad(depth, clamped, 0), 1.0);
+
+    let position = ssao.inv_proj * ndc;
+    return position.xyz / position.w;
+}
+
+
+@fragment
+fn fs_ao(in: VertexOutput) -> @location(0) vec4<f32> {
+    let half = vec2<i32>(in.position.xy);
+    let pixel = min(half * 2, vec2<i32>(textureDimensions(depth)) - 1);
+
+    // Sky
+    if (textureLoad(depth, pixel, 0) >= 1.0) {
+        return vec4<f32>(1.0);
+    }
+
+    let position = view_position(pixel);
+    let normal = normalize((ssao.view * vec4<f32>(textureLoad(normals, pixel, 0).xyz, 0.0)).xyz);
+
+    let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(normal.x) > 0.9);
+    let tangent = normalize(cross(helper, normal));
+    let bitangent = cross(normal, tangent);
+
+    // Rotation repeating every 4x4 pixels, removed again by the blur
+    let rotation = (f32((half.x & 3) + (half.y & 3) * 4) + 0.5) / 16.0 * TAU;
+
+    let radius = ssao.params.x;
+    let bias = ssao.params.y;
+    let count = u32(ssao.params.w);
+    let size = vec2<f32>(textureDimensions(depth));
+
+    var occluded = 0.0;
+
+    for (var i = 0u; i < count; i++) {
+        // Spiral over the hemisphere around the normal, denser towards it
+        let t = (f32(i) + 0.5) / f32(count);
+        let angle = f32(i) * GOLDEN_ANGLE + rotation;
+        let ring = sqrt(t);
+        let direction = tangent * (ring * cos(angle)) + bitangent * (ring * sin(angle)) + normal * sqrt(1.0 - t);
+
+        // More samples close to the position
+        let scale = mix(0.1, 1.0, pow(fract(f32(i) * 0.618034), 2.0));
+        let point = position + direction * (radius * scale);
+
+        let clip = ssao.proj * vec4<f32>(point, 1.0);
+        let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+        let occluder = view_position(vec2<i32>(uv * size));
+
+        // Occluders farther than the radius from the position don't count
+        let range = smoothstep(0.0, 1.0, radius / abs(position.z - occluder.z));
+
+        occluded += select(0.0, range, occluder.z >= point.z + bias);
+    }
+
+    return vec4<f32>(1.0 - occluded / f32(max(count, 1u)));
+}
+
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let size = vec2<i32>(textureDimensions(occlusion));
+
+    var sum = 0.0;
+
+    for (var y = -2; y < 2; y++) {
+        for (var x = -2; x < 2; x++) {
+            sum += textureLoad(occlusion, clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1), 0).r;
+        }
+    }
+
+    return vec4<f32>(sum / 16.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(scene, vec2<i32>(in.position.xy), 0);
+
+    let uv = in.position.xy / vec2<f32>(textureDimensions(scene));
+    let ao = textureSample(blurred, filtering, uv).r;
+
+    return vec4<f32>(color.rgb * mix(1.0, ao, ssao.params.z), 1.0);
+}
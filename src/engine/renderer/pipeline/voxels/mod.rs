@@ -1,41 +1,323 @@
-use wgpu::{Device, RenderPipeline, TextureFormat};
+use wgpu::{
+    BindGroupLayout, ComputePipeline, Device, PipelineLayout, RenderPipeline, TextureFormat,
+};
 
 use crate::engine::{
-    renderer::{camera::Camera, texture::Texture},
+    renderer::{
+        camera::Camera,
+        graph::{DEPTH, SCENE_TARGETS},
+        shadow::SHADOW_FORMAT,
+    },
     voxel::quad::Quad,
 };
 
-pub fn voxel_pipeline(device: &Device, camera: &Camera, format: TextureFormat) -> RenderPipeline {
+const PUSH_CONSTANT_RANGE: wgpu::PushConstantRange = wgpu::PushConstantRange {
+    stages: wgpu::ShaderStages::VERTEX,
+    range: 0..(size_of::<[f32; 4 * 4]>() + size_of::<[i32; 3]>()) as u32,
+};
+
+/// Draws voxels into the scene pass with `samples` samples per pixel
+pub fn voxel_pipeline(
+    device: &Device,
+    scene_layout: &BindGroupLayout,
+    samples: u32,
+) -> RenderPipeline {
     let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("vengine::voxel_pipeline_layout"),
+        bind_group_layouts: &[scene_layout],
+        push_constant_ranges: &[PUSH_CONSTANT_RANGE],
+    });
+
+    let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/base.wgsl"));
+
+    render_pipeline(
+        device,
+        "vengine::voxel_pipeline",
+        &render_pipeline_layout,
+        &shader,
+        "vs_main",
+        &[vertex_desc(), instance_desc()],
+        &SCENE_TARGETS,
+        samples,
+    )
+}
+
+/// Draws voxels into a shadow map, the view projection of the cascade is bound like the camera
+pub fn shadow_pipeline(device: &Device, camera: &Camera) -> RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("vengine::shadow_pipeline_layout"),
         bind_group_layouts: &[camera.bind_group_layout()],
-        push_constant_ranges: &[wgpu::PushConstantRange {
-            stages: wgpu::ShaderStages::VERTEX,
-            range: 0..(size_of::<[f32; 4 * 4]>() + size_of::<[i32; 3]>()) as u32,
-        }],
+        push_constant_ranges: &[PUSH_CONSTANT_RANGE],
     });
 
     let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/base.wgsl"));
 
+    render_pipeline(
+        device,
+        "vengine::shadow_pipeline",
+        &layout,
+        &shader,
+        "vs_main",
+        &[vertex_desc(), instance_desc()],
+        &[],
+        1,
+    )
+}
+
+/// Pipelines drawing the chunks of a `ChunkBuffer` with indirect draws written by a
+/// culling compute pass
+pub struct IndirectPipeline {
+    /// Chunk table read by the vertex shader
+    pub chunk_layout: BindGroupLayout,
+    /// Cull uniform, chunk table, indirect draws, stats and Hi-Z pyramid used by the
+    /// compute pass
+    pub cull_layout: BindGroupLayout,
+    /// Depth texture and first Hi-Z level
+    pub hiz_copy_layout: BindGroupLayout,
+    /// Hi-Z level and the level before it
+    pub hiz_reduce_layout: BindGroupLayout,
+    /// Scene and chunk table, the scene pipeline is created with `indirect_render_pipeline`
+    /// for the sample count of the scene pass
+    pub render_layout: PipelineLayout,
+    /// Draws the chunks into a shadow map
+    pub shadow: RenderPipeline,
+    pub cull: ComputePipeline,
+    pub hiz_copy: ComputePipeline,
+    pub hiz_reduce: ComputePipeline,
+}
+
+pub fn indirect_pipeline(
+    device: &Device,
+    camera: &Camera,
+    scene_layout: &BindGroupLayout,
+) -> IndirectPipeline {
+    let storage = |binding, visibility, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+
+    let hiz = wgpu::TextureSampleType::Float { filterable: false };
+
+    let chunk_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::indirect_chunk_layout"),
+        entries: &[storage(0, wgpu::ShaderStages::VERTEX, true)],
+    });
+
+    let cull_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::indirect_cull_layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            storage(1, wgpu::ShaderStages::COMPUTE, true),
+            storage(2, wgpu::ShaderStages::COMPUTE, false),
+            storage(3, wgpu::ShaderStages::COMPUTE, false),
+            texture(4, hiz),
+        ],
+    });
+
+    let level = wgpu::BindGroupLayoutEntry {
+        binding: 1,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: wgpu::TextureFormat::R32Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+    };
+
+    let hiz_copy_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::hiz_copy_layout"),
+        entries: &[texture(0, wgpu::TextureSampleType::Depth), level],
+    });
+
+    let hiz_reduce_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::hiz_reduce_layout"),
+        entries: &[level, texture(2, hiz)],
+    });
+
+    let render_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("vengine::indirect_pipeline_layout"),
+        bind_group_layouts: &[scene_layout, &chunk_layout],
+        push_constant_ranges: &[],
+    });
+
+    let shadow_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("vengine::indirect_shadow_pipeline_layout"),
+        bind_group_layouts: &[camera.bind_group_layout(), &chunk_layout],
+        push_constant_ranges: &[],
+    });
+
+    let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/base.wgsl"));
+
+    let shadow = render_pipeline(
+        device,
+        "vengine::indirect_shadow_pipeline",
+        &shadow_layout,
+        &shader,
+        "vs_indirect",
+        &[vertex_desc(), instance_desc(), slot_desc()],
+        &[],
+        1,
+    );
+
+    let cull_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("vengine::cull_pipeline_layout"),
+        bind_group_layouts: &[&cull_layout],
+        push_constant_ranges: &[],
+    });
+
+    let cull_shader = device.create_shader_module(wgpu::include_wgsl!("shaders/cull.wgsl"));
+
+    let cull = compute_pipeline(
+        device,
+        "vengine::cull_pipeline",
+        &cull_pipeline_layout,
+        &cull_shader,
+        "cs_main",
+    );
+
+    let hiz_shader = device.create_shader_module(wgpu::include_wgsl!("shaders/hiz.wgsl"));
+
+    let hiz_copy = compute_pipeline(
+        device,
+        "vengine::hiz_copy_pipeline",
+        &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("vengine::hiz_copy_pipeline_layout"),
+            bind_group_layouts: &[&hiz_copy_layout],
+            push_constant_ranges: &[],
+        }),
+        &hiz_shader,
+        "cs_copy",
+    );
+
+    let hiz_reduce = compute_pipeline(
+        device,
+        "vengine::hiz_reduce_pipeline",
+        &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("vengine::hiz_reduce_pipeline_layout"),
+            bind_group_layouts: &[&hiz_reduce_layout],
+            push_constant_ranges: &[],
+        }),
+        &hiz_shader,
+        "cs_reduce",
+    );
+
+    IndirectPipeline {
+        chunk_layout,
+        cull_layout,
+        hiz_copy_layout,
+        hiz_reduce_layout,
+        render_layout,
+        shadow,
+        cull,
+        hiz_copy,
+        hiz_reduce,
+    }
+}
+
+/// Draws the chunks into the scene pass with `samples` samples per pixel
+pub fn indirect_render_pipeline(
+    device: &Device,
+    pipeline: &IndirectPipeline,
+    samples: u32,
+) -> RenderPipeline {
+    let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/base.wgsl"));
+
+    render_pipeline(
+        device,
+        "vengine::indirect_pipeline",
+        &pipeline.render_layout,
+        &shader,
+        "vs_indirect",
+        &[vertex_desc(), instance_desc(), slot_desc()],
+        &SCENE_TARGETS,
+        samples,
+    )
+}
+
+fn compute_pipeline(
+    device: &Device,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+) -> ComputePipeline {
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        module: shader,
+        entry_point: Some(entry_point),
+        compilation_options: wgpu::PipelineCompilationOptions::default(),
+        cache: None,
+    })
+}
+
+/// Without color targets the pipeline only writes the depth of a shadow map
+#[allow(clippy::too_many_arguments)]
+fn render_pipeline(
+    device: &Device,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    buffers: &[wgpu::VertexBufferLayout],
+    targets: &[TextureFormat],
+    samples: u32,
+) -> RenderPipeline {
+    let scene = !targets.is_empty();
+
+    let targets = targets
+        .iter()
+        .map(|&format| {
+            Some(wgpu::ColorTargetState {
+                // 4.
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        })
+        .collect::<Vec<_>>();
+
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("vengine::voxel_pipeline"),
-        layout: Some(&render_pipeline_layout),
+        label: Some(label),
+        layout: Some(layout),
         vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: Some("vs_main"),               // 1.
-            buffers: &[vertex_desc(), instance_desc()], // 2.
+            module: shader,
+            entry_point: Some(entry_point), // 1.
+            buffers,                        // 2.
             compilation_options: wgpu::PipelineCompilationOptions::default(),
         },
-        fragment: Some(wgpu::FragmentState {
+        fragment: scene.then(|| wgpu::FragmentState {
             // 3.
-            module: &shader,
+            module: shader,
             entry_point: Some("fs_main"),
-            targets: &[Some(wgpu::ColorTargetState {
-                // 4.
-                format,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
+            targets: &targets,
             compilation_options: wgpu::PipelineCompilationOptions::default(),
         }),
         primitive: wgpu::PrimitiveState {
@@ -50,15 +332,29 @@ pub fn voxel_pipeline(device: &Device, camera: &Camera, format: TextureFormat) -
             // Requires Features::CONSERVATIVE_RASTERIZATION
             conservative: false,
         },
-        depth_stencil: Some(wgpu::DepthStencilState {
-            format: Texture::DEPTH_FORMAT,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less, // 1.
-            stencil: wgpu::StencilState::default(),     // 2.
-            bias: wgpu::DepthBiasState::default(),
+        depth_stencil: Some(match scene {
+            true => wgpu::DepthStencilState {
+                format: DEPTH.format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less, // 1.
+                stencil: wgpu::StencilState::default(),     // 2.
+                bias: wgpu::DepthBiasState::default(),
+            },
+            // Slope scaled bias against shadow acne on faces at a grazing angle to the sun
+            false => wgpu::DepthStencilState {
+                format: SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            },
         }),
         multisample: wgpu::MultisampleState {
-            count: 1,                         // 2.
+            count: samples,                   // 2.
             mask: !0,                         // 3.
             alpha_to_coverage_enabled: false, // 4.
         },
@@ -97,3 +393,16 @@ pub fn instance_desc() -> wgpu::VertexBufferLayout<'static> {
         ],
     }
 }
+
+/// Chunk slot of every quad in a `ChunkBuffer`
+pub fn slot_desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 3,
+            format: wgpu::VertexFormat::Uint32,
+        }],
+    }
+}
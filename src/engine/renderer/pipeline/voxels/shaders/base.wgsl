@@ -5,6 +5,48 @@ struct CameraUniform {
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+// Sun light, see `Shadows`. Light, shadow maps and sky are only bound in the scene pass,
+// the shadow passes bind the view projection of a cascade at the place of the camera
+struct Light {
+    // Direction towards the sun, or the moon at night
+    sun: vec4<f32>,
+    eye: vec4<f32>,
+    forward: vec4<f32>,
+    cascades: array<mat4x4<f32>, 4>,
+    // Distance along the view direction up to which each cascade is used
+    splits: vec4<f32>,
+    // Size of a shadow map texel of each cascade in world units
+    texels: vec4<f32>,
+    count: u32,
+    // Size of a shadow map texel in texture coordinates
+    texel: f32,
+}
+@group(0) @binding(1)
+var<uniform> light: Light;
+@group(0) @binding(2)
+var shadow_map: texture_depth_2d_array;
+@group(0) @binding(3)
+var shadow_sampler: sampler_comparison;
+
+// Sky of the scene pass, see `shaders/sky.wgsl` of the sky pipeline
+struct Sky {
+    forward: vec4<f32>,
+    right: vec4<f32>,
+    up: vec4<f32>,
+    eye: vec4<f32>,
+    sun: vec4<f32>,
+    zenith: vec4<f32>,
+    horizon: vec4<f32>,
+    // Fog start and density, see `Fog`
+    fog: vec4<f32>,
+    // Color of the sun or moon light and the ambient light
+    light: vec4<f32>,
+    // Color of the sun or moon disc
+    disc: vec4<f32>,
+}
+@group(0) @binding(4)
+var<uniform> sky: Sky;
+
 struct PushConstant {
     transform: mat4x4<f32>,
     offset: vec3<i32>
@@ -12,6 +54,16 @@ struct PushConstant {
 
 var<push_constant> pc: PushConstant;
 
+// Chunk of the indirect draws, see `ChunkBuffer`
+struct Chunk {
+    position: vec3<i32>,
+    first: u32,
+    faces: array<u32, 7>,
+    used: u32,
+}
+@group(1) @binding(0)
+var<storage, read> chunks: array<Chunk>;
+
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -20,6 +72,8 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
 }
 
 struct InstanceInput {
@@ -27,6 +81,13 @@ struct InstanceInput {
     @location(2) color: u32, 
 };
 
+struct IndirectInstanceInput {
+    @location(1) low: u32,
+    @location(2) color: u32,
+    // Chunk the quad belongs to
+    @location(3) slot: u32,
+};
+
 const CHUNK_SIZE: f32 = 32.0;
 const VOXEL_SIZE: f32 = 1.0;
 
@@ -34,15 +95,40 @@ const VOXEL_SIZE: f32 = 1.0;
 fn vs_main(
     model: VertexInput,
     instance: InstanceInput,
+) -> VertexOutput {
+    return vertex(model.position, instance.low, instance.color, pc.offset, pc.transform);
+}
+
+@vertex
+fn vs_indirect(
+    model: VertexInput,
+    instance: IndirectInstanceInput,
+) -> VertexOutput {
+    let identity = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+
+    return vertex(model.position, instance.low, instance.color, chunks[instance.slot].position, identity);
+}
+
+fn vertex(
+    model_position: vec3<f32>,
+    low: u32,
+    color: u32,
+    offset: vec3<i32>,
+    transform: mat4x4<f32>,
 ) -> VertexOutput {
     var out: VertexOutput;
 
-    var position_x: u32 = instance.low & 63u;
-    var position_y: u32 = (instance.low >> 6u) & 63u;
-    var position_z: u32 = (instance.low >> 12u) & 63u;
-    var direction: u32 = (instance.low >> 18u) & 7u;
+    var position_x: u32 = low & 63u;
+    var position_y: u32 = (low >> 6u) & 63u;
+    var position_z: u32 = (low >> 12u) & 63u;
+    var direction: u32 = (low >> 18u) & 7u;
 
-    var position: vec3<f32> = model.position;
+    var position: vec3<f32> = model_position;
 
     switch direction {
         // Left
@@ -72,41 +158,25 @@ fn vs_main(
         default: {}
     }
 
-    position += vec3(f32(position_x), f32(position_y), f32(position_z)) + (vec3(f32(pc.offset.x), f32(pc.offset.y), f32(pc.offset.z)) * CHUNK_SIZE * VOXEL_SIZE);
+    position += vec3(f32(position_x), f32(position_y), f32(position_z)) + (vec3(f32(offset.x), f32(offset.y), f32(offset.z)) * CHUNK_SIZE * VOXEL_SIZE);
 
-    let pos4 = pc.transform * vec4<f32>(position, 1.0);
+    let pos4 = transform * vec4<f32>(position, 1.0);
     position = (pos4.xyz / pos4.w);
 
-    out.color = unpack_color(instance.color);
+    out.color = unpack_color(color);
+    out.world_position = position;
 
-    // Apply "shading"
-    switch direction {
-        // Left
-        case 0u: {
-            out.color = darken_color(out.color, 0.3);
-        }
-        // Right
-        case 1u: {
-            out.color = darken_color(out.color, 0.325);
-        }
-        // Up
-        case 2u: {
-            out.color = darken_color(out.color, 0.15);
-        }
-        // Down
-        case 3u: {
-            out.color = darken_color(out.color, 0.4);
-        } 
-        // Front
-        case 4u: {
-            out.color = darken_color(out.color, 0.35);
-        }
-        // Back
-        case 5u: {
-            out.color = darken_color(out.color, 0.375);
-        }
-        default: {}
-    }
+    // (Left, Right, Up, Down, Front, Back) face (X+, X-, Y+, Y-, Z-, Z+)
+    var normals = array<vec3<f32>, 6>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(-1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, -1.0, 0.0),
+        vec3<f32>(0.0, 0.0, -1.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+    );
+
+    out.normal = normalize((transform * vec4<f32>(normals[min(direction, 5u)], 0.0)).xyz);
 
     out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
 
@@ -121,13 +191,78 @@ fn unpack_color(color: u32) -> vec4<f32> {
     return vec4<f32>(r, g, b, a);
 }
 
-fn darken_color(color: vec4<f32>, factor: f32) -> vec4<f32> {
-    return vec4<f32>(color.rgb * (1.0 - factor), color.a);
-}
-
 // Fragment shader
 
+// Attachments of the scene pass, see `SCENE_OUTPUTS`
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color;
-}
\ No newline at end of file
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let normal = normalize(in.normal);
+
+    // Lambert, faces turned away from the sun get the ambient light only
+    let lambert = max(dot(normal, light.sun.xyz), 0.0);
+
+    var lit = 0.0;
+
+    if lambert > 0.0 {
+        lit = lambert * shadow(in.world_position, normal);
+    }
+
+    // Sky light is a bit brighter on faces pointing up
+    let ambient = sky.light.a * (0.75 + 0.25 * normal.y);
+
+    let color = in.color.rgb * (ambient + sky.light.rgb * lit);
+
+    // Exponential fog fading into the horizon of the sky
+    let distance = length(in.world_position - sky.eye.xyz);
+    let fog = 1.0 - exp(-sky.fog.y * max(distance - sky.fog.x, 0.0));
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(mix(color, sky.horizon.rgb, fog), in.color.a);
+    out.normal = vec4<f32>(normal, 0.0);
+    return out;
+}
+
+// Fraction of the sun light reaching a point, filtered over 3x3 shadow map texels
+fn shadow(position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let distance = dot(position - light.eye.xyz, light.forward.xyz);
+
+    var cascade = light.count;
+
+    for (var i = 0u; i < light.count; i++) {
+        if distance <= light.splits[i] {
+            cascade = i;
+            break;
+        }
+    }
+
+    if cascade == light.count {
+        return 1.0;
+    }
+
+    // Moving the point along the normal keeps faces from shadowing themselves
+    let offset = position + normal * light.texels[cascade] * 1.5;
+    let clip = light.cascades[cascade] * vec4<f32>(offset, 1.0);
+
+    let uv = vec2<f32>(clip.x * 0.5 + 0.5, 0.5 - clip.y * 0.5);
+
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || clip.z > 1.0 {
+        return 1.0;
+    }
+
+    var sum = 0.0;
+
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let texel = uv + vec2<f32>(f32(x), f32(y)) * light.texel;
+
+            sum += textureSampleCompareLevel(shadow_map, shadow_sampler, texel, cascade, clip.z);
+        }
+    }
+
+    return sum / 9.0;
+}
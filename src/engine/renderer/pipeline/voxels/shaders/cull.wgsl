@@ -0,0 +1,161 @@
+// Culls the chunks of a `ChunkBuffer` and writes one indirect draw per face group
+
+struct Cull {
+    // Frustum planes, inside where dot(xyz, p) + w >= 0
+    planes: array<vec4<f32>, 6>,
+    eye: vec4<f32>,
+    // View projection of the frame the Hi-Z pyramid was built from
+    view_proj: mat4x4<f32>,
+    count: u32,
+    // Whether the Hi-Z pyramid holds the previous frame
+    occlusion: u32,
+}
+@group(0) @binding(0)
+var<uniform> cull: Cull;
+
+struct Chunk {
+    position: vec3<i32>,
+    // First quad in the quad buffer
+    first: u32,
+    // Face group starts relative to `first`, the last entry is the quad count
+    faces: array<u32, 7>,
+    used: u32,
+}
+@group(0) @binding(1)
+var<storage, read> chunks: array<Chunk>;
+
+struct DrawIndirect {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+}
+@group(0) @binding(2)
+var<storage, read_write> draws: array<DrawIndirect>;
+
+struct Stats {
+    drawn: atomic<u32>,
+    culled: atomic<u32>,
+    quads: atomic<u32>,
+    occluded: atomic<u32>,
+}
+@group(0) @binding(3)
+var<storage, read_write> stats: Stats;
+
+// Farthest depth of the previous frame, see `shaders/hiz.wgsl`
+@group(0) @binding(4)
+var hiz: texture_2d<f32>;
+
+const CHUNK_SIZE: f32 = 32.0;
+const VOXEL_SIZE: f32 = 1.0;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let slot = id.x;
+
+    if slot >= cull.count {
+        return;
+    }
+
+    let chunk = chunks[slot];
+
+    // Voxels span [z - 1, z] on the z axis
+    let lower = vec3<f32>(chunk.position) * CHUNK_SIZE * VOXEL_SIZE - vec3<f32>(0.0, 0.0, VOXEL_SIZE);
+    let upper = lower + vec3<f32>(CHUNK_SIZE * VOXEL_SIZE);
+
+    var visible = chunk.used != 0u;
+
+    for (var i = 0u; i < 6u; i++) {
+        let plane = cull.planes[i];
+
+        // Corner farthest along the plane normal
+        let corner = select(lower, upper, plane.xyz >= vec3<f32>(0.0));
+
+        if dot(plane.xyz, corner) + plane.w < 0.0 {
+            visible = false;
+        }
+    }
+
+    if chunk.used != 0u {
+        if !visible {
+            atomicAdd(&stats.culled, 1u);
+        } else if cull.occlusion != 0u && occluded(lower, upper) {
+            visible = false;
+            atomicAdd(&stats.occluded, 1u);
+        } else {
+            atomicAdd(&stats.drawn, 1u);
+        }
+    }
+
+    // A face group can only be seen from in front of its rearmost face,
+    // (Left, Right, Up, Down, Front, Back) face (X+, X-, Y+, Y-, Z-, Z+)
+    let eye = cull.eye.xyz;
+    var facing = array<bool, 6>(
+        eye.x >= lower.x,
+        eye.x <= upper.x,
+        eye.y >= lower.y,
+        eye.y <= upper.y,
+        eye.z <= upper.z,
+        eye.z >= lower.z,
+    );
+
+    for (var i = 0u; i < 6u; i++) {
+        var count = chunk.faces[i + 1u] - chunk.faces[i];
+
+        if !visible || !facing[i] {
+            count = 0u;
+        }
+
+        atomicAdd(&stats.quads, count);
+
+        draws[slot * 6u + i] = DrawIndirect(4u, count, 0u, chunk.first + chunk.faces[i]);
+    }
+}
+
+// Returns if the box lies behind the depth of the previous frame everywhere it covers
+fn occluded(lower: vec3<f32>, upper: vec3<f32>) -> bool {
+    var uv_min = vec2<f32>(1.0);
+    var uv_max = vec2<f32>(0.0);
+    var depth = 1.0;
+
+    for (var i = 0u; i < 8u; i++) {
+        let corner = select(lower, upper, vec3<bool>((i & 1u) != 0u, (i & 2u) != 0u, (i & 4u) != 0u));
+        let clip = cull.view_proj * vec4<f32>(corner, 1.0);
+
+        // Boxes reaching behind the eye can't be tested
+        if clip.w <= 0.0 {
+            return false;
+        }
+
+        let ndc = clip.xyz / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+
+        uv_min = min(uv_min, uv);
+        uv_max = max(uv_max, uv);
+        depth = min(depth, ndc.z);
+    }
+
+    uv_min = clamp(uv_min, vec2<f32>(0.0), vec2<f32>(1.0));
+    uv_max = clamp(uv_max, vec2<f32>(0.0), vec2<f32>(1.0));
+
+    // Level on which the box covers at most 2x2 texels
+    let base = vec2<f32>(textureDimensions(hiz, 0));
+    let extent = (uv_max - uv_min) * base;
+    let level = min(u32(ceil(log2(max(max(extent.x, extent.y), 1.0)))), textureNumLevels(hiz) - 1u);
+
+    // Texel `t` of a level covers the texels from `2t` of the level below, the last
+    // texel of a level also covers the odd ones left over
+    let size = textureDimensions(hiz, level);
+    let first = min(vec2<u32>(uv_min * base) >> vec2<u32>(level), size - 1u);
+    let last = min(vec2<u32>(uv_max * base) >> vec2<u32>(level), size - 1u);
+
+    var farthest = 0.0;
+
+    for (var y = first.y; y <= last.y; y++) {
+        for (var x = first.x; x <= last.x; x++) {
+            farthest = max(farthest, textureLoad(hiz, vec2<u32>(x, y), i32(level)).r);
+        }
+    }
+
+    return depth > farthest;
+}
@@ -0,0 +1,49 @@
+// Builds the Hi-Z pyramid, every texel holds the farthest depth of the texels it covers
+
+@group(0) @binding(0)
+var depth: texture_depth_2d;
+@group(0) @binding(1)
+var dst: texture_storage_2d<r32float, write>;
+@group(0) @binding(2)
+var src: texture_2d<f32>;
+
+// Copies the depth texture into the first level
+@compute @workgroup_size(8, 8)
+fn cs_copy(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dst);
+
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    textureStore(dst, id.xy, vec4<f32>(textureLoad(depth, id.xy, 0), 0.0, 0.0, 1.0));
+}
+
+// Reduces the previous level into the next one
+@compute @workgroup_size(8, 8)
+fn cs_reduce(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dst);
+    let src_size = textureDimensions(src);
+
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    // Odd sizes leave a column or row that the last texel covers too
+    let extra = vec2<u32>(
+        select(0u, 1u, id.x == size.x - 1u && src_size.x % 2u == 1u),
+        select(0u, 1u, id.y == size.y - 1u && src_size.y % 2u == 1u),
+    );
+
+    var farthest = 0.0;
+
+    for (var y = 0u; y < 2u + extra.y; y++) {
+        for (var x = 0u; x < 2u + extra.x; x++) {
+            let texel = min(id.xy * 2u + vec2<u32>(x, y), src_size - 1u);
+
+            farthest = max(farthest, textureLoad(src, texel, 0).r);
+        }
+    }
+
+    textureStore(dst, id.xy, vec4<f32>(farthest, 0.0, 0.0, 1.0));
+}
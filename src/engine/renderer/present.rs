@@ -0,0 +1,63 @@
+use super::{
+    graph::{fullscreen_pass, GraphPass, Resource, Targets},
+    pipeline::present::present_pipeline,
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, Device, RenderPipeline, TextureFormat, TextureView,
+};
+
+/// Last pass of the graph, copies an HDR target onto the surface
+pub struct Present {
+    source: Resource,
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+    bind_group: Option<BindGroup>,
+}
+
+impl Present {
+    pub fn new(device: &Device, source: Resource, format: TextureFormat) -> Self {
+        let (layout, pipeline) = present_pipeline(device, format);
+
+        Self {
+            source,
+            layout,
+            pipeline,
+            bind_group: None,
+        }
+    }
+}
+
+impl GraphPass for Present {
+    fn name(&self) -> &'static str {
+        "present"
+    }
+
+    fn inputs(&self) -> Vec<Resource> {
+        vec![self.source]
+    }
+
+    fn outputs(&self) -> Vec<Resource> {
+        Vec::new()
+    }
+
+    fn prepare(&mut self, device: &Device, targets: &Targets) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vengine::present_bind_group"),
+            layout: &self.layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(targets.view(self.source)),
+            }],
+        }));
+    }
+
+    fn record(&self, encoder: &mut CommandEncoder, _targets: &Targets, surface: &TextureView) {
+        fullscreen_pass(
+            encoder,
+            "vengine::present_pass",
+            surface,
+            &self.pipeline,
+            self.bind_group.as_ref().unwrap(),
+        );
+    }
+}
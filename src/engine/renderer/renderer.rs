@@ -1,11 +1,25 @@
-use super::{backend::Backend, camera::Camera, pipeline::voxels::voxel_pipeline, texture::Texture};
+use super::{
+    antialiasing::{AntiAliasing, Fxaa, ANTIALIASED},
+    backend::Backend,
+    camera::Camera,
+    graph::RenderGraph,
+    pipeline::{
+        scene_pipelines,
+        voxels::{indirect_pipeline, shadow_pipeline, IndirectPipeline},
+        ScenePipelines,
+    },
+    present::Present,
+    shadow::{ShadowSettings, Shadows},
+    sky::Sky,
+    ssao::{Ssao, SsaoQuality, SHADED},
+};
 use cgmath::Point3;
 use crossbeam::atomic::AtomicCell;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Mutex,
+    Mutex, MutexGuard,
 };
-use wgpu::{util::DeviceExt, Buffer, RenderPipeline};
+use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, Buffer, Device, RenderPipeline};
 
 pub struct Renderer<'a> {
     // Backend
@@ -16,18 +30,40 @@ pub struct Renderer<'a> {
     new_size: AtomicCell<(u32, u32)>,
     // Flag if the surface has been resized
     resized: AtomicBool,
-    // Voxel pipeline
-    pub voxel_pipeline: RenderPipeline,
+    // Voxel, sky and indirect pipelines of the scene pass
+    scene_pipelines: Mutex<ScenePipelines>,
+    // Shadow map pipeline
+    pub shadow_pipeline: RenderPipeline,
+    // Indirect chunk culling and shadow pipelines, if supported by the adapter
+    pub indirect_pipeline: Option<IndirectPipeline>,
     // Camera
     camera: Camera,
-    // Depth texture
-    pub depth_texture: Mutex<Texture>,
+    // Sun light and shadow maps
+    shadows: Shadows,
+    // Sky and fog
+    sky: Sky,
+    // Camera, light, shadow maps and sky of the scene pass
+    scene_layout: BindGroupLayout,
+    scene_bind_group: BindGroup,
+    // Targets of the scene pass and the post-process passes after it
+    graph: Mutex<RenderGraph>,
+    ssao_quality: SsaoQuality,
+    // Anti-aliasing in use
+    anti_aliasing: AtomicCell<AntiAliasing>,
+    // Anti-aliasing to switch to when the next frame starts
+    new_anti_aliasing: AtomicCell<Option<AntiAliasing>>,
     // Quad
     pub quad: Buffer,
 }
 
 impl<'a> Renderer<'a> {
-    pub fn new(backend: Backend<'a>, size: (u32, u32)) -> Self {
+    pub fn new(
+        backend: Backend<'a>,
+        size: (u32, u32),
+        shadow_settings: ShadowSettings,
+        ssao_quality: SsaoQuality,
+        anti_aliasing: AntiAliasing,
+    ) -> Self {
         // Camera related
         let camera = Camera::new(
             Point3::new(0.0, 5.0, 2.0),
@@ -36,6 +72,18 @@ impl<'a> Renderer<'a> {
             backend.queue().clone(),
         );
 
+        let shadows = Shadows::new(
+            backend.device(),
+            backend.queue().clone(),
+            &camera,
+            shadow_settings,
+        );
+
+        let sky = Sky::new(backend.device(), backend.queue().clone());
+
+        let (scene_layout, scene_bind_group) =
+            scene_bind_group(backend.device(), &camera, &shadows, &sky);
+
         // Quad
         let quad = backend
             .device()
@@ -50,24 +98,42 @@ impl<'a> Renderer<'a> {
                 usage: wgpu::BufferUsages::VERTEX,
             });
 
-        let lock = backend.surface_configuration().lock().unwrap();
+        let anti_aliasing = supported_anti_aliasing(&backend, anti_aliasing);
 
-        let depth_texture =
-            Texture::create_depth_texture(backend.device(), &lock, "engine::depth_texture");
+        let graph = render_graph(&backend, ssao_quality, anti_aliasing);
 
-        drop(lock);
+        let shadow_pipeline = shadow_pipeline(backend.device(), &camera);
 
-        let voxel_pipeline = voxel_pipeline(backend.device(), &camera, *backend.surface_format());
+        let indirect_pipeline = backend
+            .supports_indirect()
+            .then(|| indirect_pipeline(backend.device(), &camera, &scene_layout));
+
+        let scene_pipelines = scene_pipelines(
+            backend.device(),
+            &scene_layout,
+            &sky,
+            indirect_pipeline.as_ref(),
+            anti_aliasing.msaa,
+        );
 
         Self {
             backend,
             current_size: AtomicCell::new(size),
             new_size: AtomicCell::new((0, 0)),
             camera,
+            shadows,
+            sky,
+            scene_layout,
+            scene_bind_group,
             resized: AtomicBool::new(false),
-            depth_texture: Mutex::new(depth_texture),
+            graph: Mutex::new(graph),
+            ssao_quality,
+            anti_aliasing: AtomicCell::new(anti_aliasing),
+            new_anti_aliasing: AtomicCell::new(None),
             quad,
-            voxel_pipeline,
+            scene_pipelines: Mutex::new(scene_pipelines),
+            shadow_pipeline,
+            indirect_pipeline,
         }
     }
 
@@ -79,6 +145,60 @@ impl<'a> Renderer<'a> {
         &self.camera
     }
 
+    pub fn shadows(&self) -> &Shadows {
+        &self.shadows
+    }
+
+    pub fn sky(&self) -> &Sky {
+        &self.sky
+    }
+
+    pub fn graph(&self) -> MutexGuard<'_, RenderGraph> {
+        self.graph.lock().unwrap()
+    }
+
+    pub fn scene_pipelines(&self) -> MutexGuard<'_, ScenePipelines> {
+        self.scene_pipelines.lock().unwrap()
+    }
+
+    pub fn anti_aliasing(&self) -> AntiAliasing {
+        self.anti_aliasing.load()
+    }
+
+    /// Switches the anti-aliasing when the next frame starts, MSAA falls back to the
+    /// highest sample count supported by the adapter
+    pub fn set_anti_aliasing(&self, anti_aliasing: AntiAliasing) {
+        self.new_anti_aliasing.store(Some(anti_aliasing));
+    }
+
+    /// Recreates the scene pipelines and the render graph if the anti-aliasing changed
+    pub fn apply_anti_aliasing(&self) {
+        let Some(anti_aliasing) = self.new_anti_aliasing.take() else {
+            return;
+        };
+
+        let anti_aliasing = supported_anti_aliasing(&self.backend, anti_aliasing);
+
+        if anti_aliasing == self.anti_aliasing.load() {
+            return;
+        }
+
+        *self.scene_pipelines() = scene_pipelines(
+            self.backend.device(),
+            &self.scene_layout,
+            &self.sky,
+            self.indirect_pipeline.as_ref(),
+            anti_aliasing.msaa,
+        );
+        *self.graph() = render_graph(&self.backend, self.ssao_quality, anti_aliasing);
+
+        self.anti_aliasing.store(anti_aliasing);
+    }
+
+    pub fn scene_bind_group(&self) -> &BindGroup {
+        &self.scene_bind_group
+    }
+
     pub fn dimensions(&self) -> (u32, u32) {
         self.current_size.load()
     }
@@ -102,15 +222,132 @@ impl<'a> Renderer<'a> {
                 .configure(self.backend().device(), &surface_lock);
             self.camera.set_aspect(width as f32 / height as f32);
 
-            let mut texture_lock = self.depth_texture.lock().unwrap();
-
-            *texture_lock = Texture::create_depth_texture(
-                self.backend().device(),
-                &surface_lock,
-                "engine::depth_texture",
-            );
+            self.graph().resize(self.backend().device(), width, height);
             self.current_size.store((width, height));
             self.resized.store(false, Ordering::Relaxed);
         }
     }
 }
+
+/// Highest MSAA sample count up to the requested one the adapter supports
+fn supported_anti_aliasing(backend: &Backend, anti_aliasing: AntiAliasing) -> AntiAliasing {
+    let msaa = [8, 4, 2]
+        .into_iter()
+        .find(|&count| count <= anti_aliasing.msaa && backend.supports_samples(count))
+        .unwrap_or(1);
+
+    AntiAliasing {
+        msaa,
+        ..anti_aliasing
+    }
+}
+
+/// Scene targets followed by SSAO, FXAA if enabled and the copy onto the surface
+fn render_graph(
+    backend: &Backend,
+    ssao_quality: SsaoQuality,
+    anti_aliasing: AntiAliasing,
+) -> RenderGraph {
+    let device = backend.device();
+
+    let lock = backend.surface_configuration().lock().unwrap();
+
+    let mut graph = RenderGraph::new(device, (lock.width, lock.height), anti_aliasing.msaa);
+
+    drop(lock);
+
+    graph.add_pass(
+        device,
+        Box::new(Ssao::new(device, backend.queue().clone(), ssao_quality)),
+    );
+
+    let output = match anti_aliasing.fxaa {
+        true => {
+            graph.add_pass(device, Box::new(Fxaa::new(device, SHADED)));
+
+            ANTIALIASED
+        }
+        false => SHADED,
+    };
+
+    graph.add_pass(
+        device,
+        Box::new(Present::new(device, output, *backend.surface_format())),
+    );
+
+    graph
+}
+
+/// Layout and bind group of the scene pass, see the bindings of group 0 in
+/// `shaders/base.wgsl`
+fn scene_bind_group(
+    device: &Device,
+    camera: &Camera,
+    shadows: &Shadows,
+    sky: &Sky,
+) -> (BindGroupLayout, BindGroup) {
+    let uniform = |binding, visibility| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("vengine::scene_bind_group_layout"),
+        entries: &[
+            uniform(0, wgpu::ShaderStages::VERTEX),
+            uniform(1, wgpu::ShaderStages::FRAGMENT),
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+            uniform(4, wgpu::ShaderStages::FRAGMENT),
+        ],
+    });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("vengine::scene_bind_group"),
+        layout: &layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.buffer().as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: shadows.uniform().as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(shadows.view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(shadows.sampler()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: sky.uniform().as_entire_binding(),
+            },
+        ],
+    });
+
+    (layout, bind_group)
+}
@@ -0,0 +1,383 @@
+use super::camera::Camera;
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix4, MetricSpace, Point3, SquareMatrix, Vector3, Vector4,
+};
+use crossbeam::atomic::AtomicCell;
+use std::sync::Arc;
+use wgpu::{BindGroup, Buffer, Device, Queue, Sampler, TextureView};
+
+/// Cascades the light uniform holds at most
+pub const MAX_CASCADES: usize = 4;
+pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Distance the light view reaches beyond a cascade towards the sun, so voxels outside
+/// of the view still cast shadows into it
+const CASTER_DISTANCE: f32 = 256.0;
+/// Distance of the eye used to pick the face groups facing the sun, far enough that the
+/// rays are close to parallel
+const SUN_DISTANCE: f32 = 10000.0;
+/// Blend between uniform and logarithmic cascade splits
+const SPLIT_LAMBDA: f32 = 0.75;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    /// Number of shadow maps splitting the view, at most `MAX_CASCADES`
+    pub cascades: u32,
+    /// Width and height of every shadow map in texels
+    pub resolution: u32,
+    /// Distance from the eye up to which shadows are drawn
+    pub distance: f32,
+}
+
+/// Light view of one cascade
+#[derive(Debug, Clone, Copy)]
+pub struct Cascade {
+    pub view_proj: Matrix4<f32>,
+    /// Point far towards the sun, used as eye to cull face groups
+    pub eye: Point3<f32>,
+    /// Distance along the view direction up to which the cascade is used
+    pub split: f32,
+    /// Size of a shadow map texel in world units
+    pub texel: f32,
+}
+
+/// See `Light` in `shaders/base.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniform {
+    sun: [f32; 4],
+    eye: [f32; 4],
+    forward: [f32; 4],
+    cascades: [[[f32; 4]; 4]; MAX_CASCADES],
+    splits: [f32; 4],
+    texels: [f32; 4],
+    count: u32,
+    texel: f32,
+    _padding: [u32; 2],
+}
+
+/// Directional sun light with cascaded shadow maps. Every frame the view is split into
+/// cascades along the view direction, each gets an orthographic shadow map fitted around it
+pub struct Shadows {
+    settings: ShadowSettings,
+    /// Direction towards the sun, or the moon at night
+    sun: AtomicCell<Vector3<f32>>,
+    cascades: AtomicCell<[Cascade; MAX_CASCADES]>,
+    /// Layers the shadow passes draw into
+    layers: Vec<TextureView>,
+    uniform: Buffer,
+    /// View projection of every cascade, bound like the camera in the shadow passes
+    cascade_bind_groups: Vec<(Buffer, BindGroup)>,
+    /// All layers, sampled by the scene pass
+    view: TextureView,
+    sampler: Sampler,
+    queue: Arc<Queue>,
+}
+
+impl Shadows {
+    pub fn new(
+        device: &Device,
+        queue: Arc<Queue>,
+        camera: &Camera,
+        settings: ShadowSettings,
+    ) -> Self {
+        let settings = ShadowSettings {
+            cascades: settings.cascades.clamp(1, MAX_CASCADES as u32),
+            resolution: settings.resolution.max(1),
+            ..settings
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("vengine::shadow_texture"),
+            size: wgpu::Extent3d {
+                width: settings.resolution,
+                height: settings.resolution,
+                depth_or_array_layers: settings.cascades,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let layers = (0..settings.cascades)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("vengine::shadow_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vengine::light_buffer"),
+            size: size_of::<LightUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let cascade_bind_groups = (0..settings.cascades)
+            .map(|_| {
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("vengine::cascade_buffer"),
+                    size: size_of::<[[f32; 4]; 4]>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("vengine::cascade_bind_group"),
+                    layout: camera.bind_group_layout(),
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+
+                (buffer, bind_group)
+            })
+            .collect();
+
+        let cascade = Cascade {
+            view_proj: Matrix4::identity(),
+            eye: Point3::origin(),
+            split: 0.0,
+            texel: 0.0,
+        };
+
+        Self {
+            settings,
+            sun: AtomicCell::new(Vector3::new(0.4, 1.0, 0.3).normalize()),
+            cascades: AtomicCell::new([cascade; MAX_CASCADES]),
+            layers,
+            uniform,
+            cascade_bind_groups,
+            view,
+            sampler,
+            queue,
+        }
+    }
+
+    pub fn cascade_count(&self) -> u32 {
+        self.settings.cascades
+    }
+
+    /// Direction towards the sun, or the moon at night
+    pub fn sun(&self) -> Vector3<f32> {
+        self.sun.load()
+    }
+
+    pub fn set_sun(&self, direction: Vector3<f32>) {
+        self.sun.store(direction.normalize());
+    }
+
+    pub fn cascade(&self, cascade: u32) -> Cascade {
+        self.cascades.load()[cascade as usize]
+    }
+
+    /// Fits the cascades around the view of the camera, call once per frame
+    pub fn update(&self, camera: &Camera) {
+        let sun = self.sun.load();
+
+        let splits = splits(
+            self.settings.cascades,
+            camera.near(),
+            self.settings.distance,
+        );
+
+        let mut cascades = self.cascades.load();
+        let mut near = camera.near();
+
+        for (cascade, split) in cascades
+            .iter_mut()
+            .zip(splits)
+            .take(self.settings.cascades as usize)
+        {
+            *cascade = fit(
+                &camera.slice_corners(near, split),
+                sun,
+                self.settings.resolution,
+            );
+            cascade.split = split;
+
+            near = split;
+        }
+
+        self.cascades.store(cascades);
+
+        for ((buffer, _), cascade) in self.cascade_bind_groups.iter().zip(cascades) {
+            let view_proj: [[f32; 4]; 4] = cascade.view_proj.into();
+
+            self.queue
+                .write_buffer(buffer, 0, bytemuck::bytes_of(&view_proj));
+        }
+
+        let uniform = LightUniform {
+            sun: sun.extend(0.0).into(),
+            eye: camera.get_eye().to_homogeneous().into(),
+            forward: camera.forward().extend(0.0).into(),
+            cascades: cascades.map(|cascade| cascade.view_proj.into()),
+            splits: cascades.map(|cascade| cascade.split),
+            texels: cascades.map(|cascade| cascade.texel),
+            count: self.settings.cascades,
+            texel: 1.0 / self.settings.resolution as f32,
+            _padding: [0; 2],
+        };
+
+        self.queue
+            .write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Shadow map layer of a cascade
+    pub fn layer(&self, cascade: u32) -> &TextureView {
+        &self.layers[cascade as usize]
+    }
+
+    /// Light view projection of a cascade, bound at the place of the camera
+    pub fn cascade_bind_group(&self, cascade: u32) -> &BindGroup {
+        &self.cascade_bind_groups[cascade as usize].1
+    }
+
+    /// Light uniform, see `Light` in `shaders/base.wgsl`
+    pub fn uniform(&self) -> &Buffer {
+        &self.uniform
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Comparison sampler filtering the shadow maps
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+}
+
+/// Far distance of every cascade along the view direction
+fn splits(count: u32, near: f32, distance: f32) -> [f32; MAX_CASCADES] {
+    std::array::from_fn(|i| {
+        let t = ((i as u32 + 1).min(count)) as f32 / count as f32;
+
+        let uniform = near + (distance - near) * t;
+        let logarithmic = near * (distance / near).powf(t);
+
+        uniform + (logarithmic - uniform) * SPLIT_LAMBDA
+    })
+}
+
+/// Orthographic light view enclosing the bounding sphere of a slice of the view.
+/// The sphere keeps the size of the shadow map constant while the view turns, and the
+/// view is moved in whole texels, so shadow edges don't flicker
+fn fit(corners: &[Point3<f32>; 8], sun: Vector3<f32>, resolution: u32) -> Cascade {
+    let center = Point3::centroid(corners);
+
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let up = if sun.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    };
+
+    let view = Matrix4::look_at_rh(center + sun * (radius + CASTER_DISTANCE), center, up);
+
+    // Mirrored around the eye, the depths from the eye up to `depth` land in `[0, 1]`
+    let depth = 2.0 * radius + CASTER_DISTANCE;
+    let proj = cgmath::ortho(-radius, radius, -radius, radius, -depth, depth);
+
+    let view_proj = proj * view;
+
+    // Offset of the world origin from the closest texel, in normalized device coordinates
+    let half = resolution as f32 / 2.0;
+    let origin = view_proj * Vector4::unit_w() * half;
+    let offset = Vector3::new(
+        origin.x.round() - origin.x,
+        origin.y.round() - origin.y,
+        0.0,
+    ) / half;
+
+    Cascade {
+        view_proj: Matrix4::from_translation(offset) * view_proj,
+        eye: center + sun * SUN_DISTANCE,
+        split: 0.0,
+        texel: 2.0 * radius / resolution as f32,
+    }
+}
+
+#[test]
+fn test_fit() {
+    use super::camera::{projection, slice_corners, FOVY, ZFAR, ZNEAR};
+
+    let projection = projection(FOVY, 16.0 / 9.0, ZNEAR, ZFAR);
+    let eye = Point3::new(3.0, 40.0, -7.0);
+    let view = Matrix4::look_at_rh(eye, Point3::new(20.0, 30.0, 10.0), Vector3::unit_y());
+    let forward = (Point3::new(20.0, 30.0, 10.0) - eye).normalize();
+
+    let (near, far) = (10.0, 40.0);
+    let corners = slice_corners(view, projection, near, far);
+
+    // The corners lie on the edges of the screen at the distances
+    for (i, corner) in corners.iter().enumerate() {
+        let clip = projection * view * corner.to_homogeneous();
+
+        assert!((clip.x.abs() / clip.w - 1.0).abs() < 1e-3);
+        assert!((clip.y.abs() / clip.w - 1.0).abs() < 1e-3);
+
+        let distance = if i < 4 { near } else { far };
+        assert!(((corner - eye).dot(forward) - distance).abs() < 1e-3);
+    }
+
+    let cascade = fit(&corners, Vector3::new(0.3, 1.0, 0.2).normalize(), 1024);
+
+    // Everything the camera sees between the distances lies inside the shadow map
+    // and its depth range, up to the edges of the screen
+    let inverse = (projection * view).invert().unwrap();
+
+    for y in -4..=4 {
+        for x in -4..=4 {
+            for z in 0..=64 {
+                let ndc = Vector4::new(x as f32 / 4.0, y as f32 / 4.0, z as f32 / 64.0, 1.0);
+                let point = Point3::from_homogeneous(inverse * ndc);
+
+                if !(near..=far).contains(&(point - eye).dot(forward)) {
+                    continue;
+                }
+
+                let clip = cascade.view_proj * point.to_homogeneous();
+
+                assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0);
+                assert!((0.0..=1.0).contains(&clip.z));
+            }
+        }
+    }
+
+    let splits = splits(3, 0.1, 200.0);
+
+    assert!(splits[0] < splits[1] && splits[1] < splits[2]);
+    assert!((splits[2] - 200.0).abs() < 1e-3);
+    assert_eq!(splits[3], splits[2]);
+}
@@ -0,0 +1,194 @@
+use super::camera::Camera;
+use cgmath::{EuclideanSpace, Vector3};
+use crossbeam::atomic::AtomicCell;
+use std::sync::Arc;
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device, Queue};
+
+/// Fog left at the distance a fog reaches
+const FOG_REMAINING: f32 = 1.0 / 64.0;
+
+/// Exponential distance fog, blending voxels into the horizon color of the sky
+#[derive(Debug, Clone, Copy)]
+pub struct Fog {
+    /// Distance from the eye at which the fog starts
+    pub start: f32,
+    /// Fraction of the remaining view the fog covers per unit of distance past the start
+    pub density: f32,
+}
+
+impl Fog {
+    pub const NONE: Fog = Fog {
+        start: 0.0,
+        density: 0.0,
+    };
+
+    /// Fog starting at a third of `distance` and almost opaque at it, used to hide the
+    /// edge of the loaded terrain
+    pub fn reaching(distance: f32) -> Self {
+        let start = distance / 3.0;
+
+        Self {
+            start,
+            density: -FOG_REMAINING.ln() / (distance - start),
+        }
+    }
+}
+
+/// Colors of the sky and the light reaching the voxels
+#[derive(Debug, Clone, Copy)]
+pub struct Lighting {
+    /// Color of the sky straight up
+    pub zenith: [f32; 3],
+    /// Color of the sky at the horizon, the fog fades into it
+    pub horizon: [f32; 3],
+    /// Color and strength of the sun or moon light
+    pub light: [f32; 3],
+    /// Light reaching faces in the shadow
+    pub ambient: f32,
+    /// Color of the sun or moon disc in the sky
+    pub disc: [f32; 3],
+}
+
+impl Lighting {
+    pub const DAY: Lighting = Lighting {
+        zenith: [0.22, 0.45, 0.85],
+        horizon: [0.7, 0.82, 0.95],
+        light: [0.65, 0.65, 0.65],
+        ambient: 0.35,
+        disc: [1.0, 0.95, 0.8],
+    };
+}
+
+/// See `Sky` in `shaders/sky.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+    forward: [f32; 4],
+    right: [f32; 4],
+    up: [f32; 4],
+    eye: [f32; 4],
+    sun: [f32; 4],
+    zenith: [f32; 4],
+    horizon: [f32; 4],
+    fog: [f32; 4],
+    light: [f32; 4],
+    disc: [f32; 4],
+}
+
+/// Procedural sky drawn behind the voxels, a gradient from the horizon to the zenith with
+/// the sun in it. Also holds the fog of the current level and the lighting of the voxels
+pub struct Sky {
+    fog: AtomicCell<Fog>,
+    lighting: AtomicCell<Lighting>,
+    uniform: Buffer,
+    layout: BindGroupLayout,
+    bind_group: BindGroup,
+    queue: Arc<Queue>,
+}
+
+impl Sky {
+    pub fn new(device: &Device, queue: Arc<Queue>) -> Self {
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vengine::sky_buffer"),
+            size: size_of::<SkyUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vengine::sky_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vengine::sky_bind_group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            fog: AtomicCell::new(Fog::NONE),
+            lighting: AtomicCell::new(Lighting::DAY),
+            uniform,
+            layout,
+            bind_group,
+            queue,
+        }
+    }
+
+    pub fn set_fog(&self, fog: Fog) {
+        self.fog.store(fog);
+    }
+
+    pub fn set_lighting(&self, lighting: Lighting) {
+        self.lighting.store(lighting);
+    }
+
+    /// Writes the view of the camera and the direction of the sun or moon, call once per
+    /// frame
+    pub fn update(&self, camera: &Camera, sun: Vector3<f32>) {
+        // Rays through the corners of the screen, unprojected like the geometry is drawn
+        let corners = camera.slice_corners(1.0, 2.0);
+        let rays: [Vector3<f32>; 4] = std::array::from_fn(|i| corners[i + 4] - corners[i]);
+
+        let forward = (rays[0] + rays[1] + rays[2] + rays[3]) / 4.0;
+        let right = (rays[1] - rays[0]) / 2.0;
+        let up = (rays[2] - rays[0]) / 2.0;
+
+        let fog = self.fog.load();
+        let lighting = self.lighting.load();
+
+        let uniform = SkyUniform {
+            forward: forward.extend(0.0).into(),
+            right: right.extend(0.0).into(),
+            up: up.extend(0.0).into(),
+            eye: camera.get_eye().to_vec().extend(1.0).into(),
+            sun: sun.extend(0.0).into(),
+            zenith: Vector3::from(lighting.zenith).extend(1.0).into(),
+            horizon: Vector3::from(lighting.horizon).extend(1.0).into(),
+            fog: [fog.start, fog.density, 0.0, 0.0],
+            light: Vector3::from(lighting.light)
+                .extend(lighting.ambient)
+                .into(),
+            disc: Vector3::from(lighting.disc).extend(1.0).into(),
+        };
+
+        self.queue
+            .write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Sky uniform, also bound in the scene pass for the fog
+    pub fn uniform(&self) -> &Buffer {
+        &self.uniform
+    }
+
+    pub fn bind_group_layout(&self) -> &BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+}
+
+#[test]
+fn test_fog_reaching() {
+    let fog = Fog::reaching(300.0);
+
+    let remaining = |distance: f32| (-fog.density * (distance - fog.start).max(0.0)).exp();
+
+    assert_eq!(remaining(50.0), 1.0);
+    assert!((remaining(300.0) - FOG_REMAINING).abs() < 1e-4);
+}
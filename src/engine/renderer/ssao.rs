@@ -0,0 +1,213 @@
+use super::{
+    camera::Camera,
+    graph::{fullscreen_pass, GraphPass, Resource, Scale, Targets, COLOR, DEPTH, NORMAL},
+    pipeline::ssao::{ssao_pipeline, SsaoPipeline},
+};
+use cgmath::SquareMatrix;
+use std::sync::Arc;
+use wgpu::{BindGroup, Buffer, CommandEncoder, Device, Queue, Sampler, TextureFormat, TextureView};
+
+/// Distance around a position occluders are searched in
+const RADIUS: f32 = 1.0;
+/// Depth difference ignored, against flat faces occluding themselves
+const BIAS: f32 = 0.02;
+/// How much the occlusion darkens the scene, from 0 to 1
+const INTENSITY: f32 = 0.8;
+
+/// Occlusion at half resolution
+pub const OCCLUSION: Resource =
+    Resource::new("ssao_occlusion", TextureFormat::R8Unorm, Scale::Half);
+/// Occlusion after the blur
+pub const BLURRED: Resource = Resource::new("ssao_blurred", TextureFormat::R8Unorm, Scale::Half);
+/// Scene color darkened by the occlusion
+pub const SHADED: Resource = Resource::new("shaded", TextureFormat::Rgba16Float, Scale::Full);
+
+/// Quality of the screen-space ambient occlusion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsaoQuality {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl SsaoQuality {
+    /// Samples taken per pixel of the occlusion
+    pub fn samples(self) -> u32 {
+        match self {
+            SsaoQuality::Off => 0,
+            SsaoQuality::Low => 8,
+            SsaoQuality::Medium => 16,
+            SsaoQuality::High => 32,
+        }
+    }
+}
+
+/// See `Ssao` in `shaders/ssao.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoUniform {
+    view: [[f32; 4]; 4],
+    proj: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    params: [f32; 4],
+}
+
+/// Bind groups of the three passes, bound to the targets of the graph
+struct BindGroups {
+    ao: BindGroup,
+    blur: BindGroup,
+    composite: BindGroup,
+}
+
+/// Screen-space ambient occlusion, computed from the depth and normals of the scene pass
+/// at half resolution, blurred and composited with the scene color
+pub struct Ssao {
+    quality: SsaoQuality,
+    pipeline: SsaoPipeline,
+    uniform: Buffer,
+    sampler: Sampler,
+    bind_groups: Option<BindGroups>,
+    queue: Arc<Queue>,
+}
+
+impl Ssao {
+    pub fn new(device: &Device, queue: Arc<Queue>, quality: SsaoQuality) -> Self {
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vengine::ssao_buffer"),
+            size: size_of::<SsaoUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("vengine::ssao_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            quality,
+            pipeline: ssao_pipeline(device),
+            uniform,
+            sampler,
+            bind_groups: None,
+            queue,
+        }
+    }
+}
+
+impl GraphPass for Ssao {
+    fn name(&self) -> &'static str {
+        "ssao"
+    }
+
+    fn inputs(&self) -> Vec<Resource> {
+        vec![COLOR, NORMAL, DEPTH]
+    }
+
+    fn outputs(&self) -> Vec<Resource> {
+        vec![OCCLUSION, BLURRED, SHADED]
+    }
+
+    /// Writes the view and projection of the camera
+    fn update(&self, camera: &Camera) {
+        let proj = camera.projection();
+
+        let (intensity, samples) = match self.quality {
+            SsaoQuality::Off => (0.0, 0),
+            quality => (INTENSITY, quality.samples()),
+        };
+
+        let uniform = SsaoUniform {
+            view: camera.view().into(),
+            proj: proj.into(),
+            inv_proj: proj.invert().unwrap().into(),
+            params: [RADIUS, BIAS, intensity, samples as f32],
+        };
+
+        self.queue
+            .write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    fn prepare(&mut self, device: &Device, targets: &Targets) {
+        let texture = |binding, resource| wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(targets.view(resource)),
+        };
+
+        let ao = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vengine::ssao_ao_bind_group"),
+            layout: &self.pipeline.ao_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform.as_entire_binding(),
+                },
+                texture(1, DEPTH),
+                texture(2, NORMAL),
+            ],
+        });
+
+        let blur = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vengine::ssao_blur_bind_group"),
+            layout: &self.pipeline.blur_layout,
+            entries: &[texture(3, OCCLUSION)],
+        });
+
+        let composite = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vengine::ssao_composite_bind_group"),
+            layout: &self.pipeline.composite_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform.as_entire_binding(),
+                },
+                texture(4, COLOR),
+                texture(5, BLURRED),
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        self.bind_groups = Some(BindGroups {
+            ao,
+            blur,
+            composite,
+        });
+    }
+
+    fn record(&self, encoder: &mut CommandEncoder, targets: &Targets, _surface: &TextureView) {
+        let bind_groups = self.bind_groups.as_ref().unwrap();
+
+        if self.quality != SsaoQuality::Off {
+            fullscreen_pass(
+                encoder,
+                "vengine::ssao_ao_pass",
+                targets.view(OCCLUSION),
+                &self.pipeline.ao,
+                &bind_groups.ao,
+            );
+            fullscreen_pass(
+                encoder,
+                "vengine::ssao_blur_pass",
+                targets.view(BLURRED),
+                &self.pipeline.blur,
+                &bind_groups.blur,
+            );
+        }
+
+        fullscreen_pass(
+            encoder,
+            "vengine::ssao_composite_pass",
+            targets.view(SHADED),
+            &self.pipeline.composite,
+            &bind_groups.composite,
+        );
+    }
+}
@@ -10,9 +10,9 @@ pub enum Direction {
     Up = 2,
     /// Down (Y-)
     Down = 3,
-    /// Front (Z+)
+    /// Front (Z-)
     Front = 4,
-    /// Back (Z-)
+    /// Back (Z+)
     Back = 5,
 }
 
@@ -23,8 +23,8 @@ impl Direction {
             Direction::Right => Vector3::new(-1f32, 0f32, 0f32),
             Direction::Up => Vector3::new(0f32, 1f32, 0f32),
             Direction::Down => Vector3::new(0f32, -1f32, 0f32),
-            Direction::Front => Vector3::new(0f32, 0f32, 1f32),
-            Direction::Back => Vector3::new(0f32, 0f32, -1f32),
+            Direction::Front => Vector3::new(0f32, 0f32, -1f32),
+            Direction::Back => Vector3::new(0f32, 0f32, 1f32),
         }
     }
 }
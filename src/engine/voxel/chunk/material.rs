@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// What a voxel is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum Material {
+    /// Voxels without a known material, e.g. from imported models
+    #[default]
+    Unknown,
+    Grass,
+    Sand,
+    Dirt,
+    Stone,
+    DeepRock,
+    Snow,
+    Wood,
+    Leaves,
+    Coal,
+    Iron,
+    Gold,
+    Water,
+    Gravel,
+}
+
+impl Material {
+    /// Color used where the material isn't colored by the height gradient
+    pub fn color(&self) -> [u8; 4] {
+        match self {
+            Material::Unknown => [255, 0, 255, 255],
+            Material::Grass => [51, 120, 25, 255],
+            Material::Sand => [240, 222, 186, 255],
+            Material::Dirt => [96, 66, 40, 255],
+            Material::Stone => [105, 105, 100, 255],
+            Material::DeepRock => [55, 52, 58, 255],
+            Material::Snow => [235, 235, 245, 255],
+            Material::Wood => [89, 60, 31, 255],
+            Material::Leaves => [40, 110, 30, 255],
+            Material::Coal => [25, 25, 25, 255],
+            Material::Iron => [160, 110, 85, 255],
+            Material::Gold => [230, 190, 40, 255],
+            Material::Water => [30, 95, 190, 255],
+            Material::Gravel => [128, 122, 115, 255],
+        }
+    }
+
+    /// Depth of the drop next to a loose voxel at which it slides off sideways,
+    /// larger values give steeper piles. `None` for materials that don't fall
+    pub fn repose(&self) -> Option<i32> {
+        match self {
+            Material::Sand => Some(1),
+            Material::Gravel => Some(2),
+            _ => None,
+        }
+    }
+}
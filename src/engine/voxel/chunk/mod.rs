@@ -2,9 +2,11 @@ use super::quad::Quad;
 use ahash::HashMap;
 use axis::Axis;
 use direction::Direction;
+use material::Material;
 
 pub mod axis;
 pub mod direction;
+pub mod material;
 
 pub const CHUNK_SIZE: usize = 32;
 pub const VOXEL_SIZE: f32 = 1.0;
@@ -12,7 +14,7 @@ pub const VOXEL_SIZE: f32 = 1.0;
 // The chunk coordinates are left handed
 pub struct Chunk {
     voxels: Box<[u32; 32 * 32]>,
-    colors: HashMap<u16, [u8; 4]>,
+    colors: HashMap<u16, ([u8; 4], Material)>,
 }
 
 impl Chunk {
@@ -26,12 +28,27 @@ impl Chunk {
     /// Sets voxel state inside a chunk
     /// The voxel coordinate system is left handed
     pub fn set(&mut self, x: usize, y: usize, z: usize, state: bool, color: [u8; 4]) {
+        self.set_with_material(x, y, z, state, color, Material::Unknown);
+    }
+
+    /// Sets voxel state inside a chunk together with the material of the voxel
+    pub fn set_with_material(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        state: bool,
+        color: [u8; 4],
+        material: Material,
+    ) {
         assert!(x < CHUNK_SIZE);
         assert!(y < CHUNK_SIZE);
         assert!(z < CHUNK_SIZE);
 
-        self.colors
-            .insert(((z * 32 * 32) + ((31 - y) * 32) + x) as u16, color);
+        self.colors.insert(
+            ((z * 32 * 32) + ((31 - y) * 32) + x) as u16,
+            (color, material),
+        );
 
         if state {
             self.voxels[(z * 32) + (31 - y)] |= 2147483648 >> x;
@@ -40,6 +57,18 @@ impl Chunk {
         }
     }
 
+    /// Removes a voxel together with its color and material
+    pub fn clear(&mut self, x: usize, y: usize, z: usize) {
+        assert!(x < CHUNK_SIZE);
+        assert!(y < CHUNK_SIZE);
+        assert!(z < CHUNK_SIZE);
+
+        self.colors
+            .remove(&(((z * 32 * 32) + ((31 - y) * 32) + x) as u16));
+
+        self.voxels[(z * 32) + (31 - y)] &= u32::MAX ^ (2147483648 >> x);
+    }
+
     /// Gets a voxel state inside a chunk
     /// The voxel coordinate system is left handed
     pub fn get_occupied(&self, x: usize, y: usize, z: usize) -> bool {
@@ -57,12 +86,24 @@ impl Chunk {
 
         self.colors
             .get(&(((z * 32 * 32) + ((31 - y) * 32) + x) as u16))
-            .copied()
+            .map(|(color, _)| *color)
     }
 
-    pub fn remesh(&mut self, offsets: &mut [u16; 6], out: &mut Vec<Quad>) {
+    pub fn get_material(&self, x: usize, y: usize, z: usize) -> Option<Material> {
+        assert!(x < CHUNK_SIZE);
+        assert!(y < CHUNK_SIZE);
+        assert!(z < CHUNK_SIZE);
+
+        self.colors
+            .get(&(((z * 32 * 32) + ((31 - y) * 32) + x) as u16))
+            .map(|(_, material)| *material)
+    }
+
+    pub fn remesh(&mut self, offsets: &mut [u32; 6], out: &mut Vec<Quad>) {
         let mut buffer = [[0u32; 32]; 34];
 
+        offsets[0] = out.len() as u32;
+
         // X-Axis
         for n in 0..32 {
             self.slice(Axis::X, n, &mut buffer[n + 1]);
@@ -89,7 +130,7 @@ impl Chunk {
             }
         }
 
-        offsets[0] = out.len() as u16;
+        offsets[1] = out.len() as u32;
 
         for n in 1..33 {
             // "Vertical"
@@ -112,7 +153,7 @@ impl Chunk {
             }
         }
 
-        offsets[1] = out.len() as u16;
+        offsets[2] = out.len() as u32;
 
         // Y-Axis
         for n in 0..32 {
@@ -140,7 +181,7 @@ impl Chunk {
             }
         }
 
-        offsets[2] = out.len() as u16;
+        offsets[3] = out.len() as u32;
 
         for n in 1..33 {
             // "Vertical"
@@ -163,7 +204,7 @@ impl Chunk {
             }
         }
 
-        offsets[3] = out.len() as u16;
+        offsets[4] = out.len() as u32;
 
         // Z-Axis
         for n in 0..32 {
@@ -191,7 +232,7 @@ impl Chunk {
             }
         }
 
-        offsets[4] = out.len() as u16;
+        offsets[5] = out.len() as u32;
 
         for n in 1..33 {
             // "Vertical"
@@ -213,8 +254,6 @@ impl Chunk {
                 }
             }
         }
-
-        offsets[5] = out.len() as u16;
     }
 
     fn slice(&self, axis: Axis, n: usize, buffer: &mut [u32; 32]) {
@@ -243,6 +282,12 @@ impl Chunk {
         }
     }
 
+    /// Approximate heap memory used by the chunk, in bytes
+    pub fn memory_usage(&self) -> usize {
+        size_of::<[u32; 32 * 32]>()
+            + self.colors.capacity() * (size_of::<u16>() + size_of::<([u8; 4], Material)>() + 1)
+    }
+
     pub fn count(&self) -> usize {
         let mut count = 0;
 
@@ -1,4 +1,5 @@
-use cgmath::Vector3;
+use cgmath::{Array, Vector3};
+use std::ops::Range;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, Device,
@@ -7,7 +8,7 @@ use wgpu::{
 use crate::engine::geometry::plane::Plane;
 
 use super::{
-    chunk::{direction::Direction, Chunk},
+    chunk::{direction::Direction, Chunk, CHUNK_SIZE, VOXEL_SIZE},
     quad::Quad,
 };
 
@@ -17,7 +18,9 @@ pub struct ChunkMesh {
     /// On-Device quad buffer
     buffer: Option<Buffer>,
     /// Indices for face starts, (Left, Right, Up, Down, Front, Back)
-    offsets: [u16; 6],
+    offsets: [u32; 6],
+    /// Set when the chunk was edited since the last remesh
+    dirty: bool,
 }
 
 impl ChunkMesh {
@@ -26,7 +29,8 @@ impl ChunkMesh {
             chunk,
             quads: None,
             buffer: None,
-            offsets: [0u16; 6],
+            offsets: [0u32; 6],
+            dirty: false,
         }
     }
 
@@ -34,35 +38,64 @@ impl ChunkMesh {
         &self.chunk
     }
 
-    pub fn offsets(&self) -> &[u16; 6] {
+    pub fn offsets(&self) -> &[u32; 6] {
         &self.offsets
     }
 
+    /// Quads facing a direction, indexed like `offsets`
+    pub fn faces(&self, direction: usize) -> Range<u32> {
+        let end = match self.offsets.get(direction + 1) {
+            Some(end) => *end,
+            None => self.quads.as_ref().map(|q| q.len() as u32).unwrap_or(0),
+        };
+
+        self.offsets[direction]..end
+    }
+
     pub fn chunk_mut(&mut self) -> &mut Chunk {
         &mut self.chunk
     }
 
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
     pub fn quads(&self) -> Option<&[Quad]> {
         self.quads.as_deref()
     }
 
-    /// Returns which of the chunk sides are visible from the camera
-    pub fn visible(
-        &self,
-        eye: &Vector3<f32>,
-        position: Vector3<f32>,
-        _rotation: Vector3<f32>,
-    ) -> [bool; 6] {
+    /// Returns which face directions may face the camera. `eye` is in the space of the
+    /// object holding the chunk at `offset`, so rotated objects are handled too
+    pub fn visible(&self, eye: &Vector3<f32>, offset: Vector3<i32>) -> [bool; 6] {
         let mut visible = [false; 6];
 
+        // Voxels span [z - 1, z] on the z axis
+        let min = offset.map(|x| x as f32) * CHUNK_SIZE as f32 * VOXEL_SIZE
+            - Vector3::new(0.0, 0.0, VOXEL_SIZE);
+        let max = min + Vector3::new(1.0, 1.0, 1.0) * CHUNK_SIZE as f32 * VOXEL_SIZE;
+
+        // A face group can only be seen from in front of its rearmost face
         let planes = [
-            Plane::new(position, Direction::Left.unit_vector()),
-            Plane::new(position, Direction::Right.unit_vector()),
-            Plane::new(position, Direction::Up.unit_vector()),
-            Plane::new(position, Direction::Down.unit_vector()),
-            Plane::new(position, Direction::Front.unit_vector()),
-            Plane::new(position, Direction::Back.unit_vector()),
-        ];
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+            Direction::Front,
+            Direction::Back,
+        ]
+        .map(|direction| {
+            let normal = direction.unit_vector();
+
+            if normal.sum() > 0.0 {
+                Plane::new(min, normal)
+            } else {
+                Plane::new(max, normal)
+            }
+        });
 
         for (v, p) in visible.iter_mut().zip(planes.iter()) {
             *v = p.side(eye);
@@ -76,6 +109,7 @@ impl ChunkMesh {
         self.chunk.remesh(&mut self.offsets, &mut quads);
 
         self.quads = Some(quads);
+        self.dirty = false;
     }
 
     pub fn allocate(&mut self, device: &Device) -> bool {
@@ -92,6 +126,21 @@ impl ChunkMesh {
         false
     }
 
+    /// Approximate memory used by the chunk and its quads on the host, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.chunk.memory_usage()
+            + self
+                .quads
+                .as_ref()
+                .map(|q| q.capacity() * size_of::<Quad>())
+                .unwrap_or(0)
+    }
+
+    /// Memory used by the quad buffer on the device, in bytes
+    pub fn gpu_memory_usage(&self) -> usize {
+        self.buffer.as_ref().map(|b| b.size() as usize).unwrap_or(0)
+    }
+
     pub fn into_chunk(self) -> Chunk {
         self.chunk
     }
@@ -106,3 +155,32 @@ impl ChunkMesh {
         &self.buffer
     }
 }
+
+#[test]
+fn test_face_groups() {
+    let mut chunk = Chunk::empty();
+    chunk.set(4, 5, 6, true, [255; 4]);
+    chunk.set(5, 5, 6, true, [255; 4]);
+
+    let mut mesh = ChunkMesh::new(chunk);
+    mesh.remesh();
+
+    let quads = mesh.quads().unwrap();
+
+    for direction in 0..6 {
+        let faces = mesh.faces(direction);
+
+        assert!(quads[faces.start as usize..faces.end as usize]
+            .iter()
+            .all(|quad| quad.direction() as usize == direction));
+    }
+
+    assert_eq!(mesh.faces(0).len(), 1);
+    assert_eq!(mesh.faces(2).len(), 2);
+    assert_eq!(mesh.faces(5).end as usize, quads.len());
+
+    // Seen from +X, below and in front of -Z
+    let visible = mesh.visible(&Vector3::new(40.0, -8.0, -8.0), Vector3::new(0, 0, 0));
+
+    assert_eq!(visible, [true, false, false, true, true, false]);
+}
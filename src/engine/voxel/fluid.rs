@@ -0,0 +1,294 @@
+use super::chunk::material::Material;
+use super::world::{locate, TickQueue, VoxelWorld, HORIZONTAL};
+use ahash::HashMap;
+use cgmath::Vector3;
+
+/// Fill level of a full voxel
+pub const MAX_LEVEL: u8 = 8;
+/// Voxels updated per tick at most, the others wait for the next tick
+const MAX_UPDATES: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    /// Solid or not loaded
+    Blocked,
+    Air,
+    Water {
+        level: u8,
+        source: bool,
+    },
+}
+
+/// Cellular automaton letting water voxels fall and spread, state is kept per chunk.
+/// Water without a stored fill level is a source, like generated seas and rivers, which
+/// never drains. Water spilled from it keeps its level and conserves its volume
+#[derive(Default)]
+pub struct FluidSimulation {
+    /// Fill levels of the spilled water by chunk and index inside the chunk
+    levels: HashMap<Vector3<i32>, HashMap<u16, u8>>,
+    /// Voxels updated in the next tick
+    active: TickQueue,
+    tick: u64,
+}
+
+impl FluidSimulation {
+    /// Number of voxels updated in the next tick
+    pub fn active(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Returns if all water has settled
+    pub fn is_sleeping(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Call after a voxel was edited, forgets its fill level and wakes the water around it
+    pub fn wake(&mut self, position: Vector3<i32>) {
+        let (chunk_pos, index) = locate(position);
+
+        if let Some(levels) = self.levels.get_mut(&chunk_pos) {
+            levels.remove(&index);
+        }
+
+        self.active.push_around(position);
+    }
+
+    /// Call after water at `from` swapped places with a loose voxel from `to`,
+    /// the water keeps its fill level
+    pub fn displace(&mut self, from: Vector3<i32>, to: Vector3<i32>) {
+        let (chunk_pos, index) = locate(from);
+
+        let level = self
+            .levels
+            .get_mut(&chunk_pos)
+            .and_then(|levels| levels.remove(&index));
+
+        if let Some(level) = level {
+            let (chunk_pos, index) = locate(to);
+
+            self.levels
+                .entry(chunk_pos)
+                .or_default()
+                .insert(index, level);
+        }
+
+        self.active.push_around(from);
+        self.active.push_around(to);
+    }
+
+    /// Advances the water by one voxel, returns the number of updated voxels
+    pub fn step(&mut self, world: &mut impl VoxelWorld) -> usize {
+        self.tick += 1;
+
+        // Chunks that were unloaded are regenerated without the spilled water
+        self.levels
+            .retain(|chunk_pos, _| world.is_loaded(*chunk_pos));
+        self.active.retain_loaded(world);
+
+        // Lower voxels first, so falling columns move as a whole
+        let positions = self.active.take(MAX_UPDATES);
+
+        for position in &positions {
+            self.update(world, *position);
+        }
+
+        positions.len()
+    }
+
+    fn update(&mut self, world: &mut impl VoxelWorld, position: Vector3<i32>) {
+        let Cell::Water { mut level, source } = self.cell(world, position) else {
+            return;
+        };
+
+        let below = position - Vector3::unit_y();
+
+        match self.cell(world, below) {
+            Cell::Air => {
+                self.set_level(world, below, level);
+
+                if !source {
+                    self.set_level(world, position, 0);
+                }
+
+                return;
+            }
+            Cell::Water {
+                level: below_level,
+                source: false,
+            } if below_level < MAX_LEVEL => {
+                let moved = level.min(MAX_LEVEL - below_level);
+
+                self.set_level(world, below, below_level + moved);
+
+                if !source {
+                    level -= moved;
+                    self.set_level(world, position, level);
+                }
+            }
+            _ => {}
+        }
+
+        // Start with another side every tick, so no direction is preferred
+        for i in 0..HORIZONTAL.len() {
+            if level <= 1 {
+                break;
+            }
+
+            let neighbour = position + HORIZONTAL[(i + self.tick as usize) % HORIZONTAL.len()];
+
+            let neighbour_level = match self.cell(world, neighbour) {
+                Cell::Air => 0,
+                Cell::Water {
+                    level,
+                    source: false,
+                } => level,
+                _ => continue,
+            };
+
+            if neighbour_level + 1 >= level {
+                continue;
+            }
+
+            if source {
+                self.set_level(world, neighbour, MAX_LEVEL - 1);
+            } else {
+                self.set_level(world, neighbour, neighbour_level + 1);
+
+                level -= 1;
+                self.set_level(world, position, level);
+            }
+        }
+    }
+
+    fn cell(&self, world: &impl VoxelWorld, position: Vector3<i32>) -> Cell {
+        match world.voxel(position) {
+            Some(None) => Cell::Air,
+            Some(Some(Material::Water)) => {
+                let (chunk_pos, index) = locate(position);
+
+                match self
+                    .levels
+                    .get(&chunk_pos)
+                    .and_then(|levels| levels.get(&index))
+                {
+                    Some(level) => Cell::Water {
+                        level: *level,
+                        source: false,
+                    },
+                    None => Cell::Water {
+                        level: MAX_LEVEL,
+                        source: true,
+                    },
+                }
+            }
+            _ => Cell::Blocked,
+        }
+    }
+
+    /// Sets the level of spilled water, 0 turns the voxel into air
+    fn set_level(&mut self, world: &mut impl VoxelWorld, position: Vector3<i32>, level: u8) {
+        let (chunk_pos, index) = locate(position);
+
+        let levels = self.levels.entry(chunk_pos).or_default();
+
+        if level == 0 {
+            levels.remove(&index);
+            world.set_voxel(position, None);
+        } else {
+            match levels.insert(index, level) {
+                Some(previous) if previous == level => return,
+                // Only the level changed, the voxel looks the same
+                Some(_) => {}
+                None => {
+                    world.set_voxel(position, Some((Material::Water.color(), Material::Water)));
+                }
+            }
+        }
+
+        self.active.push_around(position);
+    }
+}
+
+#[test]
+fn test_flood() {
+    let mut world: HashMap<Vector3<i32>, ([u8; 4], Material)> = HashMap::default();
+
+    let stone = Some(([0; 4], Material::Stone));
+
+    // Walled floor with a dam at x = 0, a pool of source water behind it
+    for x in -5..=9 {
+        world.set_voxel(Vector3::new(x, 0, -2), stone);
+        world.set_voxel(Vector3::new(x, 0, 2), stone);
+    }
+
+    for z in -1..=1 {
+        for x in -4..=8 {
+            world.set_voxel(Vector3::new(x, -1, z), stone);
+        }
+
+        world.set_voxel(Vector3::new(-5, 0, z), stone);
+        world.set_voxel(Vector3::new(9, 0, z), stone);
+
+        world.set_voxel(Vector3::new(0, 0, z), stone);
+        world.set_voxel(Vector3::new(-1, 0, z), Some(([0; 4], Material::Water)));
+    }
+
+    let mut fluids = FluidSimulation::default();
+
+    fluids.wake(Vector3::new(0, 0, 0));
+
+    for _ in 0..64 {
+        fluids.step(&mut world);
+    }
+
+    assert!(fluids.is_sleeping());
+    assert_eq!(world.voxel(Vector3::new(1, 0, 0)), Some(None));
+
+    // Breaking the dam floods the floor behind it
+    world.set_voxel(Vector3::new(0, 0, 0), None);
+    fluids.wake(Vector3::new(0, 0, 0));
+
+    for _ in 0..64 {
+        fluids.step(&mut world);
+    }
+
+    assert!(fluids.is_sleeping());
+    assert_eq!(
+        world.voxel(Vector3::new(2, 0, 0)),
+        Some(Some(Material::Water))
+    );
+}
+
+#[test]
+fn test_displace() {
+    use super::granular::GranularSimulation;
+
+    let mut world: HashMap<Vector3<i32>, ([u8; 4], Material)> = HashMap::default();
+
+    world.set_voxel(Vector3::new(0, -1, 0), Some(([0; 4], Material::Stone)));
+
+    let mut fluids = FluidSimulation::default();
+    fluids.set_level(&mut world, Vector3::new(0, 0, 0), 3);
+
+    // Sand sinks through the water, which keeps its level above it
+    let mut sand = GranularSimulation::default();
+
+    world.set_voxel(Vector3::new(0, 1, 0), Some(([0; 4], Material::Sand)));
+    sand.wake(Vector3::new(0, 1, 0));
+
+    for (from, to) in sand.step(&mut world) {
+        fluids.displace(to, from);
+    }
+
+    assert_eq!(
+        world.voxel(Vector3::new(0, 0, 0)),
+        Some(Some(Material::Sand))
+    );
+    assert_eq!(
+        fluids.cell(&world, Vector3::new(0, 1, 0)),
+        Cell::Water {
+            level: 3,
+            source: false,
+        }
+    );
+}
@@ -0,0 +1,58 @@
+use crate::engine::voxel::chunk::{Chunk, CHUNK_SIZE};
+use cgmath::Vector3;
+use nalgebra::DMatrix;
+
+use super::TerrainGenerator;
+
+const LIGHT: [u8; 4] = [200, 200, 200, 255];
+const DARK: [u8; 4] = [60, 60, 60, 255];
+const LIGHT_TINTED: [u8; 4] = [200, 120, 120, 255];
+const DARK_TINTED: [u8; 4] = [60, 20, 20, 255];
+
+/// Single voxel thick debug floor at y = 0
+///
+/// Voxels alternate between a light and dark color, every other chunk is tinted
+/// so chunk borders are visible.
+#[derive(Default)]
+pub struct CheckerboardGenerator {}
+
+impl CheckerboardGenerator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl TerrainGenerator for CheckerboardGenerator {
+    fn generate(&self, chunk_pos: Vector3<i32>) -> Option<Chunk> {
+        if chunk_pos.y != 0 {
+            return None;
+        }
+
+        let tinted = (chunk_pos.x + chunk_pos.z).rem_euclid(2) == 1;
+
+        let mut chunk = Chunk::empty();
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let color = match ((x + z) % 2 == 0, tinted) {
+                    (true, false) => LIGHT,
+                    (false, false) => DARK,
+                    (true, true) => LIGHT_TINTED,
+                    (false, true) => DARK_TINTED,
+                };
+
+                chunk.set(x, 0, z, true, color);
+            }
+        }
+
+        Some(chunk)
+    }
+
+    fn heights(&self, chunk_pos: Vector3<i32>) -> Option<DMatrix<f32>> {
+        if chunk_pos.y != 0 {
+            return None;
+        }
+
+        Some(DMatrix::zeros(CHUNK_SIZE, CHUNK_SIZE))
+    }
+}
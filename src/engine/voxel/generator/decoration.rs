@@ -0,0 +1,232 @@
+use crate::engine::voxel::chunk::material::Material;
+use cgmath::Vector3;
+
+use super::{hash, strata::SurfaceLines};
+
+/// Side of the grid cells holding at most one feature each, in voxels
+const CELL_SIZE: i32 = 8;
+/// Salt of the vegetation cells, see [`hash`]
+const SALT: u64 = 0x5EED_0001;
+/// Largest horizontal distance of a feature voxel from its anchor
+pub const MAX_FEATURE_RADIUS: i32 = 3;
+/// Largest height of a feature above its anchor
+pub const MAX_FEATURE_HEIGHT: i32 = 14;
+/// Height difference to the neighbouring columns above which nothing grows
+const MAX_PLANT_SLOPE: i32 = 2;
+
+const TRUNK: [u8; 4] = [89, 60, 31, 255];
+const LEAVES: [u8; 4] = [40, 110, 30, 255];
+const NEEDLES: [u8; 4] = [20, 80, 40, 255];
+const BUSH: [u8; 4] = [50, 120, 35, 255];
+const STONE: [u8; 4] = [110, 110, 105, 255];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    /// Broadleaf tree with a round crown
+    Tree {
+        trunk: i32,
+        crown: i32,
+    },
+    /// Conifer with a conical crown
+    Pine {
+        trunk: i32,
+    },
+    Bush,
+    Boulder {
+        radius: i32,
+    },
+}
+
+impl Feature {
+    /// Voxels relative to the anchor, which is the first voxel above the ground.
+    /// Voxels listed earlier win where they overlap
+    pub fn voxels(&self, out: &mut Vec<([i32; 3], [u8; 4], Material)>) {
+        match *self {
+            Feature::Tree { trunk, crown } => {
+                for y in 0..trunk {
+                    out.push(([0, y, 0], TRUNK, Material::Wood));
+                }
+
+                let center = trunk + crown - 1;
+
+                for y in -crown..=crown {
+                    for z in -crown..=crown {
+                        for x in -crown..=crown {
+                            if x * x + y * y + z * z <= crown * crown + 1 {
+                                out.push(([x, center + y, z], LEAVES, Material::Leaves));
+                            }
+                        }
+                    }
+                }
+            }
+            Feature::Pine { trunk } => {
+                for y in 0..trunk {
+                    out.push(([0, y, 0], TRUNK, Material::Wood));
+                }
+
+                // Layers shrink towards the top
+                let layers = MAX_FEATURE_HEIGHT - trunk;
+
+                for layer in 0..layers {
+                    let radius = (MAX_FEATURE_RADIUS * (layers - layer)) / layers;
+
+                    for z in -radius..=radius {
+                        for x in -radius..=radius {
+                            if x.abs() + z.abs() <= radius {
+                                out.push(([x, trunk - 2 + layer, z], NEEDLES, Material::Leaves));
+                            }
+                        }
+                    }
+                }
+            }
+            Feature::Bush => {
+                for y in 0..2 {
+                    for z in -1..=1 {
+                        for x in -1..=1 {
+                            if y == 0 || x * z == 0 {
+                                out.push(([x, y, z], BUSH, Material::Leaves));
+                            }
+                        }
+                    }
+                }
+            }
+            Feature::Boulder { radius } => {
+                for y in -1..=radius {
+                    for z in -radius..=radius {
+                        for x in -radius..=radius {
+                            if x * x + y * y + z * z <= radius * radius {
+                                out.push(([x, y, z], STONE, Material::Stone));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Feature anchored at a world position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub anchor: Vector3<i32>,
+    pub feature: Feature,
+}
+
+/// Features whose voxels may reach into the area between `min` and `max` on the XZ plane.
+/// `ground` returns the surface height and slope of a column, `density` is the chance of
+/// a cell holding a feature. Nothing grows on beaches and snow, above the tree line only
+/// conifers and boulders. The result only depends on the arguments, so neighbouring
+/// chunks agree on the features crossing their border
+pub fn placements(
+    seed: u32,
+    density: f64,
+    lines: SurfaceLines,
+    min: (i32, i32),
+    max: (i32, i32),
+    ground: impl Fn(i32, i32) -> (i32, i32),
+) -> Vec<Placement> {
+    let mut placements = Vec::new();
+
+    let cells = |min: i32, max: i32| {
+        (min - MAX_FEATURE_RADIUS).div_euclid(CELL_SIZE)
+            ..=(max + MAX_FEATURE_RADIUS).div_euclid(CELL_SIZE)
+    };
+
+    for cell_z in cells(min.1, max.1) {
+        for cell_x in cells(min.0, max.0) {
+            let hash = hash(seed, cell_x, cell_z, SALT);
+
+            if unit(hash) >= density {
+                continue;
+            }
+
+            let x = cell_x * CELL_SIZE + ((hash >> 8) % CELL_SIZE as u64) as i32;
+            let z = cell_z * CELL_SIZE + ((hash >> 16) % CELL_SIZE as u64) as i32;
+
+            if x + MAX_FEATURE_RADIUS < min.0
+                || x - MAX_FEATURE_RADIUS > max.0
+                || z + MAX_FEATURE_RADIUS < min.1
+                || z - MAX_FEATURE_RADIUS > max.1
+            {
+                continue;
+            }
+
+            let (height, slope) = ground(x, z);
+
+            if let Some(feature) = select(hash >> 24, lines, height, slope) {
+                placements.push(Placement {
+                    anchor: Vector3::new(x, height + 1, z),
+                    feature,
+                });
+            }
+        }
+    }
+
+    placements
+}
+
+/// Picks a feature fitting the biome at `height`
+fn select(hash: u64, lines: SurfaceLines, height: i32, slope: i32) -> Option<Feature> {
+    let roll = hash % 100;
+    let variant = ((hash >> 8) & 0xFFFF) as i32;
+
+    if height <= lines.sand || height > lines.snow {
+        return None;
+    }
+
+    if slope > MAX_PLANT_SLOPE {
+        return (roll < 30).then_some(Feature::Boulder {
+            radius: 1 + variant % 2,
+        });
+    }
+
+    if height > lines.tree {
+        return Some(match roll {
+            0..60 => Feature::Pine {
+                trunk: 4 + variant % 3,
+            },
+            _ => Feature::Boulder {
+                radius: 1 + variant % 2,
+            },
+        });
+    }
+
+    Some(match roll {
+        0..55 => Feature::Tree {
+            trunk: 4 + variant % 3,
+            crown: 2 + variant % 2,
+        },
+        55..70 => Feature::Pine {
+            trunk: 4 + variant % 3,
+        },
+        70..95 => Feature::Bush,
+        _ => Feature::Boulder { radius: 1 },
+    })
+}
+
+/// Maps the low bits of a hash onto `[0, 1)`
+fn unit(hash: u64) -> f64 {
+    (hash & 0xFF) as f64 / 256.0
+}
+
+#[test]
+fn test_placements_across_border() {
+    let ground = |_, _| (50, 0);
+
+    // Two neighbouring chunks see the same features near their common border
+    let lines = SurfaceLines::default();
+
+    let left = placements(7, 1.0, lines, (0, 0), (31, 31), ground);
+    let right = placements(7, 1.0, lines, (32, 0), (63, 31), ground);
+
+    let shared: Vec<_> = left
+        .iter()
+        .filter(|p| p.anchor.x >= 32 - MAX_FEATURE_RADIUS)
+        .collect();
+
+    assert!(!shared.is_empty());
+
+    for placement in shared {
+        assert!(right.contains(placement));
+    }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+/// Grid based hydraulic and thermal erosion applied to height tiles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErosionParameters {
+    /// Hydraulic iterations, 0 disables erosion
+    pub iterations: u32,
+    /// Water added to every cell per iteration
+    pub rain: f32,
+    /// Height dissolved per unit of water and iteration
+    pub solubility: f32,
+    /// Fraction of the water that evaporates per iteration
+    pub evaporation: f32,
+    /// Sediment a unit of water can carry
+    pub capacity: f32,
+    /// Thermal iterations run after the hydraulic ones
+    pub thermal_iterations: u32,
+    /// Height difference between neighbours above which material slides down
+    pub talus: f32,
+    /// Fraction of the excess height moved per thermal iteration
+    pub thermal_rate: f32,
+}
+
+impl Default for ErosionParameters {
+    fn default() -> Self {
+        Self {
+            iterations: 30,
+            rain: 0.5,
+            solubility: 0.05,
+            evaporation: 0.5,
+            capacity: 0.1,
+            thermal_iterations: 10,
+            talus: 1.5,
+            thermal_rate: 0.25,
+        }
+    }
+}
+
+const NEIGHBOURS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// Erodes a square height grid of `size * size` cells indexed by `z * size + x`.
+/// The result only depends on the input, cells near the border are less accurate
+/// and should be cropped away by the caller
+pub fn erode(heights: &mut [f32], size: usize, parameters: &ErosionParameters) {
+    assert_eq!(heights.len(), size * size);
+
+    hydraulic(heights, size, parameters);
+    thermal(heights, size, parameters);
+}
+
+fn neighbours(i: usize, size: usize) -> impl Iterator<Item = usize> {
+    let x = (i % size) as isize;
+    let z = (i / size) as isize;
+
+    NEIGHBOURS.iter().filter_map(move |(dx, dz)| {
+        let (x, z) = (x + dx, z + dz);
+
+        if x >= 0 && z >= 0 && (x as usize) < size && (z as usize) < size {
+            Some(z as usize * size + x as usize)
+        } else {
+            None
+        }
+    })
+}
+
+fn hydraulic(heights: &mut [f32], size: usize, parameters: &ErosionParameters) {
+    let cells = heights.len();
+
+    let mut water = vec![0.0f32; cells];
+    let mut sediment = vec![0.0f32; cells];
+
+    // Changes are collected first, so the result doesn't depend on the iteration order
+    let mut water_delta = vec![0.0f32; cells];
+    let mut sediment_delta = vec![0.0f32; cells];
+
+    for _ in 0..parameters.iterations {
+        for i in 0..cells {
+            water[i] += parameters.rain;
+
+            let dissolved = parameters.solubility * water[i];
+            heights[i] -= dissolved;
+            sediment[i] += dissolved;
+        }
+
+        water_delta.fill(0.0);
+        sediment_delta.fill(0.0);
+
+        for i in 0..cells {
+            if water[i] <= 0.0 {
+                continue;
+            }
+
+            let level = heights[i] + water[i];
+
+            let total: f32 = neighbours(i, size)
+                .map(|n| (level - heights[n] - water[n]).max(0.0))
+                .sum();
+
+            if total <= 0.0 {
+                continue;
+            }
+
+            // Move at most half of the difference to avoid oscillation
+            let moved = water[i].min(total / 2.0);
+            let carried = sediment[i] * moved / water[i];
+
+            for n in neighbours(i, size) {
+                let share = (level - heights[n] - water[n]).max(0.0) / total;
+
+                water_delta[n] += moved * share;
+                sediment_delta[n] += carried * share;
+            }
+
+            water_delta[i] -= moved;
+            sediment_delta[i] -= carried;
+        }
+
+        for i in 0..cells {
+            water[i] += water_delta[i];
+            sediment[i] += sediment_delta[i];
+
+            water[i] *= 1.0 - parameters.evaporation;
+
+            let capacity = parameters.capacity * water[i];
+
+            if sediment[i] > capacity {
+                heights[i] += sediment[i] - capacity;
+                sediment[i] = capacity;
+            }
+        }
+    }
+
+    for i in 0..cells {
+        heights[i] += sediment[i];
+    }
+}
+
+fn thermal(heights: &mut [f32], size: usize, parameters: &ErosionParameters) {
+    let cells = heights.len();
+
+    let mut delta = vec![0.0f32; cells];
+
+    for _ in 0..parameters.thermal_iterations {
+        delta.fill(0.0);
+
+        for i in 0..cells {
+            let excess: f32 = neighbours(i, size)
+                .map(|n| (heights[i] - heights[n] - parameters.talus).max(0.0))
+                .sum();
+
+            if excess <= 0.0 {
+                continue;
+            }
+
+            let moved = excess * parameters.thermal_rate / 2.0;
+
+            for n in neighbours(i, size) {
+                delta[n] += moved * (heights[i] - heights[n] - parameters.talus).max(0.0) / excess;
+            }
+
+            delta[i] -= moved;
+        }
+
+        for i in 0..cells {
+            heights[i] += delta[i];
+        }
+    }
+}
+
+#[test]
+fn test_erode() {
+    const SIZE: usize = 16;
+
+    let mut heights = vec![0.0f32; SIZE * SIZE];
+    heights[8 * SIZE + 8] = 64.0;
+
+    let mass: f32 = heights.iter().sum();
+
+    erode(&mut heights, SIZE, &ErosionParameters::default());
+
+    // The peak is worn down without losing material
+    assert!(heights[8 * SIZE + 8] < 64.0);
+    assert!((heights.iter().sum::<f32>() - mass).abs() < 0.01);
+}
@@ -0,0 +1,80 @@
+use crate::{
+    engine::voxel::chunk::{Chunk, CHUNK_SIZE},
+    io::load_voxels,
+};
+use ahash::{HashMap, HashMapExt};
+use cgmath::Vector3;
+use nalgebra::DMatrix;
+use std::path::Path;
+
+use super::TerrainGenerator;
+
+/// Chunk local voxel positions with their colors
+type ChunkVoxels = Vec<([usize; 3], [u8; 4])>;
+
+/// Terrain made from a VOXELSRS file, voxel positions are used as world positions
+pub struct FileGenerator {
+    chunks: HashMap<Vector3<i32>, ChunkVoxels>,
+}
+
+impl FileGenerator {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self::from_voxels(load_voxels(path))
+    }
+
+    pub fn from_voxels(voxels: Vec<([i32; 3], [u8; 4])>) -> Self {
+        let mut chunks: HashMap<Vector3<i32>, ChunkVoxels> = HashMap::new();
+
+        for (voxel, color) in voxels {
+            let chunk_pos = Vector3::new(
+                voxel[0].div_euclid(CHUNK_SIZE as i32),
+                voxel[1].div_euclid(CHUNK_SIZE as i32),
+                voxel[2].div_euclid(CHUNK_SIZE as i32),
+            );
+
+            let local = [
+                voxel[0].rem_euclid(CHUNK_SIZE as i32) as usize,
+                voxel[1].rem_euclid(CHUNK_SIZE as i32) as usize,
+                voxel[2].rem_euclid(CHUNK_SIZE as i32) as usize,
+            ];
+
+            chunks.entry(chunk_pos).or_default().push((local, color));
+        }
+
+        Self { chunks }
+    }
+}
+
+impl TerrainGenerator for FileGenerator {
+    fn generate(&self, chunk_pos: Vector3<i32>) -> Option<Chunk> {
+        let voxels = self.chunks.get(&chunk_pos)?;
+
+        let mut chunk = Chunk::empty();
+
+        for ([x, y, z], color) in voxels {
+            chunk.set(*x, *y, *z, true, *color);
+        }
+
+        Some(chunk)
+    }
+
+    fn heights(&self, _chunk_pos: Vector3<i32>) -> Option<DMatrix<f32>> {
+        None
+    }
+}
+
+#[test]
+fn test_from_voxels() {
+    let generator =
+        FileGenerator::from_voxels(vec![([0, 0, 0], [255u8; 4]), ([-1, 33, -32], [128u8; 4])]);
+
+    let chunk = generator.generate(Vector3::new(0, 0, 0)).unwrap();
+    assert!(chunk.get_occupied(0, 0, 0));
+    assert_eq!(chunk.get_color(0, 0, 0), Some([255u8; 4]));
+
+    let chunk = generator.generate(Vector3::new(-1, 1, -1)).unwrap();
+    assert!(chunk.get_occupied(31, 1, 0));
+    assert_eq!(chunk.get_color(31, 1, 0), Some([128u8; 4]));
+
+    assert!(generator.generate(Vector3::new(1, 0, 0)).is_none());
+}
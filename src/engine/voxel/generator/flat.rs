@@ -0,0 +1,54 @@
+use crate::engine::voxel::chunk::{Chunk, CHUNK_SIZE};
+use cgmath::Vector3;
+use nalgebra::DMatrix;
+
+use super::TerrainGenerator;
+
+/// Flat ground filled up to a fixed height with a single color
+pub struct FlatGenerator {
+    height: i32,
+    color: [u8; 4],
+}
+
+impl FlatGenerator {
+    pub fn new(height: i32, color: [u8; 4]) -> Self {
+        Self { height, color }
+    }
+}
+
+impl TerrainGenerator for FlatGenerator {
+    fn generate(&self, chunk_pos: Vector3<i32>) -> Option<Chunk> {
+        let min_y = chunk_pos.y * CHUNK_SIZE as i32;
+
+        if self.height < min_y {
+            return None;
+        }
+
+        let local_height = ((self.height - min_y) as usize).min(CHUNK_SIZE - 1);
+
+        let mut chunk = Chunk::empty();
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                for y in 0..=local_height {
+                    chunk.set(x, y, z, true, self.color);
+                }
+            }
+        }
+
+        Some(chunk)
+    }
+
+    fn heights(&self, chunk_pos: Vector3<i32>) -> Option<DMatrix<f32>> {
+        // The heightfield covers the whole column, only the surface chunk carries it
+        if self.height.div_euclid(CHUNK_SIZE as i32) != chunk_pos.y {
+            return None;
+        }
+
+        Some(DMatrix::from_element(
+            CHUNK_SIZE,
+            CHUNK_SIZE,
+            self.height as f32,
+        ))
+    }
+}
@@ -0,0 +1,59 @@
+use cgmath::Vector3;
+use nalgebra::DMatrix;
+
+use super::chunk::Chunk;
+
+pub mod checkerboard;
+pub mod decoration;
+pub mod erosion;
+pub mod file;
+pub mod flat;
+pub mod noise;
+pub mod river;
+pub mod strata;
+pub mod structure;
+
+/// SplitMix64 of the seed and a grid cell. Every feature grid passes its own `salt`,
+/// so the grids of the same seed don't line up
+pub fn hash(seed: u32, x: i32, z: i32, salt: u64) -> u64 {
+    let mut hash = ((seed as u64) << 32)
+        ^ salt
+        ^ (x as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (z as u32 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^ (hash >> 31)
+}
+
+/// Source of terrain chunks driven by [`Terrain`](super::terrain::Terrain)
+pub trait TerrainGenerator: Send + Sync {
+    /// Generates the chunk at `chunk_pos`, returns `None` if the chunk is empty
+    fn generate(&self, chunk_pos: Vector3<i32>) -> Option<Chunk>;
+
+    /// Column heights of the chunk at `chunk_pos` used for its heightfield collider,
+    /// indexed by `(z, x)`. Returns `None` if the chunk has no collider
+    fn heights(&self, chunk_pos: Vector3<i32>) -> Option<DMatrix<f32>>;
+
+    /// Returns if a chunk `generate` returned `None` for is all air,
+    /// rather than solid ground that is never drawn
+    fn is_air(&self, _chunk_pos: Vector3<i32>) -> bool {
+        true
+    }
+
+    /// Generates a chunk of solid ground `generate` skipped, called once it is edited
+    fn generate_solid(&self, _chunk_pos: Vector3<i32>) -> Option<Chunk> {
+        None
+    }
+
+    /// Called after a chunk went out of range, so cached data can be dropped
+    fn unload(&self, _chunk_pos: Vector3<i32>) {}
+
+    /// Memory used by internal caches, in bytes
+    fn memory_usage(&self) -> usize {
+        0
+    }
+
+    /// Evicts the least recently used cache entries until at most `budget` bytes are used
+    fn trim(&self, _budget: usize) {}
+}
@@ -0,0 +1,917 @@
+use crate::engine::voxel::chunk::{material::Material, Chunk, CHUNK_SIZE};
+use ahash::{HashMap, HashMapExt};
+use cgmath::Vector3;
+use colorgrad::Gradient;
+use nalgebra::DMatrix;
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use super::decoration::{placements, MAX_FEATURE_HEIGHT, MAX_FEATURE_RADIUS};
+use super::erosion::{erode, ErosionParameters};
+use super::river::River;
+use super::strata::{Strata, StrataParameters, SurfaceLines};
+use super::structure::{sites, StructureSite, StructureTemplate, FLATTEN_MARGIN};
+use super::TerrainGenerator;
+
+/// Lowest world height covered by the gradient, lower voxels get its first color
+pub const GRADIENT_MIN_HEIGHT: f64 = -128.0;
+/// Highest world height covered by the gradient, higher voxels get its last color
+pub const GRADIENT_MAX_HEIGHT: f64 = 512.0;
+
+/// Shape of the generated terrain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseParameters {
+    /// Horizontal scale of the base octave
+    pub scale: f64,
+    pub height_multiplier: f64,
+    pub octaves: u32,
+    /// Amplitude factor between two octaves
+    pub persistence: f64,
+    /// Amplitude of the small scale detail noise
+    pub detail_scale: f64,
+    /// Strength of the noise applied to the gradient position of voxels
+    pub noise_intensity: f64,
+    pub erosion: ErosionParameters,
+    /// Height up to which basins are filled with water
+    pub sea_level: i32,
+    /// Chance of a river cell holding a river source, 0 disables rivers
+    pub rivers: f64,
+    /// Chance of a vegetation cell holding a tree, bush or boulder, 0 disables decoration
+    pub vegetation: f64,
+    pub strata: StrataParameters,
+}
+
+impl Default for NoiseParameters {
+    fn default() -> Self {
+        Self {
+            scale: 0.001,
+            height_multiplier: 100.0,
+            octaves: 4,
+            persistence: 0.5,
+            detail_scale: 2.0,
+            noise_intensity: 3.0,
+            erosion: ErosionParameters::default(),
+            sea_level: 32,
+            rivers: 0.5,
+            vegetation: 0.6,
+            strata: StrataParameters::default(),
+        }
+    }
+}
+
+/// Cells around a height tile that are eroded along with it. Every erosion iteration
+/// reads the neighbours of the neighbours of a cell, so the wrong values at the edge of
+/// the grid move two cells further in per iteration
+fn erosion_border(parameters: &ErosionParameters) -> usize {
+    2 * (parameters.iterations + parameters.thermal_iterations) as usize
+}
+
+/// Side of the square regions that are eroded at once, in tiles. Tiles are cropped from
+/// their region, so the erosion border is shared by all of them
+const EROSION_REGION: usize = 8;
+
+/// Memory used by one cached height tile, in bytes
+const TILE_MEMORY: usize =
+    CHUNK_SIZE * CHUNK_SIZE * size_of::<i32>() * 2 + size_of::<((i32, i32), HeightTile)>();
+
+/// Memory used by one cached erosion region, in bytes
+const REGION_MEMORY: usize = (EROSION_REGION * CHUNK_SIZE).pow(2) * size_of::<i32>()
+    + size_of::<((i32, i32), (Arc<OnceLock<Arc<[i32]>>>, u64))>();
+
+/// Perlin heightmap terrain colored by a height gradient
+pub struct NoiseGenerator {
+    seed: u32,
+    parameters: NoiseParameters,
+    gradient: Box<dyn Gradient + Send + Sync>,
+    structures: Arc<[StructureTemplate]>,
+    surface: SurfaceLines,
+    height_cache: Mutex<HashMap<(i32, i32), HeightTile>>,
+    /// Rivers by source cell with their last use, `None` for cells without a river
+    #[allow(clippy::type_complexity)]
+    rivers: Mutex<HashMap<(i32, i32), (Option<Arc<River>>, u64)>>,
+    /// Eroded heights by region with their last use, filled by the first tile needing them
+    #[allow(clippy::type_complexity)]
+    regions: Mutex<HashMap<(i32, i32), (Arc<OnceLock<Arc<[i32]>>>, u64)>>,
+    /// Incremented on every cache access, used to find the least recently used tiles
+    tick: AtomicU64,
+}
+
+/// Ground heights and water levels of one chunk column, indexed by `z * CHUNK_SIZE + x`
+struct HeightTile {
+    heights: Arc<[i32]>,
+    water: Arc<[i32]>,
+    /// Lowest ground and highest ground or water
+    bounds: (i32, i32),
+    last_used: u64,
+}
+
+impl NoiseGenerator {
+    pub fn new(
+        seed: u32,
+        parameters: NoiseParameters,
+        gradient: Box<dyn Gradient + Send + Sync>,
+    ) -> Self {
+        Self {
+            seed,
+            parameters,
+            gradient,
+            structures: Arc::new([]),
+            surface: SurfaceLines::default(),
+            height_cache: Mutex::new(HashMap::new()),
+            rivers: Mutex::new(HashMap::new()),
+            regions: Mutex::new(HashMap::new()),
+            tick: AtomicU64::new(0),
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn parameters(&self) -> &NoiseParameters {
+        &self.parameters
+    }
+
+    /// Stamps the templates into the terrain at seed derived sites
+    pub fn with_structures(mut self, structures: Arc<[StructureTemplate]>) -> Self {
+        self.structures = structures;
+        self
+    }
+
+    /// Places beaches, trees and snow at the given heights
+    pub fn with_surface(mut self, surface: SurfaceLines) -> Self {
+        self.surface = surface;
+        self
+    }
+
+    /// Structures overlapping the area, placed on the uneroded height of their center
+    fn sites(&self, min: (i32, i32), max: (i32, i32)) -> Vec<StructureSite> {
+        sites(self.seed, &self.structures, min, max, |x, z| {
+            heightmap(self.seed, &self.parameters, x, z).floor() as i32
+        })
+    }
+
+    /// River starting in a river cell, traced on the uneroded heights
+    fn river(&self, cell: (i32, i32)) -> Option<Arc<River>> {
+        let tick = self.tick.fetch_add(1, Ordering::Relaxed);
+
+        if let Some((river, last_used)) = self.rivers.lock().unwrap().get_mut(&cell) {
+            *last_used = tick;
+            return river.clone();
+        }
+
+        let river = River::trace(
+            self.seed,
+            cell,
+            self.parameters.rivers,
+            self.parameters.sea_level,
+            |x, z| {
+                heightmap(
+                    self.seed,
+                    &self.parameters,
+                    x.round() as i32,
+                    z.round() as i32,
+                ) as f32
+            },
+        )
+        .map(Arc::new);
+
+        self.rivers
+            .lock()
+            .unwrap()
+            .insert(cell, (river.clone(), tick));
+
+        river
+    }
+
+    /// Returns the heights and water levels of a chunk column with their bounds
+    #[allow(clippy::type_complexity)]
+    fn get_cached_heights(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> (Arc<[i32]>, Arc<[i32]>, (i32, i32)) {
+        let tick = self.tick.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(tile) = self
+            .height_cache
+            .lock()
+            .unwrap()
+            .get_mut(&(chunk_x, chunk_z))
+        {
+            tile.last_used = tick;
+            return (tile.heights.clone(), tile.water.clone(), tile.bounds);
+        }
+
+        let min_x = chunk_x * CHUNK_SIZE as i32;
+        let min_z = chunk_z * CHUNK_SIZE as i32;
+
+        let mut heights = if self.parameters.erosion.iterations == 0 {
+            let mut heights = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE);
+
+            for z in 0..CHUNK_SIZE as i32 {
+                for x in 0..CHUNK_SIZE as i32 {
+                    heights.push(
+                        heightmap(self.seed, &self.parameters, min_x + x, min_z + z).floor() as i32,
+                    );
+                }
+            }
+
+            heights
+        } else {
+            self.eroded_heights(chunk_x, chunk_z)
+        };
+
+        let max = (min_x + CHUNK_SIZE as i32 - 1, min_z + CHUNK_SIZE as i32 - 1);
+
+        let mut water = vec![self.parameters.sea_level; CHUNK_SIZE * CHUNK_SIZE];
+
+        if self.parameters.rivers > 0.0 {
+            for cell in River::cells((min_x, min_z), max) {
+                if let Some(river) = self.river(cell) {
+                    river.carve((min_x, min_z), CHUNK_SIZE, &mut heights, &mut water);
+                }
+            }
+        }
+
+        // Flatten the ground below structures, which also fills their foundations
+        for site in self.sites((min_x, min_z), max) {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let i = z * CHUNK_SIZE + x;
+                    let (world_x, world_z) = (min_x + x as i32, min_z + z as i32);
+
+                    heights[i] = site.flatten(&self.structures, world_x, world_z, heights[i]);
+
+                    // Keep rivers out of the flattened ground
+                    if site.distance(&self.structures, world_x, world_z) <= FLATTEN_MARGIN {
+                        water[i] = self.parameters.sea_level;
+                    }
+                }
+            }
+        }
+
+        let bounds = (
+            *heights.iter().min().unwrap(),
+            heights
+                .iter()
+                .zip(&water)
+                .map(|(height, water)| *height.max(water))
+                .max()
+                .unwrap(),
+        );
+
+        let heights: Arc<[i32]> = heights.into();
+        let water: Arc<[i32]> = water.into();
+
+        self.height_cache.lock().unwrap().insert(
+            (chunk_x, chunk_z),
+            HeightTile {
+                heights: heights.clone(),
+                water: water.clone(),
+                bounds,
+                last_used: tick,
+            },
+        );
+
+        (heights, water, bounds)
+    }
+
+    /// Ground height and water level of a world column
+    fn column(&self, x: i32, z: i32) -> (i32, i32) {
+        let (heights, water, _) = self.get_cached_heights(
+            x.div_euclid(CHUNK_SIZE as i32),
+            z.div_euclid(CHUNK_SIZE as i32),
+        );
+
+        let i = z.rem_euclid(CHUNK_SIZE as i32) as usize * CHUNK_SIZE
+            + x.rem_euclid(CHUNK_SIZE as i32) as usize;
+
+        (heights[i], water[i])
+    }
+
+    /// Surface height of a world column and the largest height difference to its neighbours
+    fn ground(&self, x: i32, z: i32) -> (i32, i32) {
+        let center = self.column(x, z).0;
+
+        let slope = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .iter()
+            .map(|(dx, dz)| (self.column(x + dx, z + dz).0 - center).abs())
+            .max()
+            .unwrap();
+
+        (center, slope)
+    }
+
+    /// Places the parts of the structures lying inside the chunk, returns if any voxel was set
+    fn stamp(&self, chunk: &mut Chunk, chunk_pos: Vector3<i32>, sites: &[StructureSite]) -> bool {
+        let min = chunk_pos * CHUNK_SIZE as i32;
+
+        let mut placed = false;
+
+        for site in sites {
+            let template = &self.structures[site.template];
+
+            let offset = site.origin - min;
+
+            if offset.y + template.size().y <= 0 || offset.y >= CHUNK_SIZE as i32 {
+                continue;
+            }
+
+            for ([x, y, z], color, material) in template.voxels() {
+                if let Some((x, y, z)) = chunk_local(offset + Vector3::new(*x, *y, *z)) {
+                    chunk.set_with_material(x, y, z, true, *color, *material);
+                    placed = true;
+                }
+            }
+        }
+
+        placed
+    }
+
+    /// Places the features reaching into the chunk, returns if any voxel was set
+    fn decorate(
+        &self,
+        chunk: &mut Chunk,
+        chunk_pos: Vector3<i32>,
+        sites: &[StructureSite],
+    ) -> bool {
+        let min = chunk_pos * CHUNK_SIZE as i32;
+        let max = min + Vector3::new(1, 1, 1) * (CHUNK_SIZE as i32 - 1);
+
+        let mut placed = false;
+        let mut voxels = Vec::new();
+
+        for placement in placements(
+            self.seed,
+            self.parameters.vegetation,
+            self.surface,
+            (min.x, min.z),
+            (max.x, max.z),
+            |x, z| self.ground(x, z),
+        ) {
+            if placement.anchor.y + MAX_FEATURE_HEIGHT < min.y
+                || placement.anchor.y - MAX_FEATURE_RADIUS > max.y
+            {
+                continue;
+            }
+
+            // Nothing grows under water
+            if self.column(placement.anchor.x, placement.anchor.z).1 >= placement.anchor.y {
+                continue;
+            }
+
+            // Keep structures and their surroundings clear
+            if sites.iter().any(|site| {
+                site.distance(&self.structures, placement.anchor.x, placement.anchor.z)
+                    <= FLATTEN_MARGIN
+            }) {
+                continue;
+            }
+
+            voxels.clear();
+            placement.feature.voxels(&mut voxels);
+
+            for ([x, y, z], color, material) in &voxels {
+                let Some((x, y, z)) =
+                    chunk_local(placement.anchor + Vector3::new(*x, *y, *z) - min)
+                else {
+                    continue;
+                };
+
+                // Terrain and earlier features win
+                if !chunk.get_occupied(x, y, z) {
+                    chunk.set_with_material(x, y, z, true, *color, *material);
+                    placed = true;
+                }
+            }
+        }
+
+        placed
+    }
+
+    /// Crops the tile from the eroded heights of its region, the region is eroded by the
+    /// first tile needing it while the others wait
+    fn eroded_heights(&self, chunk_x: i32, chunk_z: i32) -> Vec<i32> {
+        let tick = self.tick.fetch_add(1, Ordering::Relaxed);
+
+        let region = (
+            chunk_x.div_euclid(EROSION_REGION as i32),
+            chunk_z.div_euclid(EROSION_REGION as i32),
+        );
+
+        let cell = {
+            let mut regions = self.regions.lock().unwrap();
+
+            let (cell, last_used) = regions
+                .entry(region)
+                .or_insert_with(|| (Arc::default(), tick));
+
+            *last_used = tick;
+            cell.clone()
+        };
+
+        let width = EROSION_REGION * CHUNK_SIZE;
+
+        let heights = cell.get_or_init(|| {
+            self.eroded_area(region.0 * width as i32, region.1 * width as i32, width)
+                .into_iter()
+                .map(|height| height.floor() as i32)
+                .collect()
+        });
+
+        let x = chunk_x.rem_euclid(EROSION_REGION as i32) as usize * CHUNK_SIZE;
+        let z = chunk_z.rem_euclid(EROSION_REGION as i32) as usize * CHUNK_SIZE;
+
+        let mut tile = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE);
+
+        for row in z..z + CHUNK_SIZE {
+            tile.extend_from_slice(&heights[row * width + x..][..CHUNK_SIZE]);
+        }
+
+        tile
+    }
+
+    /// Eroded heights of a square area of `width * width` columns, indexed by
+    /// `z * width + x`. The border eroded along with the area is wide enough for the
+    /// result not to depend on the area, so neighbouring tiles match up exactly
+    fn eroded_area(&self, min_x: i32, min_z: i32, width: usize) -> Vec<f32> {
+        let border = erosion_border(&self.parameters.erosion);
+        let size = width + border * 2;
+
+        let mut grid = Vec::with_capacity(size * size);
+
+        for z in 0..size as i32 {
+            for x in 0..size as i32 {
+                grid.push(heightmap(
+                    self.seed,
+                    &self.parameters,
+                    min_x + x - border as i32,
+                    min_z + z - border as i32,
+                ) as f32);
+            }
+        }
+
+        erode(&mut grid, size, &self.parameters.erosion);
+
+        let mut heights = Vec::with_capacity(width * width);
+
+        for z in 0..width {
+            heights.extend_from_slice(&grid[(z + border) * size + border..][..width]);
+        }
+
+        heights
+    }
+}
+
+impl NoiseGenerator {
+    /// Generates the chunk at `chunk_pos`, chunks lying wholly below the ground are
+    /// skipped unless `solid` is set
+    fn generate_chunk(&self, chunk_pos: Vector3<i32>, solid: bool) -> Option<Chunk> {
+        let min_x = chunk_pos.x * CHUNK_SIZE as i32;
+        let min_y = chunk_pos.y * CHUNK_SIZE as i32;
+        let min_z = chunk_pos.z * CHUNK_SIZE as i32;
+
+        let (heights, water, (min_height, _)) = self.get_cached_heights(chunk_pos.x, chunk_pos.z);
+
+        let sites = self.sites(
+            (min_x - MAX_FEATURE_RADIUS, min_z - MAX_FEATURE_RADIUS),
+            (
+                min_x + CHUNK_SIZE as i32 - 1 + MAX_FEATURE_RADIUS,
+                min_z + CHUNK_SIZE as i32 - 1 + MAX_FEATURE_RADIUS,
+            ),
+        );
+
+        // Features reaching into the chunk may be rooted in the neighbouring columns, which
+        // are never further away than one tile
+        let features = (-1..=1)
+            .flat_map(|dz| (-1..=1).map(move |dx| (dx, dz)))
+            .map(|(dx, dz)| {
+                let (_, _, (_, max_height)) =
+                    self.get_cached_heights(chunk_pos.x + dx, chunk_pos.z + dz);
+
+                max_height
+            })
+            .max()
+            .unwrap()
+            + MAX_FEATURE_HEIGHT;
+
+        let structures = sites
+            .iter()
+            .map(|site| site.origin.y + self.structures[site.template].size().y)
+            .max()
+            .unwrap_or(i32::MIN);
+
+        if features.max(structures) < min_y || (!solid && min_height >= min_y + CHUNK_SIZE as i32) {
+            return None;
+        }
+
+        let mut chunk = Chunk::empty();
+        let mut has_voxels = false;
+        let perlin = Perlin::new(self.seed);
+        let strata = Strata::new(self.seed, &self.parameters.strata, self.surface);
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let height = heights[z * CHUNK_SIZE + x];
+                let top = height.max(water[z * CHUNK_SIZE + x]);
+
+                if top < min_y {
+                    continue;
+                }
+
+                has_voxels = true;
+
+                let local_top = ((top - min_y) as usize).min(CHUNK_SIZE - 1);
+
+                for y in 0..=local_top {
+                    let position = [min_x + x as i32, min_y + y as i32, min_z + z as i32];
+                    let depth = height - position[1];
+
+                    if depth < 0 {
+                        chunk.set_with_material(
+                            x,
+                            y,
+                            z,
+                            true,
+                            Material::Water.color(),
+                            Material::Water,
+                        );
+                        continue;
+                    }
+
+                    let material = strata.material(position, depth, height);
+
+                    // The surface is colored by the height gradient, the layers below by material
+                    let color = if depth == 0 {
+                        let noise_y = calculate_noise(
+                            x as i32,
+                            y as i32,
+                            z as i32,
+                            min_x,
+                            min_y,
+                            min_z,
+                            self.parameters.noise_intensity,
+                            &perlin,
+                        );
+
+                        self.gradient.at(gradient_position(noise_y)).to_rgba8()
+                    } else {
+                        material.color()
+                    };
+
+                    chunk.set_with_material(x, y, z, true, color, material);
+                }
+            }
+        }
+
+        has_voxels |= self.stamp(&mut chunk, chunk_pos, &sites);
+
+        if self.parameters.vegetation > 0.0 {
+            has_voxels |= self.decorate(&mut chunk, chunk_pos, &sites);
+        }
+
+        if has_voxels {
+            Some(chunk)
+        } else {
+            None
+        }
+    }
+}
+
+impl TerrainGenerator for NoiseGenerator {
+    fn generate(&self, chunk_pos: Vector3<i32>) -> Option<Chunk> {
+        self.generate_chunk(chunk_pos, false)
+    }
+
+    fn generate_solid(&self, chunk_pos: Vector3<i32>) -> Option<Chunk> {
+        self.generate_chunk(chunk_pos, true)
+    }
+
+    fn heights(&self, chunk_pos: Vector3<i32>) -> Option<DMatrix<f32>> {
+        let (column, _, (min_height, _)) = self.get_cached_heights(chunk_pos.x, chunk_pos.z);
+
+        // The heightfield covers the whole column, only the lowest surface chunk carries it
+        if min_height.div_euclid(CHUNK_SIZE as i32) != chunk_pos.y {
+            return None;
+        }
+
+        let mut heights = DMatrix::<f32>::zeros(CHUNK_SIZE, CHUNK_SIZE);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                heights[(z, x)] = column[z * CHUNK_SIZE + x] as f32;
+            }
+        }
+
+        Some(heights)
+    }
+
+    fn is_air(&self, chunk_pos: Vector3<i32>) -> bool {
+        let (_, _, (min_height, _)) = self.get_cached_heights(chunk_pos.x, chunk_pos.z);
+
+        // Chunks below the lowest ground are skipped as well, they are solid
+        min_height < (chunk_pos.y + 1) * CHUNK_SIZE as i32
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.height_cache.lock().unwrap().len() * TILE_MEMORY
+            + self.regions.lock().unwrap().len() * REGION_MEMORY
+            + self
+                .rivers
+                .lock()
+                .unwrap()
+                .values()
+                .map(river_memory)
+                .sum::<usize>()
+    }
+
+    fn trim(&self, budget: usize) {
+        let mut height_cache = self.height_cache.lock().unwrap();
+
+        let capacity = budget / TILE_MEMORY;
+
+        // Regions and then rivers get what the tiles leave of the budget
+        let left = budget.saturating_sub(height_cache.len().min(capacity) * TILE_MEMORY);
+        let regions = trim_lru(&mut self.regions.lock().unwrap(), left, |_| REGION_MEMORY);
+        trim_lru(
+            &mut self.rivers.lock().unwrap(),
+            left.saturating_sub(regions),
+            river_memory,
+        );
+
+        if height_cache.len() <= capacity {
+            return;
+        }
+
+        let mut tiles: Vec<(u64, (i32, i32))> = height_cache
+            .iter()
+            .map(|(key, tile)| (tile.last_used, *key))
+            .collect();
+
+        tiles.sort_unstable();
+
+        let evict = height_cache.len() - capacity;
+
+        for (_, key) in tiles.into_iter().take(evict) {
+            height_cache.remove(&key);
+        }
+    }
+}
+
+/// Evicts the least recently used entries of a cache by cell until at most `budget` bytes
+/// are used, returns the bytes still used
+fn trim_lru<V>(
+    cache: &mut HashMap<(i32, i32), (V, u64)>,
+    budget: usize,
+    memory: impl Fn(&(V, u64)) -> usize,
+) -> usize {
+    let mut used: usize = cache.values().map(&memory).sum();
+
+    if used <= budget {
+        return used;
+    }
+
+    let mut cells: Vec<(u64, (i32, i32))> = cache
+        .iter()
+        .map(|(cell, (_, last_used))| (*last_used, *cell))
+        .collect();
+
+    cells.sort_unstable();
+
+    for (_, cell) in cells {
+        if used <= budget {
+            break;
+        }
+
+        used -= memory(&cache.remove(&cell).unwrap());
+    }
+
+    used
+}
+
+/// Memory used by one cached river cell, in bytes
+fn river_memory((river, _): &(Option<Arc<River>>, u64)) -> usize {
+    size_of::<((i32, i32), (Option<Arc<River>>, u64))>()
+        + river
+            .as_ref()
+            .map_or(0, |river| size_of::<River>() + river.memory_usage())
+}
+
+fn heightmap(seed: u32, parameters: &NoiseParameters, x: i32, z: i32) -> f64 {
+    let perlin = Perlin::new(seed);
+
+    let NoiseParameters {
+        scale,
+        height_multiplier,
+        octaves,
+        persistence,
+        detail_scale,
+        ..
+    } = *parameters;
+
+    let mut amplitude = 1.0;
+    let mut frequency = 3.0;
+    let mut height = 0.0;
+
+    let flatness = perlin.get([x as f64 * 0.001, z as f64 * 0.001]);
+
+    for _ in 0..octaves {
+        height +=
+            perlin.get([x as f64 * scale * frequency, z as f64 * scale * frequency]) * amplitude;
+
+        let weirdness = perlin.get([x as f64 * scale * frequency, z as f64 * scale * frequency]);
+
+        let pv = 1.0 - (3.0 * weirdness.abs() - 2.0).abs();
+
+        height += pv * amplitude * (flatness * 1.2);
+
+        amplitude *= persistence;
+        frequency *= 2.0;
+    }
+
+    let continental = perlin.get([x as f64 * 0.0001, z as f64 * 0.0001]);
+    let detail = perlin.get([x as f64 * 0.09, z as f64 * 0.09]);
+
+    ((height + 1.0) * (height_multiplier * continental) + (detail * detail_scale)) * flatness
+}
+
+/// Converts a position relative to the chunk origin, returns `None` outside the chunk
+fn chunk_local(position: Vector3<i32>) -> Option<(usize, usize, usize)> {
+    let range = 0..CHUNK_SIZE as i32;
+
+    if range.contains(&position.x) && range.contains(&position.y) && range.contains(&position.z) {
+        Some((
+            position.x as usize,
+            position.y as usize,
+            position.z as usize,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Maps a world height onto the gradient
+fn gradient_position(height: f64) -> f32 {
+    ((height - GRADIENT_MIN_HEIGHT) / (GRADIENT_MAX_HEIGHT - GRADIENT_MIN_HEIGHT)) as f32
+}
+
+#[allow(clippy::too_many_arguments)]
+fn calculate_noise(
+    x: i32,
+    y: i32,
+    z: i32,
+    min_x: i32,
+    min_y: i32,
+    min_z: i32,
+    noise_intensity: f64,
+    perlin: &Perlin,
+) -> f64 {
+    let noise_y = min_y as f64 + y as f64;
+    let height_difference = noise_y / 30.0;
+
+    if noise_y >= 32.0 + noise_intensity * height_difference {
+        noise_y
+            + perlin.get([
+                (min_x + x) as f64 * 0.1,
+                (min_y + y) as f64 * 0.1,
+                (min_z + z) as f64 * 0.1,
+            ]) * noise_intensity
+                * height_difference
+    } else {
+        noise_y
+    }
+}
+
+#[test]
+fn test_trim() {
+    struct Gray;
+
+    impl Gradient for Gray {
+        fn at(&self, t: f32) -> colorgrad::Color {
+            colorgrad::Color::new(t, t, t, 1.0)
+        }
+    }
+
+    let parameters = NoiseParameters {
+        rivers: 0.0,
+        ..Default::default()
+    };
+
+    let generator = NoiseGenerator::new(0, parameters, Box::new(Gray));
+
+    for x in 0..4 {
+        generator.heights(Vector3::new(x, 0, 0));
+    }
+
+    assert_eq!(generator.memory_usage(), 4 * TILE_MEMORY + REGION_MEMORY);
+
+    // Touch the oldest tile, so the second one is evicted first
+    generator.heights(Vector3::new(0, 0, 0));
+    generator.trim(3 * TILE_MEMORY);
+
+    // The tiles take the whole budget, the region they were cropped from goes
+    assert!(generator.regions.lock().unwrap().is_empty());
+
+    let height_cache = generator.height_cache.lock().unwrap();
+
+    assert_eq!(height_cache.len(), 3);
+    assert!(height_cache.contains_key(&(0, 0)));
+    assert!(!height_cache.contains_key(&(1, 0)));
+}
+
+#[test]
+fn test_erosion_seams() {
+    struct Gray;
+
+    impl Gradient for Gray {
+        fn at(&self, t: f32) -> colorgrad::Color {
+            colorgrad::Color::new(t, t, t, 1.0)
+        }
+    }
+
+    // Steep hills, so the erosion moves a lot of material
+    let parameters = NoiseParameters {
+        scale: 0.02,
+        ..Default::default()
+    };
+
+    let generator = NoiseGenerator::new(0, parameters, Box::new(Gray));
+
+    let left = generator.eroded_area(0, 0, CHUNK_SIZE);
+    let right = generator.eroded_area(CHUNK_SIZE as i32, 0, CHUNK_SIZE);
+
+    // Both tiles eroded at once, without an edge between them
+    let area = generator.eroded_area(0, 0, CHUNK_SIZE * 2);
+
+    for z in 0..CHUNK_SIZE {
+        let row = z * CHUNK_SIZE * 2;
+
+        assert_eq!(
+            left[z * CHUNK_SIZE + CHUNK_SIZE - 1],
+            area[row + CHUNK_SIZE - 1]
+        );
+        assert_eq!(right[z * CHUNK_SIZE], area[row + CHUNK_SIZE]);
+    }
+}
+
+#[test]
+fn test_erosion_regions() {
+    struct Gray;
+
+    impl Gradient for Gray {
+        fn at(&self, t: f32) -> colorgrad::Color {
+            colorgrad::Color::new(t, t, t, 1.0)
+        }
+    }
+
+    let generator = NoiseGenerator::new(0, NoiseParameters::default(), Box::new(Gray));
+
+    // A tile cropped from its region matches the tile eroded on its own
+    let (chunk_x, chunk_z) = (-3, 9);
+
+    let tile: Vec<i32> = generator
+        .eroded_area(
+            chunk_x * CHUNK_SIZE as i32,
+            chunk_z * CHUNK_SIZE as i32,
+            CHUNK_SIZE,
+        )
+        .into_iter()
+        .map(|height| height.floor() as i32)
+        .collect();
+
+    assert_eq!(generator.eroded_heights(chunk_x, chunk_z), tile);
+    assert_eq!(generator.memory_usage(), REGION_MEMORY);
+}
+
+#[test]
+fn test_solid() {
+    struct Gray;
+
+    impl Gradient for Gray {
+        fn at(&self, t: f32) -> colorgrad::Color {
+            colorgrad::Color::new(t, t, t, 1.0)
+        }
+    }
+
+    let generator = NoiseGenerator::new(0, NoiseParameters::default(), Box::new(Gray));
+
+    // Deep underground chunks are skipped, but generated as solid rock on request
+    let chunk_pos = Vector3::new(0, -20, 0);
+
+    assert!(generator.generate(chunk_pos).is_none());
+    assert!(!generator.is_air(chunk_pos));
+
+    let chunk = generator.generate_solid(chunk_pos).unwrap();
+
+    for (x, y, z) in (0..CHUNK_SIZE)
+        .flat_map(|x| (0..CHUNK_SIZE).flat_map(move |y| (0..CHUNK_SIZE).map(move |z| (x, y, z))))
+    {
+        assert!(chunk.get_occupied(x, y, z));
+    }
+
+    assert_eq!(chunk.get_material(0, 0, 0), Some(Material::DeepRock));
+}
@@ -0,0 +1,296 @@
+use super::hash;
+
+/// Side of the grid cells holding at most one river source each, in voxels
+const RIVER_CELL_SIZE: i32 = 256;
+/// Salt of the river cells, see [`hash`]
+const SALT: u64 = 0x5EED_0003;
+/// Distance between two points of a river, in voxels
+const STEP: f32 = 4.0;
+/// Points after which a river stops
+const MAX_STEPS: usize = 256;
+/// Rivers shorter than this are dropped
+const MIN_STEPS: usize = 16;
+/// Candidate sources per cell along each axis
+const SOURCE_CANDIDATES: i32 = 4;
+/// Sources lower than this don't start a river
+const SOURCE_MIN_HEIGHT: f32 = 60.0;
+/// Height a river may climb in one step, so small bumps don't stop it
+const MAX_RISE: f32 = 1.5;
+/// Depth of the river bed below the water in the middle of the river
+const DEPTH: f32 = 3.0;
+/// Width of the valley slopes next to the river
+const BANK: f32 = 6.0;
+/// Radius of the lake a river ends in when it gets stuck above the sea
+const LAKE_RADIUS: f32 = 16.0;
+/// Height of the lake surface above the point the river got stuck at
+const LAKE_DEPTH: i32 = 2;
+
+/// Farthest distance between a source and any column its river touches
+pub const MAX_RIVER_REACH: i32 = (STEP * MAX_STEPS as f32 + LAKE_RADIUS + BANK) as i32 + 16;
+
+struct RiverPoint {
+    x: f32,
+    z: f32,
+    /// Height of the water surface, never rises downstream
+    level: i32,
+    width: f32,
+}
+
+/// River flowing downhill from a source, optionally ending in a lake
+pub struct River {
+    points: Vec<RiverPoint>,
+    /// Lake center with its water level
+    lake: Option<(f32, f32, i32)>,
+    /// Bounds of all touched columns on the XZ plane
+    min: (i32, i32),
+    max: (i32, i32),
+}
+
+impl River {
+    /// Traces the river of a cell, `height` is the terrain height at a world column.
+    /// Returns `None` if the cell has no river
+    pub fn trace(
+        seed: u32,
+        cell: (i32, i32),
+        chance: f64,
+        sea_level: i32,
+        height: impl Fn(f32, f32) -> f32,
+    ) -> Option<Self> {
+        let hash = hash(seed, cell.0, cell.1, SALT);
+
+        if (hash & 0xFF) as f64 / 256.0 >= chance {
+            return None;
+        }
+
+        // The highest of a jittered grid of candidates, so rivers start on the peaks
+        let spacing = RIVER_CELL_SIZE / SOURCE_CANDIDATES;
+        let jitter_x = ((hash >> 8) % spacing as u64) as i32;
+        let jitter_z = ((hash >> 24) % spacing as u64) as i32;
+
+        let (mut ground, mut x, mut z) = (0..SOURCE_CANDIDATES * SOURCE_CANDIDATES)
+            .map(|i| {
+                let x = (cell.0 * RIVER_CELL_SIZE + (i % SOURCE_CANDIDATES) * spacing + jitter_x)
+                    as f32;
+                let z = (cell.1 * RIVER_CELL_SIZE + (i / SOURCE_CANDIDATES) * spacing + jitter_z)
+                    as f32;
+
+                (height(x, z), x, z)
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap();
+
+        if ground < SOURCE_MIN_HEIGHT {
+            return None;
+        }
+
+        let mut points = Vec::with_capacity(MAX_STEPS);
+        let mut level = ground.floor() as i32 - 1;
+        let mut lake = None;
+
+        // Previous direction, keeps the river from turning back on flat ground
+        let mut direction = (0.0f32, 0.0f32);
+
+        for step in 0..MAX_STEPS {
+            level = level.min(ground.floor() as i32 - 1);
+
+            points.push(RiverPoint {
+                x,
+                z,
+                level,
+                width: 2.0 + step as f32 / 40.0,
+            });
+
+            if level < sea_level {
+                break;
+            }
+
+            let mut best: Option<(f32, f32, f32)> = None;
+
+            for i in 0..8 {
+                let angle = i as f32 * std::f32::consts::FRAC_PI_4;
+                let (dx, dz) = (angle.cos(), angle.sin());
+
+                if dx * direction.0 + dz * direction.1 < -0.1 {
+                    continue;
+                }
+
+                let next = height(x + dx * STEP, z + dz * STEP);
+
+                if best.is_none_or(|(lowest, _, _)| next < lowest) {
+                    best = Some((next, dx, dz));
+                }
+            }
+
+            match best {
+                Some((next, dx, dz)) if next < ground + MAX_RISE => {
+                    x += dx * STEP;
+                    z += dz * STEP;
+                    ground = next;
+                    direction = (dx, dz);
+                }
+                _ => {
+                    // Stuck in a basin above the sea
+                    lake = Some((x, z, level + LAKE_DEPTH));
+                    break;
+                }
+            }
+        }
+
+        if points.len() < MIN_STEPS {
+            return None;
+        }
+
+        points.shrink_to_fit();
+
+        let reach = (LAKE_RADIUS.max(points.last().unwrap().width + BANK)).ceil() as i32 + 1;
+
+        let min = points.iter().fold((i32::MAX, i32::MAX), |min, point| {
+            (
+                min.0.min(point.x as i32 - reach),
+                min.1.min(point.z as i32 - reach),
+            )
+        });
+        let max = points.iter().fold((i32::MIN, i32::MIN), |max, point| {
+            (
+                max.0.max(point.x as i32 + reach),
+                max.1.max(point.z as i32 + reach),
+            )
+        });
+
+        Some(Self {
+            points,
+            lake,
+            min,
+            max,
+        })
+    }
+
+    /// Cells whose river may touch the area between `min` and `max`
+    pub fn cells(min: (i32, i32), max: (i32, i32)) -> impl Iterator<Item = (i32, i32)> {
+        let range = |min: i32, max: i32| {
+            (min - MAX_RIVER_REACH).div_euclid(RIVER_CELL_SIZE)
+                ..=(max + MAX_RIVER_REACH).div_euclid(RIVER_CELL_SIZE)
+        };
+
+        range(min.1, max.1).flat_map(move |z| range(min.0, max.0).map(move |x| (x, z)))
+    }
+
+    pub fn overlaps(&self, min: (i32, i32), max: (i32, i32)) -> bool {
+        self.min.0 <= max.0 && self.max.0 >= min.0 && self.min.1 <= max.1 && self.max.1 >= min.1
+    }
+
+    /// Approximate heap memory used by the river, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.points.capacity() * size_of::<RiverPoint>()
+    }
+
+    /// Carves the river bed and its valley into the ground and raises the water level.
+    /// Both grids are `size * size` columns starting at `min`, indexed by `z * size + x`
+    pub fn carve(&self, min: (i32, i32), size: usize, heights: &mut [i32], water: &mut [i32]) {
+        if !self.overlaps(min, (min.0 + size as i32 - 1, min.1 + size as i32 - 1)) {
+            return;
+        }
+
+        for z in 0..size {
+            for x in 0..size {
+                let i = z * size + x;
+
+                let column = ((min.0 + x as i32) as f32, (min.1 + z as i32) as f32);
+
+                if let Some((lake_x, lake_z, level)) = self.lake {
+                    let distance = (column.0 - lake_x).hypot(column.1 - lake_z);
+
+                    if distance <= LAKE_RADIUS && heights[i] < level {
+                        water[i] = water[i].max(level);
+                    }
+                }
+
+                let Some((distance, point)) = self.nearest(column) else {
+                    continue;
+                };
+
+                if distance <= point.width {
+                    let t = distance / point.width;
+                    let bed = point.level - 1 - (DEPTH * (1.0 - t * t)).floor() as i32;
+
+                    heights[i] = heights[i].min(bed);
+                    water[i] = water[i].max(point.level);
+                } else if distance <= point.width + BANK {
+                    // Valley slopes blending from the water level into the terrain
+                    let t = (distance - point.width) / BANK;
+                    let slope =
+                        point.level + (t * (heights[i] - point.level).max(0) as f32).round() as i32;
+
+                    heights[i] = heights[i].min(slope);
+                }
+            }
+        }
+    }
+
+    /// Distance to the closest point on the river with the properties interpolated there
+    fn nearest(&self, column: (f32, f32)) -> Option<(f32, RiverPoint)> {
+        let mut nearest: Option<(f32, RiverPoint)> = None;
+
+        for segment in self.points.windows(2) {
+            let (a, b) = (&segment[0], &segment[1]);
+
+            let (dx, dz) = (b.x - a.x, b.z - a.z);
+            let length = dx * dx + dz * dz;
+
+            let t = (((column.0 - a.x) * dx + (column.1 - a.z) * dz) / length).clamp(0.0, 1.0);
+
+            let x = a.x + dx * t;
+            let z = a.z + dz * t;
+
+            let distance = (column.0 - x).hypot(column.1 - z);
+
+            if distance > b.width + BANK {
+                continue;
+            }
+
+            if nearest.as_ref().is_none_or(|(d, _)| distance < *d) {
+                nearest = Some((
+                    distance,
+                    RiverPoint {
+                        x,
+                        z,
+                        level: if t < 0.5 { a.level } else { b.level },
+                        width: a.width + (b.width - a.width) * t,
+                    },
+                ));
+            }
+        }
+
+        nearest
+    }
+}
+
+#[test]
+fn test_river_flows_downhill() {
+    // Plane sloping down towards positive X
+    let height = |x: f32, _z: f32| 200.0 - x * 0.1;
+
+    let river = (0..64)
+        .find_map(|x| River::trace(1, (x, 0), 1.0, 32, height))
+        .unwrap();
+
+    assert!(river
+        .points
+        .windows(2)
+        .all(|pair| pair[1].level <= pair[0].level && pair[1].x > pair[0].x));
+
+    let start = &river.points[0];
+
+    let mut heights = vec![start.level + 10; 4];
+    let mut water = vec![i32::MIN; 4];
+
+    river.carve(
+        (start.x as i32, start.z as i32),
+        2,
+        &mut heights,
+        &mut water,
+    );
+
+    assert!(heights[0] < start.level);
+    assert_eq!(water[0], start.level);
+}
@@ -0,0 +1,171 @@
+use crate::engine::voxel::chunk::material::Material;
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+/// Heights at which the surface changes, matching the gradient bands of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceLines {
+    /// Ground up to this height is beach or sea floor
+    pub sand: i32,
+    /// Ground above this height is bare rock
+    pub tree: i32,
+    /// Ground above this height is covered in snow
+    pub snow: i32,
+}
+
+impl Default for SurfaceLines {
+    /// Lines of the natural gradient
+    fn default() -> Self {
+        Self {
+            sand: 35,
+            tree: 90,
+            snow: 140,
+        }
+    }
+}
+
+impl SurfaceLines {
+    /// Material of the top voxel of a column at `height`
+    pub fn material(&self, height: i32) -> Material {
+        if height <= self.sand {
+            Material::Sand
+        } else if height <= self.tree {
+            Material::Grass
+        } else if height <= self.snow {
+            Material::Stone
+        } else {
+            Material::Snow
+        }
+    }
+}
+
+/// Vein of ore found in 3D noise blobs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OreParameters {
+    pub material: Material,
+    /// Depth range below the surface the ore appears in
+    pub min_depth: i32,
+    pub max_depth: i32,
+    /// Frequency of the blobs, larger values give smaller blobs
+    pub scale: f64,
+    /// Noise value above which a voxel is ore, larger values give rarer ore
+    pub threshold: f64,
+}
+
+/// Layers below the surface
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StrataParameters {
+    /// Depth of the dirt or sand layer below the surface
+    pub dirt_depth: i32,
+    /// Depth below which stone turns into deep rock
+    pub stone_depth: i32,
+    pub ores: Vec<OreParameters>,
+}
+
+impl Default for StrataParameters {
+    fn default() -> Self {
+        Self {
+            dirt_depth: 3,
+            stone_depth: 48,
+            ores: vec![
+                OreParameters {
+                    material: Material::Coal,
+                    min_depth: 4,
+                    max_depth: 96,
+                    scale: 0.12,
+                    threshold: 0.55,
+                },
+                OreParameters {
+                    material: Material::Iron,
+                    min_depth: 16,
+                    max_depth: 160,
+                    scale: 0.15,
+                    threshold: 0.62,
+                },
+                OreParameters {
+                    material: Material::Gold,
+                    min_depth: 64,
+                    max_depth: i32::MAX,
+                    scale: 0.2,
+                    threshold: 0.7,
+                },
+            ],
+        }
+    }
+}
+
+/// Assigns materials to the voxels of a column
+pub struct Strata<'a> {
+    parameters: &'a StrataParameters,
+    lines: SurfaceLines,
+    /// One noise per ore
+    noises: Vec<Perlin>,
+}
+
+impl<'a> Strata<'a> {
+    pub fn new(seed: u32, parameters: &'a StrataParameters, lines: SurfaceLines) -> Self {
+        Self {
+            parameters,
+            lines,
+            noises: (0..parameters.ores.len())
+                .map(|i| Perlin::new(seed.wrapping_add(i as u32 + 1)))
+                .collect(),
+        }
+    }
+
+    /// Material at a world position `depth` voxels below the surface at `height`
+    pub fn material(&self, position: [i32; 3], depth: i32, height: i32) -> Material {
+        let surface = self.lines.material(height);
+
+        if depth == 0 {
+            return surface;
+        }
+
+        if depth <= self.parameters.dirt_depth {
+            return match surface {
+                Material::Sand => Material::Sand,
+                Material::Grass => Material::Dirt,
+                _ => Material::Stone,
+            };
+        }
+
+        for (ore, noise) in self.parameters.ores.iter().zip(&self.noises) {
+            if depth < ore.min_depth || depth > ore.max_depth {
+                continue;
+            }
+
+            let value = noise.get([
+                position[0] as f64 * ore.scale,
+                position[1] as f64 * ore.scale,
+                position[2] as f64 * ore.scale,
+            ]);
+
+            if value > ore.threshold {
+                return ore.material;
+            }
+        }
+
+        if depth <= self.parameters.stone_depth {
+            Material::Stone
+        } else {
+            Material::DeepRock
+        }
+    }
+}
+
+#[test]
+fn test_layers() {
+    let parameters = StrataParameters {
+        ores: Vec::new(),
+        ..Default::default()
+    };
+
+    let strata = Strata::new(0, &parameters, SurfaceLines::default());
+
+    assert_eq!(strata.material([0, 60, 0], 0, 60), Material::Grass);
+    assert_eq!(strata.material([0, 58, 0], 2, 60), Material::Dirt);
+    assert_eq!(strata.material([0, 40, 0], 20, 60), Material::Stone);
+    assert_eq!(strata.material([0, -40, 0], 100, 60), Material::DeepRock);
+    assert_eq!(strata.material([0, 29, 0], 1, 30), Material::Sand);
+}
@@ -0,0 +1,244 @@
+use super::hash;
+use crate::engine::voxel::chunk::material::Material;
+use crate::io::load_voxels;
+use cgmath::Vector3;
+use std::{fs, path::Path};
+
+/// Directory the structure templates are loaded from
+pub const STRUCTURE_DIRECTORY: &str = "structures";
+
+/// Side of the grid regions holding at most one structure each, in voxels
+const REGION_SIZE: i32 = 256;
+/// Salt of the structure regions, see [`hash`]
+const SALT: u64 = 0x5EED_0002;
+/// Chance of a region holding a structure
+const STRUCTURE_CHANCE: f64 = 0.5;
+/// Ground around a structure that is blended into its level, in voxels
+pub const FLATTEN_MARGIN: i32 = 8;
+/// Sites lower than this are under water
+const MIN_SITE_LEVEL: i32 = 33;
+/// Materials template voxels are matched to by color, loose materials and water would
+/// fall or flow out of the structure
+const BUILDING_MATERIALS: [Material; 10] = [
+    Material::Grass,
+    Material::Dirt,
+    Material::Stone,
+    Material::DeepRock,
+    Material::Snow,
+    Material::Wood,
+    Material::Leaves,
+    Material::Coal,
+    Material::Iron,
+    Material::Gold,
+];
+
+/// Hand-built model placed into the terrain, the ground lies below `y = 0`
+pub struct StructureTemplate {
+    size: Vector3<i32>,
+    voxels: Vec<([i32; 3], [u8; 4], Material)>,
+}
+
+impl StructureTemplate {
+    /// Moves the voxels so the smallest corner lies at the origin. Every voxel gets the
+    /// building material closest to its color, the files only store colors
+    pub fn new(voxels: Vec<([i32; 3], [u8; 4])>) -> Self {
+        let min = voxels.iter().fold([i32::MAX; 3], |min, (voxel, _)| {
+            [0, 1, 2].map(|i| min[i].min(voxel[i]))
+        });
+        let max = voxels.iter().fold([i32::MIN; 3], |max, (voxel, _)| {
+            [0, 1, 2].map(|i| max[i].max(voxel[i]))
+        });
+
+        let voxels = voxels
+            .into_iter()
+            .map(|(voxel, color)| {
+                (
+                    [0, 1, 2].map(|i| voxel[i] - min[i]),
+                    color,
+                    closest_material(color),
+                )
+            })
+            .collect();
+
+        Self {
+            size: Vector3::new(
+                max[0] - min[0] + 1,
+                max[1] - min[1] + 1,
+                max[2] - min[2] + 1,
+            ),
+            voxels,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        Self::new(load_voxels(path))
+    }
+
+    /// Loads every `.voxels` file of `directory` sorted by name, so indices are stable
+    pub fn load_all<P: AsRef<Path>>(directory: P) -> Vec<Self> {
+        let mut paths: Vec<_> = fs::read_dir(directory)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "voxels")
+            })
+            .collect();
+
+        paths.sort();
+
+        paths.into_iter().map(Self::load).collect()
+    }
+
+    pub fn size(&self) -> Vector3<i32> {
+        self.size
+    }
+
+    pub fn voxels(&self) -> &[([i32; 3], [u8; 4], Material)] {
+        &self.voxels
+    }
+}
+
+/// Structure placed in the world
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StructureSite {
+    /// Smallest corner of the structure, the flattened ground lies right below it
+    pub origin: Vector3<i32>,
+    pub template: usize,
+}
+
+impl StructureSite {
+    /// Returns how far the column lies outside of the footprint, 0 if inside
+    pub fn distance(&self, templates: &[StructureTemplate], x: i32, z: i32) -> i32 {
+        let size = templates[self.template].size;
+
+        let dx = (self.origin.x - x)
+            .max(x - (self.origin.x + size.x - 1))
+            .max(0);
+        let dz = (self.origin.z - z)
+            .max(z - (self.origin.z + size.z - 1))
+            .max(0);
+
+        dx.max(dz)
+    }
+
+    /// Ground height at a column, blended between the site level and `height` in the margin
+    pub fn flatten(&self, templates: &[StructureTemplate], x: i32, z: i32, height: i32) -> i32 {
+        let level = self.origin.y - 1;
+
+        match self.distance(templates, x, z) {
+            0 => level,
+            distance if distance <= FLATTEN_MARGIN => {
+                let t = distance as f32 / (FLATTEN_MARGIN + 1) as f32;
+
+                (level as f32 + (height - level) as f32 * t).round() as i32
+            }
+            _ => height,
+        }
+    }
+}
+
+/// Structures whose footprint including the flattened margin overlaps the area between
+/// `min` and `max` on the XZ plane. `level` returns the ground height the structure is
+/// placed on, given the center of its footprint
+pub fn sites(
+    seed: u32,
+    templates: &[StructureTemplate],
+    min: (i32, i32),
+    max: (i32, i32),
+    level: impl Fn(i32, i32) -> i32,
+) -> Vec<StructureSite> {
+    let mut sites = Vec::new();
+
+    if templates.is_empty() {
+        return sites;
+    }
+
+    let regions = |min: i32, max: i32| {
+        (min - REGION_SIZE).div_euclid(REGION_SIZE)..=(max + REGION_SIZE).div_euclid(REGION_SIZE)
+    };
+
+    for region_z in regions(min.1, max.1) {
+        for region_x in regions(min.0, max.0) {
+            let hash = hash(seed, region_x, region_z, SALT);
+
+            if (hash & 0xFF) as f64 / 256.0 >= STRUCTURE_CHANCE {
+                continue;
+            }
+
+            let template = ((hash >> 8) % templates.len() as u64) as usize;
+            let size = templates[template].size;
+
+            // Keep the structure and its margin inside the region
+            let free_x = (REGION_SIZE - size.x - FLATTEN_MARGIN * 2).max(1);
+            let free_z = (REGION_SIZE - size.z - FLATTEN_MARGIN * 2).max(1);
+
+            let x = region_x * REGION_SIZE + FLATTEN_MARGIN + ((hash >> 16) % free_x as u64) as i32;
+            let z = region_z * REGION_SIZE + FLATTEN_MARGIN + ((hash >> 32) % free_z as u64) as i32;
+
+            if x + size.x + FLATTEN_MARGIN <= min.0
+                || x - FLATTEN_MARGIN > max.0
+                || z + size.z + FLATTEN_MARGIN <= min.1
+                || z - FLATTEN_MARGIN > max.1
+            {
+                continue;
+            }
+
+            let level = level(x + size.x / 2, z + size.z / 2);
+
+            if level < MIN_SITE_LEVEL {
+                continue;
+            }
+
+            sites.push(StructureSite {
+                origin: Vector3::new(x, level + 1, z),
+                template,
+            });
+        }
+    }
+
+    sites
+}
+
+/// Building material whose color is closest to `color`
+fn closest_material(color: [u8; 4]) -> Material {
+    BUILDING_MATERIALS
+        .into_iter()
+        .min_by_key(|material| {
+            material
+                .color()
+                .iter()
+                .zip(color)
+                .take(3)
+                .map(|(a, b)| (*a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_sites() {
+    let templates = [StructureTemplate::new(vec![
+        ([10, 5, 10], [255; 4]),
+        ([13, 9, 11], [255; 4]),
+    ])];
+
+    assert_eq!(templates[0].size(), Vector3::new(4, 5, 2));
+    assert_eq!(templates[0].voxels()[0].2, Material::Snow);
+    assert_eq!(closest_material([92, 62, 30, 255]), Material::Wood);
+
+    let all = sites(3, &templates, (0, 0), (4095, 4095), |_, _| 50);
+
+    assert!(!all.is_empty());
+
+    // Every site is found again from any area overlapping its footprint
+    for site in all {
+        let x = site.origin.x + 1;
+        let z = site.origin.z;
+
+        assert!(sites(3, &templates, (x, z), (x, z), |_, _| 50).contains(&site));
+        assert_eq!(site.flatten(&templates, x, z, 80), 50);
+    }
+}
@@ -0,0 +1,151 @@
+use super::chunk::material::Material;
+use super::world::{TickQueue, VoxelWorld, HORIZONTAL};
+use cgmath::Vector3;
+
+/// Voxels updated per tick at most, the others wait for the next tick
+const MAX_UPDATES: usize = 8192;
+
+/// Falling sand simulation: loose voxels like sand and gravel fall when unsupported and
+/// slide off piles steeper than their angle of repose. Only voxels queued by edits or
+/// by earlier moves are updated, so settled piles cost nothing
+#[derive(Default)]
+pub struct GranularSimulation {
+    queue: TickQueue,
+    tick: u64,
+}
+
+impl GranularSimulation {
+    /// Number of voxels updated in the next tick
+    pub fn active(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Call after a voxel was edited, wakes the loose voxels around it
+    pub fn wake(&mut self, position: Vector3<i32>) {
+        self.queue.push_around(position);
+    }
+
+    /// Moves every queued loose voxel by at most one voxel. Returns the moves as `(from, to)`,
+    /// water in the way swaps places with the voxel and its fill level should follow it
+    pub fn step(&mut self, world: &mut impl VoxelWorld) -> Vec<(Vector3<i32>, Vector3<i32>)> {
+        self.tick += 1;
+
+        self.queue.retain_loaded(world);
+
+        let mut moves = Vec::new();
+
+        // Lower voxels first, so falling columns move as a whole
+        for position in self.queue.take(MAX_UPDATES) {
+            let Some(Some(material)) = world.voxel(position) else {
+                continue;
+            };
+
+            let Some(repose) = material.repose() else {
+                continue;
+            };
+
+            let Some(target) = self.target(world, position, repose) else {
+                continue;
+            };
+
+            let color = world.color(position).unwrap_or(material.color());
+
+            let displaced = match world.voxel(target) {
+                Some(Some(Material::Water)) => Some((
+                    world.color(target).unwrap_or(Material::Water.color()),
+                    Material::Water,
+                )),
+                _ => None,
+            };
+
+            world.set_voxel(target, Some((color, material)));
+            world.set_voxel(position, displaced);
+
+            // The voxels above and beside may lose their support
+            self.queue.push_around(position);
+            self.queue.push(target);
+
+            moves.push((position, target));
+        }
+
+        moves
+    }
+
+    /// Position a loose voxel moves to, straight down or diagonally off a steep edge
+    fn target(
+        &self,
+        world: &impl VoxelWorld,
+        position: Vector3<i32>,
+        repose: i32,
+    ) -> Option<Vector3<i32>> {
+        let below = position - Vector3::unit_y();
+
+        if is_free(world, below) {
+            return Some(below);
+        }
+
+        // Start with another side every tick, so piles grow evenly
+        (0..HORIZONTAL.len())
+            .map(|i| position + HORIZONTAL[(i + self.tick as usize) % HORIZONTAL.len()])
+            .find(|side| (0..=repose).all(|depth| is_free(world, side - Vector3::unit_y() * depth)))
+            .map(|side| side - Vector3::unit_y())
+    }
+}
+
+/// Returns if a loose voxel can move into the position, water swaps places with it
+fn is_free(world: &impl VoxelWorld, position: Vector3<i32>) -> bool {
+    matches!(world.voxel(position), Some(None | Some(Material::Water)))
+}
+
+#[test]
+fn test_pile() {
+    let mut world: ahash::HashMap<Vector3<i32>, ([u8; 4], Material)> = Default::default();
+
+    for z in -8..=8 {
+        for x in -8..=8 {
+            world.set_voxel(Vector3::new(x, -1, z), Some(([0; 4], Material::Stone)));
+        }
+    }
+
+    let mut sand = GranularSimulation::default();
+
+    // Pour a column of sand onto a single spot
+    for y in 0..16 {
+        let position = Vector3::new(0, y + 4, 0);
+
+        world.set_voxel(position, Some(([0; 4], Material::Sand)));
+        sand.wake(position);
+    }
+
+    for _ in 0..512 {
+        sand.step(&mut world);
+    }
+
+    assert!(sand.is_sleeping());
+
+    let sand_voxels = world
+        .values()
+        .filter(|(_, material)| *material == Material::Sand)
+        .count();
+
+    assert_eq!(sand_voxels, 16);
+
+    // No sand voxel stands on a drop of more than one voxel
+    for (position, (_, material)) in &world {
+        if *material != Material::Sand {
+            continue;
+        }
+
+        assert!(!is_free(&world, position - Vector3::unit_y()));
+
+        for side in HORIZONTAL {
+            let side = position + side;
+
+            assert!(!(is_free(&world, side) && is_free(&world, side - Vector3::unit_y())));
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use super::{
+    chunk::material::Material,
+    object::Object,
+    world::{VoxelWorld, NEIGHBOURS},
+};
+use crate::engine::physics::simulation::Simulation;
+use ahash::{HashMap, HashSet};
+use cgmath::{Matrix4, Vector3};
+use rapier3d::{
+    math::Isometry,
+    prelude::{ColliderBuilder, RigidBodyBuilder, RigidBodyHandle, SharedShape},
+};
+use std::{collections::VecDeque, sync::Arc};
+use wgpu::Device;
+
+/// Voxels farther than this from an edit, on any axis, count as anchored
+const RADIUS: i32 = 16;
+/// Clusters with more voxels count as anchored, too heavy to break off
+const MAX_CLUSTER: usize = 4096;
+
+/// Returns if a voxel holds structures together, water and loose voxels don't
+fn is_rigid(world: &impl VoxelWorld, position: Vector3<i32>) -> bool {
+    matches!(world.voxel(position), Some(Some(material)) if material != Material::Water && material.repose().is_none())
+}
+
+/// Clusters of rigid voxels next to a removed voxel that lost their support. A cluster is
+/// supported if it reaches an unloaded chunk or a voxel outside the radius around the edit
+pub fn unsupported(world: &impl VoxelWorld, position: Vector3<i32>) -> Vec<Vec<Vector3<i32>>> {
+    let mut clusters = Vec::new();
+    // Voxels of unsupported clusters, which are always complete
+    let mut detached = HashSet::default();
+    // Voxels reached by searches that stopped at support
+    let mut anchored = HashSet::default();
+
+    for offset in NEIGHBOURS {
+        let start = position + offset;
+
+        if detached.contains(&start) || anchored.contains(&start) || !is_rigid(world, start) {
+            continue;
+        }
+
+        let mut cluster = vec![start];
+        let mut queue = VecDeque::from([start]);
+        let mut visited = HashSet::from_iter([start]);
+        let mut supported = false;
+
+        while let Some(voxel) = queue.pop_front() {
+            let distance = voxel - position;
+
+            if distance.x.abs().max(distance.y.abs()).max(distance.z.abs()) > RADIUS
+                || cluster.len() > MAX_CLUSTER
+            {
+                supported = true;
+                break;
+            }
+
+            for offset in NEIGHBOURS {
+                let neighbour = voxel + offset;
+
+                if world.voxel(neighbour).is_none() || anchored.contains(&neighbour) {
+                    supported = true;
+                    break;
+                }
+
+                if is_rigid(world, neighbour) && visited.insert(neighbour) {
+                    cluster.push(neighbour);
+                    queue.push_back(neighbour);
+                }
+            }
+
+            if supported {
+                break;
+            }
+        }
+
+        match supported {
+            true => anchored.extend(cluster),
+            false => {
+                detached.extend(cluster.iter().copied());
+                clusters.push(cluster);
+            }
+        }
+    }
+
+    clusters
+}
+
+/// Removes a cluster from the world and turns it into a dynamic object with a collider
+/// made of one box per row of voxels
+pub fn detach(
+    world: &mut impl VoxelWorld,
+    simulation: &mut Simulation,
+    device: Arc<Device>,
+    cluster: &[Vector3<i32>],
+) -> (Object, RigidBodyHandle) {
+    let min = cluster.iter().fold(cluster[0], |min, voxel| {
+        Vector3::new(min.x.min(voxel.x), min.y.min(voxel.y), min.z.min(voxel.z))
+    });
+
+    let mut voxels = Vec::with_capacity(cluster.len());
+
+    for voxel in cluster {
+        let color = world.color(*voxel).unwrap_or(Material::Unknown.color());
+
+        world.set_voxel(*voxel, None);
+
+        let local = voxel - min;
+        voxels.push(([local.x, local.y, local.z], color));
+    }
+
+    // Rows along x, keyed by (y, z)
+    let mut rows: HashMap<(i32, i32), Vec<i32>> = HashMap::default();
+
+    for (voxel, _) in &voxels {
+        rows.entry((voxel[1], voxel[2])).or_default().push(voxel[0]);
+    }
+
+    let mut shapes = Vec::new();
+
+    for ((y, z), mut xs) in rows {
+        xs.sort_unstable();
+
+        let mut start = 0;
+
+        for i in 1..=xs.len() {
+            if i < xs.len() && xs[i] == xs[i - 1] + 1 {
+                continue;
+            }
+
+            let length = (xs[i - 1] - xs[start] + 1) as f32;
+
+            // Voxels span [z - 1, z] on the z axis
+            shapes.push((
+                Isometry::translation(
+                    xs[start] as f32 + length / 2.0,
+                    y as f32 + 0.5,
+                    z as f32 - 0.5,
+                ),
+                SharedShape::cuboid(length / 2.0, 0.5, 0.5),
+            ));
+
+            start = i;
+        }
+    }
+
+    let rigid_body = RigidBodyBuilder::dynamic()
+        .translation(nalgebra::Vector3::new(
+            min.x as f32,
+            min.y as f32,
+            min.z as f32,
+        ))
+        .build();
+
+    let handle = simulation.add_rigid_body(rigid_body);
+
+    simulation.add_collider(ColliderBuilder::compound(shapes), Some(handle));
+
+    let object = Object::from_voxels(
+        device,
+        Matrix4::from_translation(min.map(|x| x as f32)),
+        voxels,
+    );
+
+    (object, handle)
+}
+
+#[test]
+fn test_unsupported() {
+    let mut world: HashMap<Vector3<i32>, ([u8; 4], Material)> = HashMap::default();
+
+    let stone = Some(([0; 4], Material::Stone));
+
+    // Floor reaching past the radius, a pillar carrying a beam
+    for z in -20..=20 {
+        for x in -20..=20 {
+            world.set_voxel(Vector3::new(x, 0, z), stone);
+        }
+    }
+
+    for y in 1..=4 {
+        world.set_voxel(Vector3::new(0, y, 0), stone);
+    }
+
+    for x in 1..=3 {
+        world.set_voxel(Vector3::new(x, 4, 0), stone);
+    }
+
+    // Sand isn't part of the structure
+    world.set_voxel(Vector3::new(3, 5, 0), Some(([0; 4], Material::Sand)));
+
+    assert!(unsupported(&world, Vector3::new(5, 1, 5)).is_empty());
+
+    world.set_voxel(Vector3::new(0, 2, 0), None);
+
+    let clusters = unsupported(&world, Vector3::new(0, 2, 0));
+
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(clusters[0].len(), 5);
+    assert!(!clusters[0].contains(&Vector3::new(0, 1, 0)));
+}
+
+#[test]
+fn test_shared_support() {
+    let mut world: HashMap<Vector3<i32>, ([u8; 4], Material)> = HashMap::default();
+
+    let stone = Some(([0; 4], Material::Stone));
+
+    // A beam reaching past the radius on one side of the removed voxel, and a long
+    // path from the other side joining the beam close to the removed voxel
+    for x in 1..=20 {
+        world.set_voxel(Vector3::new(x, 0, 0), stone);
+    }
+
+    for z in -12..=0 {
+        world.set_voxel(Vector3::new(-1, 0, z), stone);
+    }
+
+    for x in 0..=5 {
+        world.set_voxel(Vector3::new(x, 0, -12), stone);
+    }
+
+    for z in -11..=-1 {
+        world.set_voxel(Vector3::new(5, 0, z), stone);
+    }
+
+    assert!(unsupported(&world, Vector3::new(0, 0, 0)).is_empty());
+}
@@ -1,5 +1,10 @@
 pub mod chunk;
 pub mod chunk_mesh;
+pub mod fluid;
+pub mod generator;
+pub mod granular;
+pub mod integrity;
 pub mod object;
 pub mod quad;
 pub mod terrain;
+pub mod world;
@@ -1,111 +1,316 @@
 use crate::engine::core::engine::Engine;
 use crate::engine::physics::simulation::Simulation;
 use crate::engine::renderer::frame::voxel_pass::VoxelPass;
-use crate::engine::voxel::chunk::{Chunk, CHUNK_SIZE, VOXEL_SIZE};
+use crate::engine::renderer::graph::DEPTH;
+use crate::engine::renderer::indirect::ChunkBuffer;
+use crate::engine::voxel::chunk::{material::Material, Chunk, CHUNK_SIZE, VOXEL_SIZE};
 use crate::engine::voxel::chunk_mesh::ChunkMesh;
+use crate::engine::voxel::generator::TerrainGenerator;
+use crate::engine::voxel::world::{split, VoxelWorld};
 use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
-use cgmath::{EuclideanSpace, Matrix4, MetricSpace, SquareMatrix, Vector3};
-use colorgrad::Gradient;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, SquareMatrix, Vector3, Zero};
+use crossbeam::atomic::AtomicCell;
 use crossbeam::channel::{unbounded, Receiver, Sender};
 use nalgebra::DMatrix;
-use noise::{NoiseFn, Perlin};
 use rapier3d::dynamics::{RigidBodyBuilder, RigidBodyHandle};
 use rapier3d::geometry::ColliderBuilder;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
 use std::thread;
+use std::time::Duration;
 use wgpu::Device;
 
-pub const MAX_STACKED_CHUNKS: usize = 8;
+/// Cosine of the half angle of the view cone whose chunks are generated first
+const FRUSTUM_COS: f32 = 0.5;
+/// Smallest fraction of the load distance, keeps at least the chunks around the eye
+const MIN_LOAD_SCALE: f32 = 0.1;
+
+/// Range around the eye in which chunks are loaded, in chunks
+#[derive(Debug, Clone, Copy)]
+pub struct TerrainDistance {
+    /// Radius around the eye on the XZ plane
+    pub horizontal: u32,
+    /// Layers below the eye
+    pub below: u32,
+    /// Layers above the eye
+    pub above: u32,
+}
+
+impl TerrainDistance {
+    /// Radius on the XZ plane in world units
+    pub fn radius(&self) -> f32 {
+        self.horizontal as f32 * CHUNK_SIZE as f32 * VOXEL_SIZE
+    }
+
+    /// Returns if the chunk lies inside the range scaled by `scale`, `eye` is given in chunks
+    fn contains(&self, eye: Vector3<f32>, chunk_pos: Vector3<i32>, scale: f32) -> bool {
+        let dx = chunk_pos.x as f32 - eye.x;
+        let dy = chunk_pos.y as f32 - eye.y.floor();
+        let dz = chunk_pos.z as f32 - eye.z;
+
+        (dx * dx + dz * dz).sqrt() <= self.horizontal as f32 * scale
+            && dy >= -(self.below as f32 * scale)
+            && dy <= self.above as f32 * scale
+    }
+}
+
+/// Memory limits of a [`Terrain`], in bytes
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryBudget {
+    /// Generator caches, e.g. height tiles
+    pub caches: usize,
+    /// Loaded chunks including their quads and device buffers
+    pub chunks: usize,
+}
 
 #[allow(clippy::type_complexity)]
 pub struct Terrain {
-    distance: u32,
-    eye_sender: Sender<Vector3<f32>>,
-    chunk_receiver: Receiver<(DMatrix<f32>, Arc<(Vector3<i32>, ChunkMesh)>)>,
+    distance: TerrainDistance,
+    budget: MemoryBudget,
+    eye_sender: Sender<(Vector3<f32>, Vector3<f32>)>,
+    chunk_receiver: Receiver<(u64, Option<DMatrix<f32>>, Arc<(Vector3<i32>, ChunkMesh)>)>,
+    skipped_receiver: Receiver<(u64, Vector3<i32>, bool)>,
     unload_sender: Sender<Vector3<i32>>,
-    chunks: HashMap<Vector3<i32>, (RigidBodyHandle, Arc<(Vector3<i32>, ChunkMesh)>)>,
+    /// Loaded chunks with the frame they were last in view, only chunks carrying a
+    /// heightfield have a rigid body
+    chunks: HashMap<Vector3<i32>, (Option<RigidBodyHandle>, Arc<(Vector3<i32>, ChunkMesh)>, u64)>,
+    /// Chunks the generator left empty, they count as loaded air
+    empty: HashSet<Vector3<i32>>,
+    /// Chunks the generator skipped as solid ground, generated once they are edited
+    solid: HashSet<Vector3<i32>>,
+    generator: Arc<Generator>,
+    frame: u64,
+    memory: usize,
+    gpu_memory: usize,
+    /// Quads of all chunks when they are drawn with indirect draws
+    indirect: Option<ChunkBuffer>,
 }
 
 impl Terrain {
     pub fn new(
-        seed: u32,
-        distance: u32,
-        gradient: Box<dyn Gradient + Send + Sync>,
+        distance: TerrainDistance,
+        budget: MemoryBudget,
+        generator: Box<dyn TerrainGenerator>,
         engine: &Engine,
     ) -> Terrain {
-        let capacity = (distance * 2).pow(2) as usize;
+        let capacity = (distance.horizontal * 2).pow(2) as usize;
 
         let (eye_sender, eye_receiver) = unbounded();
         let (chunk_sender, chunk_receiver) = unbounded();
+        let (skipped_sender, skipped_receiver) = unbounded();
         let (unload_sender, unload_receiver) = unbounded();
 
-        let mut generator = Generator {
+        let indirect = engine
+            .renderer()
+            .indirect_pipeline
+            .as_ref()
+            .map(|pipeline| {
+                ChunkBuffer::new(
+                    engine.device().clone(),
+                    engine.renderer().backend().queue().clone(),
+                    pipeline,
+                )
+            });
+
+        let generator = Arc::new(Generator {
             device: engine.device().clone(),
-            seed,
+            // Chunks drawn with indirect draws live in the `ChunkBuffer`
+            allocate: indirect.is_none(),
             distance,
-            gradient,
-            chunks: HashSet::with_capacity(capacity),
-            height_cache: HashMap::new(),
-            height_bounds_cache: HashMap::with_capacity(capacity),
-            eye_receiver,
+            budget,
+            generator: RwLock::new(Arc::from(generator)),
+            epoch: AtomicU64::new(0),
+            jobs: Mutex::new(Vec::new()),
+            jobs_available: Condvar::new(),
+            chunks: Mutex::new(HashSet::with_capacity(capacity)),
+            eye: AtomicCell::new(Vector3::zero()),
+            scale: AtomicCell::new(1.0),
+            in_flight: AtomicUsize::new(0),
+            memory: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
             chunk_sender,
-            unload_receiver,
-        };
+            skipped_sender,
+        });
 
-        thread::spawn(move || loop {
-            while let Ok(eye) = generator.eye_receiver.recv() {
-                generator.generate(eye);
+        // Leave one core for rendering
+        let workers = thread::available_parallelism()
+            .map(|n| n.get().saturating_sub(1))
+            .unwrap_or(1)
+            .max(1);
 
-                while let Ok(chunk_pos) = generator.unload_receiver.try_recv() {
-                    generator.unload_chunk(chunk_pos);
-                }
-            }
-        });
+        for _ in 0..workers {
+            let generator = generator.clone();
+
+            thread::spawn(move || generator.work());
+        }
+
+        let scheduler = generator.clone();
+
+        thread::spawn(move || scheduler.schedule(eye_receiver, unload_receiver));
 
         Terrain {
             distance,
+            budget,
             eye_sender,
             chunk_receiver,
+            skipped_receiver,
             unload_sender,
             chunks: HashMap::with_capacity(capacity),
+            empty: HashSet::with_capacity(capacity),
+            solid: HashSet::new(),
+            generator,
+            frame: 0,
+            memory: 0,
+            gpu_memory: 0,
+            indirect,
+        }
+    }
+
+    /// Number of chunks that are queued or currently generated
+    pub fn pending(&self) -> usize {
+        self.generator.pending()
+    }
+
+    /// Number of chunks that are loaded
+    pub fn loaded(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Host memory used by loaded chunks, in bytes
+    pub fn chunk_memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Device memory used by loaded chunks, in bytes
+    pub fn gpu_memory(&self) -> usize {
+        self.gpu_memory
+            + self
+                .indirect
+                .as_ref()
+                .map(|indirect| indirect.memory_usage())
+                .unwrap_or(0)
+    }
+
+    /// Memory used by the generator caches, in bytes
+    pub fn cache_memory(&self) -> usize {
+        self.generator.generator.read().unwrap().memory_usage()
+    }
+
+    /// Replaces the generator, all chunks are dropped and regenerated around the eye
+    pub fn set_generator(
+        &mut self,
+        generator: Box<dyn TerrainGenerator>,
+        simulation: &mut Simulation,
+    ) {
+        self.generator.replace(Arc::from(generator));
+
+        for (_, chunk) in self.chunks.drain() {
+            if let Some(handle) = chunk.0 {
+                simulation.remove_rigid_body(handle);
+            }
+        }
+
+        self.empty.clear();
+        self.solid.clear();
+
+        if let Some(indirect) = &mut self.indirect {
+            indirect.clear();
         }
+
+        self.memory = 0;
+        self.gpu_memory = 0;
     }
 
     pub fn render(&mut self, engine: &Engine, pass: &mut VoxelPass, simulation: &mut Simulation) {
         let eye = engine.camera().get_eye();
+        let direction = engine.camera().get_look_at() - eye;
+
+        self.frame += 1;
+
+        self.eye_sender.send((eye.to_vec(), direction)).unwrap();
+
+        while let Ok((epoch, heights, chunk)) = self.chunk_receiver.try_recv() {
+            // Generated before the generator was replaced
+            if epoch != self.generator.epoch.load(Ordering::Relaxed) {
+                continue;
+            }
 
-        self.eye_sender.send(eye.to_vec()).unwrap();
+            // Only chunks carrying the heightfield of their column get a body to collide with
+            let handle = heights.map(|heights| {
+                let rigid_body = RigidBodyBuilder::fixed()
+                    .translation(nalgebra::Vector3::new(
+                        chunk.0.x as f32 * CHUNK_SIZE as f32 + CHUNK_SIZE as f32 / 2.0,
+                        VOXEL_SIZE / 2.0,
+                        chunk.0.z as f32 * CHUNK_SIZE as f32 + CHUNK_SIZE as f32 / 2.0,
+                    ))
+                    .build();
 
-        while let Ok(data) = self.chunk_receiver.try_recv() {
-            let heights = data.0;
-            let chunk = data.1;
+                let handle = simulation.add_rigid_body(rigid_body);
 
-            let rigid_body = RigidBodyBuilder::fixed()
-                .translation(nalgebra::Vector3::new(
-                    chunk.0.x as f32 * CHUNK_SIZE as f32 + CHUNK_SIZE as f32 / 2.0,
-                    VOXEL_SIZE / 2.0,
-                    chunk.0.z as f32 * CHUNK_SIZE as f32 + CHUNK_SIZE as f32 / 2.0,
-                ))
-                .build();
+                let collider = ColliderBuilder::heightfield(
+                    heights,
+                    nalgebra::Vector3::new(CHUNK_SIZE as f32, 1.0, CHUNK_SIZE as f32),
+                );
 
-            let handle = simulation.add_rigid_body(rigid_body);
+                simulation.add_collider(collider, Some(handle));
 
-            let collider = ColliderBuilder::heightfield(
-                heights,
-                nalgebra::Vector3::new(CHUNK_SIZE as f32, 1.0, CHUNK_SIZE as f32),
-            );
+                handle
+            });
 
-            simulation.add_collider(collider, Some(handle));
+            self.memory += chunk.1.memory_usage();
+            self.gpu_memory += chunk.1.gpu_memory_usage();
+
+            if let Some(indirect) = &mut self.indirect {
+                indirect.insert(chunk.0, &chunk.1);
+            }
+
+            self.empty.remove(&chunk.0);
+            self.solid.remove(&chunk.0);
+
+            // The generator may have forgotten and regenerated a chunk before its unload arrived
+            if let Some(old) = self.chunks.insert(chunk.0, (handle, chunk, self.frame)) {
+                if let Some(handle) = old.0 {
+                    simulation.remove_rigid_body(handle);
+                }
+
+                self.memory -= old.1 .1.memory_usage();
+                self.gpu_memory -= old.1 .1.gpu_memory_usage();
+            }
+        }
+
+        while let Ok((epoch, chunk_pos, air)) = self.skipped_receiver.try_recv() {
+            // Chunks created by edits keep their voxels
+            if epoch == self.generator.epoch.load(Ordering::Relaxed)
+                && !self.chunks.contains_key(&chunk_pos)
+            {
+                match air {
+                    true => self.empty.insert(chunk_pos),
+                    false => self.solid.insert(chunk_pos),
+                };
+            }
+        }
+
+        let direction = if direction.is_zero() {
+            direction
+        } else {
+            direction.normalize()
+        };
 
-            self.chunks.insert(chunk.0, (handle, chunk));
+        for (chunk_pos, chunk) in self.chunks.iter_mut() {
+            if in_view(*chunk_pos, eye.to_vec(), direction) {
+                chunk.2 = self.frame;
+            }
         }
 
         let unload: Vec<Vector3<i32>> = self
             .chunks
-            .iter()
-            .filter_map(|(chunk_pos, _)| {
-                if (eye.to_vec() / CHUNK_SIZE as f32).distance(chunk_pos.map(|x| x as f32))
-                    > self.distance as f32 * 1.5
+            .keys()
+            .chain(&self.empty)
+            .chain(&self.solid)
+            .filter_map(|chunk_pos| {
+                if !self
+                    .distance
+                    .contains(eye.to_vec() / CHUNK_SIZE as f32, *chunk_pos, 1.5)
                 {
                     Some(*chunk_pos)
                 } else {
@@ -115,262 +320,532 @@ impl Terrain {
             .collect();
 
         for chunk_pos in unload {
-            if let Some(chunk) = self.chunks.get(&chunk_pos) {
-                simulation.remove_rigid_body(chunk.0);
+            self.unload_chunk(chunk_pos, simulation);
+        }
+
+        // Evict the least recently viewed chunks outside the load distance, the generator
+        // would only queue the ones inside again. If those don't fit into the budget, the
+        // load distance shrinks until they do, and grows back once there is room
+        let mut scale = self.generator.scale.load();
+
+        while self.memory + self.gpu_memory() > self.budget.chunks {
+            let mut lru: Vec<(u64, Vector3<i32>)> = self
+                .chunks
+                .iter()
+                .filter(|(chunk_pos, _)| {
+                    !self
+                        .distance
+                        .contains(eye.to_vec() / CHUNK_SIZE as f32, **chunk_pos, scale)
+                })
+                .map(|(chunk_pos, chunk)| (chunk.2, *chunk_pos))
+                .collect();
+
+            lru.sort_unstable_by_key(|(last_used, _)| *last_used);
+
+            for (_, chunk_pos) in lru {
+                if self.memory + self.gpu_memory() <= self.budget.chunks {
+                    break;
+                }
+
+                self.unload_chunk(chunk_pos, simulation);
+            }
 
-                self.unload_sender.send(chunk_pos).unwrap();
-                self.chunks.remove(&chunk_pos);
+            if self.memory + self.gpu_memory() <= self.budget.chunks || scale <= MIN_LOAD_SCALE {
+                break;
             }
+
+            scale = (scale * 0.9).max(MIN_LOAD_SCALE);
         }
 
-        for (chunk_pos, chunk) in &self.chunks {
-            pass.render_chunk(Matrix4::identity(), *chunk_pos, &chunk.1 .1);
+        // Growing by less than shrinking, so the distance settles instead of oscillating
+        if self.memory + self.gpu_memory() < self.budget.chunks * 3 / 4 {
+            scale = (scale * 1.05).min(1.0);
         }
-    }
-}
 
-#[allow(clippy::type_complexity)]
-struct Generator {
-    seed: u32,
-    distance: u32,
-    gradient: Box<dyn Gradient + Send + Sync>,
-    chunks: HashSet<Vector3<i32>>,
-    height_cache: HashMap<(i32, i32), usize>,
-    height_bounds_cache: HashMap<(i32, i32), (i32, i32)>,
-    eye_receiver: Receiver<Vector3<f32>>,
-    chunk_sender: Sender<(DMatrix<f32>, Arc<(Vector3<i32>, ChunkMesh)>)>,
-    unload_receiver: Receiver<Vector3<i32>>,
-    device: Arc<Device>,
-}
+        self.generator.scale.store(scale);
 
-impl Generator {
-    pub fn generate(&mut self, eye: Vector3<f32>) {
-        let eye_x = eye.x as i32 / CHUNK_SIZE as i32;
-        let eye_y = eye.y as i32 / CHUNK_SIZE as i32;
-        let eye_z = eye.z as i32 / CHUNK_SIZE as i32;
+        self.generator
+            .memory
+            .store(self.memory + self.gpu_memory(), Ordering::Relaxed);
 
-        let distance = self.distance as i32;
+        // Edited chunks are remeshed once per frame, however many voxels changed
+        for (_, chunk, _) in self.chunks.values_mut() {
+            let Some((chunk_pos, mesh)) = Arc::get_mut(chunk) else {
+                continue;
+            };
 
-        for x in (eye_x - distance)..(eye_x + distance) {
-            for y in (eye_y - distance)..(eye_y + distance) {
-                for z in (eye_z - distance)..(eye_z + distance) {
-                    let chunk_pos = Vector3::new(x, y, z);
+            if !mesh.is_dirty() {
+                continue;
+            }
 
-                    if !self.chunks.contains(&chunk_pos) {
-                        if let Some(chunk) = self.generate_chunk(chunk_pos) {
-                            let _ = self.chunk_sender.send(chunk);
-                        }
-                    }
+            self.memory -= mesh.memory_usage();
+            self.gpu_memory -= mesh.gpu_memory_usage();
+
+            mesh.remesh();
+
+            match &mut self.indirect {
+                Some(indirect) => indirect.insert(*chunk_pos, mesh),
+                None => {
+                    mesh.allocate(engine.device());
                 }
             }
+
+            self.memory += mesh.memory_usage();
+            self.gpu_memory += mesh.gpu_memory_usage();
         }
+
+        self.draw(engine, pass);
     }
 
-    #[allow(clippy::type_complexity)]
-    fn generate_chunk(
-        &mut self,
-        chunk_pos: Vector3<i32>,
-    ) -> Option<(DMatrix<f32>, Arc<(Vector3<i32>, ChunkMesh)>)> {
-        let min_x = chunk_pos.x * CHUNK_SIZE as i32;
-        let min_y = chunk_pos.y * CHUNK_SIZE as i32;
-        let min_z = chunk_pos.z * CHUNK_SIZE as i32;
+    /// Draws the loaded chunks into the shadow map of a shadow pass, call after `render`
+    pub fn render_shadow(&mut self, engine: &Engine, pass: &mut VoxelPass) {
+        self.draw(engine, pass);
+    }
 
-        let bounds_key = (chunk_pos.x, chunk_pos.z);
+    fn draw(&mut self, engine: &Engine, pass: &mut VoxelPass) {
+        match (&mut self.indirect, &engine.renderer().indirect_pipeline) {
+            (Some(indirect), Some(pipeline)) => {
+                match pass.cascade() {
+                    Some(cascade) => {
+                        indirect.cull_shadow(pipeline, cascade, pass.frustum(), pass.eye())
+                    }
+                    None => {
+                        let (depth, depth_size) = {
+                            let graph = engine.renderer().graph();
+
+                            (
+                                graph.targets().view(DEPTH).clone(),
+                                graph.targets().size(DEPTH),
+                            )
+                        };
+
+                        indirect.cull(
+                            pipeline,
+                            pass.frustum(),
+                            pass.eye(),
+                            engine.camera().view_projection(),
+                            &depth,
+                            depth_size,
+                        );
+                    }
+                }
 
-        let (min_height, max_height) =
-            if let Some(&bounds) = self.height_bounds_cache.get(&bounds_key) {
-                bounds
-            } else {
-                let mut min_height = i32::MAX;
-                let mut max_height = i32::MIN;
+                pass.render_indirect(engine.renderer(), indirect);
+            }
+            // Adapters without indirect draws draw chunk by chunk
+            _ => {
+                for (chunk_pos, chunk) in &self.chunks {
+                    pass.render_chunk(Matrix4::identity(), *chunk_pos, &chunk.1 .1);
+                }
+            }
+        }
+    }
+}
 
-                for dx in (0..CHUNK_SIZE as i32).step_by(4) {
-                    for dz in (0..CHUNK_SIZE as i32).step_by(4) {
-                        let h = self.get_cached_height(min_x + dx, min_z + dz) as i32;
+impl Terrain {
+    /// Generates the solid chunk at `chunk_pos`, if it was skipped
+    fn generate_solid(&mut self, chunk_pos: Vector3<i32>) {
+        if !self.solid.remove(&chunk_pos) {
+            return;
+        }
 
-                        min_height = min_height.min(h);
-                        max_height = max_height.max(h);
-                    }
-                }
+        let generator = self.generator.generator.read().unwrap().clone();
+        let chunk = generator
+            .generate_solid(chunk_pos)
+            .unwrap_or_else(Chunk::empty);
 
-                self.height_bounds_cache
-                    .insert(bounds_key, (min_height, max_height));
+        let mut mesh = ChunkMesh::new(chunk);
+        mesh.mark_dirty();
+        self.memory += mesh.memory_usage();
 
-                (min_height, max_height)
-            };
+        self.chunks
+            .insert(chunk_pos, (None, Arc::new((chunk_pos, mesh)), self.frame));
+    }
 
-        if max_height < min_y || min_height >= min_y + CHUNK_SIZE as i32 {
-            return None;
+    fn unload_chunk(&mut self, chunk_pos: Vector3<i32>, simulation: &mut Simulation) {
+        if self.empty.remove(&chunk_pos) || self.solid.remove(&chunk_pos) {
+            self.unload_sender.send(chunk_pos).unwrap();
         }
 
-        let mut chunk = Chunk::empty();
-        let mut has_voxels = false;
-        let perlin = Perlin::new(self.seed);
-        for z in 0..CHUNK_SIZE {
-            for x in 0..CHUNK_SIZE {
-                let height = self.get_cached_height(min_x + x as i32, min_z + z as i32) as i32;
-
-                const NOISE_INTENSITY: f64 = 3.0;
-                if height >= min_y && height < min_y + CHUNK_SIZE as i32 {
-                    has_voxels = true;
-
-                    let local_height = (height - min_y) as usize;
-
-                    for y in 0..=local_height {
-                        let noise_y = calculate_noise(
-                            x as i32,
-                            y as i32,
-                            z as i32,
-                            min_x,
-                            min_y,
-                            min_z,
-                            NOISE_INTENSITY,
-                            &perlin,
-                        );
-                        chunk.set(
-                            x,
-                            y,
-                            z,
-                            true,
-                            self.gradient.at((noise_y / 256.0) as f32).to_rgba8(),
-                        );
-                    }
-                } else if height >= min_y + CHUNK_SIZE as i32 {
-                    has_voxels = true;
-                    for y in 0..CHUNK_SIZE {
-                        let noise_y = calculate_noise(
-                            x as i32,
-                            y as i32,
-                            z as i32,
-                            min_x,
-                            min_y,
-                            min_z,
-                            NOISE_INTENSITY,
-                            &perlin,
-                        );
-                        chunk.set(
-                            x,
-                            y,
-                            z,
-                            true,
-                            self.gradient.at((noise_y / 256.0) as f32).to_rgba8(),
-                        );
-                    }
-                }
+        if let Some(chunk) = self.chunks.remove(&chunk_pos) {
+            if let Some(handle) = chunk.0 {
+                simulation.remove_rigid_body(handle);
+            }
+
+            if let Some(indirect) = &mut self.indirect {
+                indirect.remove(chunk_pos);
             }
+
+            self.memory -= chunk.1 .1.memory_usage();
+            self.gpu_memory -= chunk.1 .1.gpu_memory_usage();
+
+            self.unload_sender.send(chunk_pos).unwrap();
         }
+    }
+}
 
-        if has_voxels {
-            let mut chunk_mesh = ChunkMesh::new(chunk);
-            chunk_mesh.remesh();
-            chunk_mesh.allocate(&self.device);
+impl VoxelWorld for Terrain {
+    fn voxel(&self, position: Vector3<i32>) -> Option<Option<Material>> {
+        let (chunk_pos, local) = split(position);
+
+        if self.empty.contains(&chunk_pos) {
+            return Some(None);
+        }
 
-            let chunk = Arc::new((chunk_pos, chunk_mesh));
-            self.chunks.insert(chunk_pos);
+        if self.solid.contains(&chunk_pos) {
+            return Some(Some(Material::Stone));
+        }
 
-            let mut heights = DMatrix::<f32>::zeros(CHUNK_SIZE, CHUNK_SIZE);
+        let chunk = self.chunks.get(&chunk_pos)?.1 .1.chunk();
 
-            for x in 0..CHUNK_SIZE {
-                for z in 0..CHUNK_SIZE {
-                    let y = self.get_cached_height(
-                        chunk_pos.x * CHUNK_SIZE as i32 + x as i32,
-                        chunk_pos.z * CHUNK_SIZE as i32 + z as i32,
-                    );
+        Some(chunk.get_occupied(local.x, local.y, local.z).then(|| {
+            chunk
+                .get_material(local.x, local.y, local.z)
+                .unwrap_or_default()
+        }))
+    }
 
-                    heights[(z, x)] = y as f32;
-                }
-            }
+    fn color(&self, position: Vector3<i32>) -> Option<[u8; 4]> {
+        let (chunk_pos, local) = split(position);
+
+        let chunk = self.chunks.get(&chunk_pos)?.1 .1.chunk();
 
-            Some((heights, chunk))
+        if chunk.get_occupied(local.x, local.y, local.z) {
+            chunk.get_color(local.x, local.y, local.z)
         } else {
             None
         }
     }
 
-    fn get_cached_height(&mut self, x: i32, z: i32) -> usize {
-        *self
-            .height_cache
-            .entry((x, z))
-            .or_insert_with(|| heightmap(self.seed, x, z))
-    }
+    fn set_voxel(&mut self, position: Vector3<i32>, voxel: Option<([u8; 4], Material)>) -> bool {
+        let (chunk_pos, local) = split(position);
 
-    fn unload_chunk(&mut self, chunk_pos: Vector3<i32>) {
-        self.chunks.remove(&chunk_pos);
+        // Empty chunks get their voxels on the first write
+        if self.empty.contains(&chunk_pos) {
+            if voxel.is_none() {
+                return true;
+            }
+
+            self.empty.remove(&chunk_pos);
 
-        for x in 0..CHUNK_SIZE {
-            for z in 0..CHUNK_SIZE {
-                self.height_cache.remove(&(
-                    chunk_pos.x * CHUNK_SIZE as i32 + x as i32,
-                    chunk_pos.z * CHUNK_SIZE as i32 + z as i32,
-                ));
+            let mesh = ChunkMesh::new(Chunk::empty());
+            self.memory += mesh.memory_usage();
+
+            self.chunks
+                .insert(chunk_pos, (None, Arc::new((chunk_pos, mesh)), self.frame));
+        }
+
+        self.generate_solid(chunk_pos);
+
+        // Digging exposes the solid chunks around the hole
+        if voxel.is_none() {
+            for offset in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+                self.generate_solid(split(position + offset).0);
+                self.generate_solid(split(position - offset).0);
             }
         }
 
-        self.height_bounds_cache.remove(&(chunk_pos.x, chunk_pos.z));
+        // Chunks are only shared while they travel from the workers
+        let Some((_, mesh)) = self
+            .chunks
+            .get_mut(&chunk_pos)
+            .and_then(|chunk| Arc::get_mut(&mut chunk.1))
+        else {
+            return false;
+        };
+
+        match voxel {
+            Some((color, material)) => mesh
+                .chunk_mut()
+                .set_with_material(local.x, local.y, local.z, true, color, material),
+            None => mesh.chunk_mut().clear(local.x, local.y, local.z),
+        }
+
+        mesh.mark_dirty();
+
+        true
+    }
+
+    fn is_loaded(&self, chunk_pos: Vector3<i32>) -> bool {
+        self.chunks.contains_key(&chunk_pos)
+            || self.empty.contains(&chunk_pos)
+            || self.solid.contains(&chunk_pos)
+    }
+}
+
+impl Drop for Terrain {
+    fn drop(&mut self) {
+        self.generator.stop();
     }
 }
 
-fn heightmap(seed: u32, x: i32, z: i32) -> usize {
-    let perlin = Perlin::new(seed);
+/// Generation state shared by the scheduler and the worker threads
+#[allow(clippy::type_complexity)]
+struct Generator {
+    distance: TerrainDistance,
+    budget: MemoryBudget,
+    generator: RwLock<Arc<dyn TerrainGenerator>>,
+    /// Incremented whenever the generator is replaced
+    epoch: AtomicU64,
+    /// Chunks waiting for a worker, the most important one is last
+    jobs: Mutex<Vec<Vector3<i32>>>,
+    jobs_available: Condvar,
+    /// Chunks that are generated, in flight or known to be empty
+    chunks: Mutex<HashSet<Vector3<i32>>>,
+    /// Most recent eye, in chunks
+    eye: AtomicCell<Vector3<f32>>,
+    /// Fraction of the load distance whose chunks fit into the memory budget
+    scale: AtomicCell<f32>,
+    in_flight: AtomicUsize,
+    /// Memory used by the chunks loaded in `Terrain`
+    memory: AtomicUsize,
+    stopped: AtomicBool,
+    chunk_sender: Sender<(u64, Option<DMatrix<f32>>, Arc<(Vector3<i32>, ChunkMesh)>)>,
+    /// Chunks the generator skipped, with whether they are all air or solid ground
+    skipped_sender: Sender<(u64, Vector3<i32>, bool)>,
+    device: Arc<Device>,
+    /// Whether workers upload the quads of every chunk into its own buffer
+    allocate: bool,
+}
+
+impl Generator {
+    fn pending(&self) -> usize {
+        self.jobs.lock().unwrap().len() + self.in_flight.load(Ordering::Relaxed)
+    }
+
+    fn replace(&self, generator: Arc<dyn TerrainGenerator>) {
+        // Workers read the epoch under the same lock, so no stale chunk stays marked
+        let mut chunks = self.chunks.lock().unwrap();
+
+        *self.generator.write().unwrap() = generator;
+        self.epoch.fetch_add(1, Ordering::Relaxed);
+
+        chunks.clear();
+        self.jobs.lock().unwrap().clear();
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.jobs.lock().unwrap().clear();
+        self.jobs_available.notify_all();
+    }
+
+    /// Returns if the chunk would still be kept by `Terrain`
+    fn in_range(&self, chunk_pos: Vector3<i32>) -> bool {
+        self.distance.contains(self.eye.load(), chunk_pos, 1.5)
+    }
+
+    /// Rebuilds the job queue whenever the eye enters another chunk or turns
+    fn schedule(
+        &self,
+        eye_receiver: Receiver<(Vector3<f32>, Vector3<f32>)>,
+        unload_receiver: Receiver<Vector3<i32>>,
+    ) {
+        let mut last_center = Vector3::new(i32::MAX, i32::MAX, i32::MAX);
+        let mut last_direction = Vector3::zero();
+        let mut last_epoch = 0;
+        let mut last_scale = 1.0;
+
+        while let Ok(mut eye) = eye_receiver.recv() {
+            // Only the most recent eye matters
+            while let Ok(next) = eye_receiver.try_recv() {
+                eye = next;
+            }
+
+            let (eye, direction) = eye;
+
+            let direction = if direction.is_zero() {
+                direction
+            } else {
+                direction.normalize()
+            };
+
+            let generator = self.generator.read().unwrap().clone();
+
+            while let Ok(chunk_pos) = unload_receiver.try_recv() {
+                self.chunks.lock().unwrap().remove(&chunk_pos);
+                generator.unload(chunk_pos);
+            }
 
-    const SCALE: f64 = 0.001;
-    const HEIGHT_MULTIPLIER: f64 = 100.0;
-    const OCTAVES: u32 = 4;
-    const PERSISTENCE: f64 = 0.5;
-    const DETAIL_SCALE: f64 = 2.0;
+            generator.trim(self.budget.caches);
+            // Evicted chunks may have made room for waiting workers
+            self.jobs_available.notify_all();
 
-    let mut amplitude = 1.0;
-    let mut frequency = 3.0;
-    let mut height = 0.0;
+            self.eye.store(eye / CHUNK_SIZE as f32);
 
-    let flatness = perlin.get([x as f64 * 0.001, z as f64 * 0.001]);
+            let center = eye.map(|x| (x / CHUNK_SIZE as f32).floor() as i32);
 
-    for _ in 0..OCTAVES {
-        height +=
-            perlin.get([x as f64 * SCALE * frequency, z as f64 * SCALE * frequency]) * amplitude;
+            let epoch = self.epoch.load(Ordering::Relaxed);
+            let scale = self.scale.load();
 
-        let weirdness = perlin.get([x as f64 * SCALE * frequency, z as f64 * SCALE * frequency]);
+            if center == last_center
+                && direction.dot(last_direction) > 0.95
+                && epoch == last_epoch
+                && scale == last_scale
+            {
+                continue;
+            }
 
-        let pv = 1.0 - (3.0 * weirdness.abs() - 2.0).abs();
+            last_center = center;
+            last_direction = direction;
+            last_epoch = epoch;
+            last_scale = scale;
 
-        height += pv * amplitude * (flatness * 1.2);
+            self.enqueue(center, eye, direction, scale);
+        }
 
-        amplitude *= PERSISTENCE;
-        frequency *= 2.0;
+        self.stop();
     }
 
-    let continental = perlin.get([x as f64 * 0.0001, z as f64 * 0.0001]);
-    let detail = perlin.get([x as f64 * 0.09, z as f64 * 0.09]);
+    fn enqueue(
+        &self,
+        center: Vector3<i32>,
+        eye: Vector3<f32>,
+        direction: Vector3<f32>,
+        scale: f32,
+    ) {
+        let horizontal = self.distance.horizontal as i32;
+        let below = self.distance.below as i32;
+        let above = self.distance.above as i32;
+
+        let mut jobs =
+            Vec::with_capacity(((horizontal * 2 + 1).pow(2) * (below + above + 1)) as usize);
+
+        {
+            let mut chunks = self.chunks.lock().unwrap();
+
+            // Forget far away empty chunks, loaded chunks are unloaded by `Terrain`
+            chunks.retain(|chunk_pos| {
+                self.distance
+                    .contains(eye / CHUNK_SIZE as f32, *chunk_pos, 2.0)
+            });
+
+            for x in (center.x - horizontal)..=(center.x + horizontal) {
+                for y in (center.y - below)..=(center.y + above) {
+                    for z in (center.z - horizontal)..=(center.z + horizontal) {
+                        let chunk_pos = Vector3::new(x, y, z);
+
+                        // Stay well inside the unload range of `Terrain`
+                        if !self
+                            .distance
+                            .contains(eye / CHUNK_SIZE as f32, chunk_pos, scale)
+                        {
+                            continue;
+                        }
+
+                        if !chunks.contains(&chunk_pos) {
+                            jobs.push(chunk_pos);
+                        }
+                    }
+                }
+            }
+        }
+
+        let priority = |chunk_pos: &Vector3<i32>| {
+            let middle = (chunk_pos.map(|x| x as f32) + Vector3::new(0.5, 0.5, 0.5))
+                * CHUNK_SIZE as f32
+                - eye;
 
-    let height =
-        ((height + 1.0) * (HEIGHT_MULTIPLIER * continental) + (detail * DETAIL_SCALE)) * flatness;
+            (in_view(*chunk_pos, eye, direction), -middle.magnitude())
+        };
 
-    height.clamp(0.0, (MAX_STACKED_CHUNKS * CHUNK_SIZE - 1) as f64) as usize
-}
+        // Most important chunks last, workers pop from the back
+        jobs.sort_unstable_by(|a, b| priority(a).partial_cmp(&priority(b)).unwrap());
+
+        *self.jobs.lock().unwrap() = jobs;
+        self.jobs_available.notify_all();
+    }
+
+    fn work(&self) {
+        loop {
+            let chunk_pos = {
+                let mut jobs = self.jobs.lock().unwrap();
+
+                loop {
+                    if self.stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    // Wait for `Terrain` to evict chunks while the budget is used up
+                    if self.memory.load(Ordering::Relaxed) < self.budget.chunks {
+                        if let Some(chunk_pos) = jobs.pop() {
+                            break chunk_pos;
+                        }
+                    }
+
+                    jobs = self
+                        .jobs_available
+                        .wait_timeout(jobs, Duration::from_millis(50))
+                        .unwrap()
+                        .0;
+                }
+            };
+
+            let (epoch, generator) = {
+                let mut chunks = self.chunks.lock().unwrap();
+
+                if !self.in_range(chunk_pos) || !chunks.insert(chunk_pos) {
+                    continue;
+                }
 
-#[allow(clippy::too_many_arguments)]
-fn calculate_noise(
-    x: i32,
-    y: i32,
-    z: i32,
-    min_x: i32,
-    min_y: i32,
-    min_z: i32,
-    noise_intensity: f64,
-    perlin: &Perlin,
-) -> f64 {
-    let noise_y = min_y as f64 + y as f64;
-    let height_difference = noise_y / 30.0;
-
-    if noise_y >= 32.0 + noise_intensity * height_difference {
-        noise_y
-            + perlin.get([
-                (min_x + x) as f64 * 0.1,
-                (min_y + y) as f64 * 0.1,
-                (min_z + z) as f64 * 0.1,
-            ]) * noise_intensity
-                * height_difference
-    } else {
-        noise_y
+                (
+                    self.epoch.load(Ordering::Relaxed),
+                    self.generator.read().unwrap().clone(),
+                )
+            };
+
+            self.in_flight.fetch_add(1, Ordering::Relaxed);
+
+            if let Some((heights, chunk)) =
+                self.generate_chunk(generator.as_ref(), epoch, chunk_pos)
+            {
+                let _ = self.chunk_sender.send((epoch, heights, chunk));
+            }
+
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        }
     }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_chunk(
+        &self,
+        generator: &dyn TerrainGenerator,
+        epoch: u64,
+        chunk_pos: Vector3<i32>,
+    ) -> Option<(Option<DMatrix<f32>>, Arc<(Vector3<i32>, ChunkMesh)>)> {
+        let Some(chunk) = generator.generate(chunk_pos) else {
+            let _ = self
+                .skipped_sender
+                .send((epoch, chunk_pos, generator.is_air(chunk_pos)));
+
+            return None;
+        };
+
+        // Cancel the job if the eye moved away in the meantime
+        if !self.in_range(chunk_pos) {
+            self.chunks.lock().unwrap().remove(&chunk_pos);
+            return None;
+        }
+
+        let mut chunk_mesh = ChunkMesh::new(chunk);
+        chunk_mesh.remesh();
+
+        if self.allocate {
+            chunk_mesh.allocate(&self.device);
+        }
+
+        let chunk = Arc::new((chunk_pos, chunk_mesh));
+
+        Some((generator.heights(chunk_pos), chunk))
+    }
+}
+
+/// Returns if the chunk is close to the eye or inside the view cone
+fn in_view(chunk_pos: Vector3<i32>, eye: Vector3<f32>, direction: Vector3<f32>) -> bool {
+    let middle =
+        (chunk_pos.map(|x| x as f32) + Vector3::new(0.5, 0.5, 0.5)) * CHUNK_SIZE as f32 - eye;
+
+    let distance = middle.magnitude();
+
+    distance < CHUNK_SIZE as f32 * 2.0 || middle.dot(direction) >= distance * FRUSTUM_COS
 }
@@ -0,0 +1,219 @@
+use super::chunk::{material::Material, CHUNK_SIZE};
+use ahash::{HashMap, HashSet};
+use cgmath::{InnerSpace, Point3, Vector3};
+
+pub const HORIZONTAL: [Vector3<i32>; 4] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(0, 0, 1),
+    Vector3::new(-1, 0, 0),
+    Vector3::new(0, 0, -1),
+];
+
+pub const NEIGHBOURS: [Vector3<i32>; 6] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(-1, 0, 0),
+    Vector3::new(0, 1, 0),
+    Vector3::new(0, -1, 0),
+    Vector3::new(0, 0, 1),
+    Vector3::new(0, 0, -1),
+];
+
+/// Voxels addressed by world position, read and edited by the voxel simulations
+pub trait VoxelWorld {
+    /// Material of a voxel, `Some(None)` for air and `None` where no chunk is loaded
+    fn voxel(&self, position: Vector3<i32>) -> Option<Option<Material>>;
+
+    /// Color of a voxel, `None` for air and where no chunk is loaded
+    fn color(&self, position: Vector3<i32>) -> Option<[u8; 4]>;
+
+    /// Sets or clears a voxel, returns `false` where no chunk is loaded
+    fn set_voxel(&mut self, position: Vector3<i32>, voxel: Option<([u8; 4], Material)>) -> bool;
+
+    fn is_loaded(&self, chunk_pos: Vector3<i32>) -> bool;
+}
+
+/// Sparse voxels without chunks, every position counts as loaded
+impl VoxelWorld for HashMap<Vector3<i32>, ([u8; 4], Material)> {
+    fn voxel(&self, position: Vector3<i32>) -> Option<Option<Material>> {
+        Some(self.get(&position).map(|(_, material)| *material))
+    }
+
+    fn color(&self, position: Vector3<i32>) -> Option<[u8; 4]> {
+        self.get(&position).map(|(color, _)| *color)
+    }
+
+    fn set_voxel(&mut self, position: Vector3<i32>, voxel: Option<([u8; 4], Material)>) -> bool {
+        match voxel {
+            Some(voxel) => self.insert(position, voxel),
+            None => self.remove(&position),
+        };
+
+        true
+    }
+
+    fn is_loaded(&self, _chunk_pos: Vector3<i32>) -> bool {
+        true
+    }
+}
+
+/// Splits a world position into the chunk position and the position inside the chunk
+pub fn split(position: Vector3<i32>) -> (Vector3<i32>, Vector3<usize>) {
+    (
+        position.map(|x| x.div_euclid(CHUNK_SIZE as i32)),
+        position.map(|x| x.rem_euclid(CHUNK_SIZE as i32) as usize),
+    )
+}
+
+/// Voxels waiting for a simulation tick, grouped by chunk. Chunks without any are asleep
+#[derive(Default)]
+pub struct TickQueue {
+    chunks: HashMap<Vector3<i32>, HashSet<u16>>,
+}
+
+impl TickQueue {
+    pub fn push(&mut self, position: Vector3<i32>) {
+        let (chunk_pos, index) = locate(position);
+
+        self.chunks.entry(chunk_pos).or_default().insert(index);
+    }
+
+    /// Queues a voxel and its neighbours
+    pub fn push_around(&mut self, position: Vector3<i32>) {
+        self.push(position);
+
+        for offset in NEIGHBOURS {
+            self.push(position + offset);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(|indices| indices.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Forgets the voxels of chunks that are no longer loaded
+    pub fn retain_loaded(&mut self, world: &impl VoxelWorld) {
+        self.chunks
+            .retain(|chunk_pos, _| world.is_loaded(*chunk_pos));
+    }
+
+    /// Removes at most `limit` voxels, lowest first, the others stay queued
+    pub fn take(&mut self, limit: usize) -> Vec<Vector3<i32>> {
+        let mut positions: Vec<Vector3<i32>> = self
+            .chunks
+            .drain()
+            .flat_map(|(chunk_pos, indices)| {
+                indices
+                    .into_iter()
+                    .map(move |index| position(chunk_pos, index))
+            })
+            .collect();
+
+        positions.sort_unstable_by_key(|position| (position.y, position.z, position.x));
+
+        for position in positions.split_off(positions.len().min(limit)) {
+            self.push(position);
+        }
+
+        positions
+    }
+}
+
+/// Chunk of a world position and the index of the voxel inside the chunk
+pub fn locate(position: Vector3<i32>) -> (Vector3<i32>, u16) {
+    let (chunk_pos, local) = split(position);
+
+    (
+        chunk_pos,
+        ((local.z * CHUNK_SIZE + local.y) * CHUNK_SIZE + local.x) as u16,
+    )
+}
+
+/// World position of a voxel index inside a chunk
+pub fn position(chunk_pos: Vector3<i32>, index: u16) -> Vector3<i32> {
+    let index = index as i32;
+    let size = CHUNK_SIZE as i32;
+
+    chunk_pos * size + Vector3::new(index % size, index / size % size, index / (size * size))
+}
+
+/// First solid voxel hit by a ray together with the voxel the ray entered it from,
+/// water is passed through. Voxels span `[x, x + 1]`, `[y, y + 1]` and `[z - 1, z]`
+pub fn raycast(
+    world: &impl VoxelWorld,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    distance: f32,
+) -> Option<(Vector3<i32>, Vector3<i32>)> {
+    let direction = direction.normalize();
+    let origin = Vector3::new(origin.x, origin.y, origin.z + 1.0);
+
+    let mut voxel = origin.map(|x| x.floor() as i32);
+    let mut previous = voxel;
+
+    let step = direction.map(|d| d.signum() as i32);
+    let delta = direction.map(|d| 1.0 / d.abs());
+
+    // Distance along the ray to the next voxel border on each axis
+    let mut next = Vector3::new(0, 1, 2).map(|axis: usize| {
+        if direction[axis] > 0.0 {
+            (voxel[axis] as f32 + 1.0 - origin[axis]) * delta[axis]
+        } else {
+            (origin[axis] - voxel[axis] as f32) * delta[axis]
+        }
+    });
+
+    loop {
+        if let Some(Some(material)) = world.voxel(voxel) {
+            if material != Material::Water {
+                return Some((voxel, previous));
+            }
+        }
+
+        let axis = if next.x < next.y && next.x < next.z {
+            0
+        } else if next.y < next.z {
+            1
+        } else {
+            2
+        };
+
+        if next[axis] > distance {
+            return None;
+        }
+
+        previous = voxel;
+        voxel[axis] += step[axis];
+        next[axis] += delta[axis];
+    }
+}
+
+#[test]
+fn test_raycast() {
+    let mut world = HashMap::default();
+
+    world.set_voxel(Vector3::new(0, 0, 5), Some(([0; 4], Material::Water)));
+    world.set_voxel(Vector3::new(0, 0, 8), Some(([0; 4], Material::Stone)));
+
+    let hit = raycast(
+        &world,
+        Point3::new(0.5, 0.5, -0.5),
+        Vector3::new(0.0, 0.0, 1.0),
+        16.0,
+    );
+
+    assert_eq!(hit, Some((Vector3::new(0, 0, 8), Vector3::new(0, 0, 7))));
+
+    assert_eq!(
+        raycast(
+            &world,
+            Point3::new(0.5, 0.5, -0.5),
+            Vector3::new(0.0, 0.0, 1.0),
+            4.0
+        ),
+        None
+    );
+}
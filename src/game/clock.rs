@@ -0,0 +1,158 @@
+use cgmath::{InnerSpace, Vector3};
+use std::{f32::consts::TAU, fmt, time::Instant};
+
+use crate::engine::{core::engine::Engine, renderer::sky::Lighting};
+
+/// Hours in a day of the world clock
+pub const DAY_LENGTH: f32 = 24.0;
+/// Time of day of new worlds, in hours
+pub const DEFAULT_TIME: f32 = 10.0;
+/// Hours passing per real second, a day lasts 20 minutes
+pub const DEFAULT_SPEED: f32 = DAY_LENGTH / (20.0 * 60.0);
+/// Longest real time a tick advances the clock by, so it doesn't jump after a pause
+const MAX_STEP: f32 = 0.25;
+/// Offset of the sun path towards +Z, so it doesn't pass straight overhead
+const SUN_TILT: f32 = 0.3;
+
+const DUSK: Lighting = Lighting {
+    zenith: [0.25, 0.3, 0.55],
+    horizon: [0.95, 0.55, 0.35],
+    light: [0.6, 0.35, 0.2],
+    ambient: 0.25,
+    disc: [1.0, 0.6, 0.3],
+};
+const NIGHT: Lighting = Lighting {
+    zenith: [0.01, 0.02, 0.06],
+    horizon: [0.04, 0.06, 0.12],
+    light: [0.12, 0.14, 0.2],
+    ambient: 0.12,
+    disc: [0.8, 0.85, 0.95],
+};
+
+/// Time of day of the world, drives the sun, the sky colors and the light of the voxels
+pub struct WorldClock {
+    /// Hours since midnight, in `0..DAY_LENGTH`
+    time: f32,
+    /// Hours passing per real second
+    pub speed: f32,
+    last: Option<Instant>,
+}
+
+impl Default for WorldClock {
+    fn default() -> Self {
+        Self {
+            time: DEFAULT_TIME,
+            speed: DEFAULT_SPEED,
+            last: None,
+        }
+    }
+}
+
+impl WorldClock {
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.rem_euclid(DAY_LENGTH);
+    }
+
+    /// Advances the clock by the real time since the last tick, call once per frame
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+
+        if let Some(last) = self.last {
+            let seconds = (now - last).as_secs_f32().min(MAX_STEP);
+
+            self.set_time(self.time + seconds * self.speed);
+        }
+
+        self.last = Some(now);
+    }
+
+    /// Direction towards the sun, it rises at 6:00 in +X and sets at 18:00 in -X
+    pub fn sun(&self) -> Vector3<f32> {
+        let angle = (self.time - 6.0) / DAY_LENGTH * TAU;
+
+        Vector3::new(angle.cos(), angle.sin(), SUN_TILT).normalize()
+    }
+
+    /// Direction of the light casting shadows, the sun by day and the moon opposite of it
+    /// by night
+    pub fn light(&self) -> Vector3<f32> {
+        let sun = self.sun();
+
+        if sun.y >= 0.0 {
+            sun
+        } else {
+            -sun
+        }
+    }
+
+    /// Colors of the sky and the light, blended from dusk to day or night by the height of
+    /// the sun
+    pub fn lighting(&self) -> Lighting {
+        let height = self.sun().y;
+
+        let mut lighting = if height >= 0.0 {
+            blend(&DUSK, &Lighting::DAY, smoothstep(0.0, 0.25, height))
+        } else {
+            blend(&DUSK, &NIGHT, smoothstep(0.0, 0.2, -height))
+        };
+
+        // The light flips from the sun to the moon at the horizon, fade it out around it
+        let fade = smoothstep(0.0, 0.1, height.abs());
+        lighting.light = lighting.light.map(|c| c * fade);
+        lighting.disc = lighting
+            .disc
+            .map(|c| c * smoothstep(0.0, 0.05, height.abs()));
+
+        lighting
+    }
+
+    /// Sends the sun direction and the lighting to the renderer, call before starting a frame
+    pub fn apply(&self, engine: &Engine) {
+        engine.renderer().shadows().set_sun(self.light());
+        engine.renderer().sky().set_lighting(self.lighting());
+    }
+}
+
+impl fmt::Display for WorldClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = (self.time * 60.0) as u32;
+
+        write!(f, "{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn blend(a: &Lighting, b: &Lighting, t: f32) -> Lighting {
+    let mix = |a: [f32; 3], b: [f32; 3]| std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t);
+
+    Lighting {
+        zenith: mix(a.zenith, b.zenith),
+        horizon: mix(a.horizon, b.horizon),
+        light: mix(a.light, b.light),
+        ambient: a.ambient + (b.ambient - a.ambient) * t,
+        disc: mix(a.disc, b.disc),
+    }
+}
+
+#[test]
+fn test_clock() {
+    let mut clock = WorldClock::default();
+
+    clock.set_time(12.0);
+    assert!(clock.sun().y > 0.9);
+    assert!((clock.lighting().ambient - Lighting::DAY.ambient).abs() < 1e-6);
+
+    clock.set_time(24.0 + 0.5);
+    assert_eq!(clock.to_string(), "00:30");
+    assert!(clock.sun().y < 0.0 && clock.light().y > 0.0);
+    assert!((clock.lighting().ambient - NIGHT.ambient).abs() < 1e-6);
+}
@@ -0,0 +1,191 @@
+use crate::engine::{
+    core::engine::Engine,
+    physics::simulation::Simulation,
+    renderer::frame::voxel_pass::VoxelPass,
+    voxel::{
+        chunk::material::Material,
+        fluid::FluidSimulation,
+        granular::GranularSimulation,
+        integrity::{detach, unsupported},
+        object::Object,
+        world::{raycast, VoxelWorld},
+    },
+};
+use cgmath::{Matrix4, Quaternion, Vector3};
+use rapier3d::prelude::RigidBodyHandle;
+use winit::{
+    event::{ElementState, MouseButton, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+/// Farthest distance from the eye at which voxels can be edited
+const REACH: f32 = 64.0;
+
+/// Materials that can be placed, picked with the number keys
+const BRUSHES: [(KeyCode, Material); 4] = [
+    (KeyCode::Digit1, Material::Stone),
+    (KeyCode::Digit2, Material::Sand),
+    (KeyCode::Digit3, Material::Gravel),
+    (KeyCode::Digit4, Material::Water),
+];
+
+/// Key toggling the structural integrity check
+const INTEGRITY_KEY: KeyCode = KeyCode::KeyI;
+/// Broken off clusters kept at most, the oldest are removed first
+const MAX_DEBRIS: usize = 64;
+
+/// Voxel editing with the voxel simulations reacting to the edits.
+/// The left button breaks the voxel in the center of the view, the right button places
+/// the brush material in front of it. With the integrity check enabled, voxels that lose
+/// their support break off and fall as rigid bodies
+pub struct Editor {
+    brush: Material,
+    fluids: FluidSimulation,
+    granular: GranularSimulation,
+    integrity: bool,
+    debris: Vec<(Object, RigidBodyHandle)>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            brush: Material::Stone,
+            fluids: FluidSimulation::default(),
+            granular: GranularSimulation::default(),
+            integrity: true,
+            debris: Vec::new(),
+        }
+    }
+}
+
+impl Editor {
+    pub fn brush(&self) -> Material {
+        self.brush
+    }
+
+    pub fn fluids(&self) -> &FluidSimulation {
+        &self.fluids
+    }
+
+    pub fn granular(&self) -> &GranularSimulation {
+        &self.granular
+    }
+
+    pub fn integrity(&self) -> bool {
+        self.integrity
+    }
+
+    pub fn handle(
+        &mut self,
+        engine: &Engine,
+        world: &mut impl VoxelWorld,
+        simulation: &mut Simulation,
+        event: &WindowEvent,
+    ) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } if event.state.is_pressed() => {
+                if event.physical_key == PhysicalKey::Code(INTEGRITY_KEY) && !event.repeat {
+                    self.integrity = !self.integrity;
+                }
+
+                if let Some((_, material)) = BRUSHES
+                    .iter()
+                    .find(|(code, _)| event.physical_key == PhysicalKey::Code(*code))
+                {
+                    self.brush = *material;
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } => {
+                if let Some(position) = self.edit(engine, world, *button) {
+                    self.fluids.wake(position);
+                    self.granular.wake(position);
+
+                    if self.integrity && *button == MouseButton::Left {
+                        self.break_off(engine, world, simulation, position);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Advances the simulations by one tick, meant to run alongside `Simulation::step`
+    pub fn step(&mut self, world: &mut impl VoxelWorld) {
+        for (from, to) in self.granular.step(world) {
+            self.fluids.displace(to, from);
+        }
+
+        self.fluids.step(world);
+    }
+
+    /// Updates the broken off clusters from their rigid bodies and renders them
+    pub fn render(&mut self, simulation: &Simulation, pass: &mut VoxelPass) {
+        for (object, handle) in self.debris.iter_mut() {
+            let pos = simulation.rigid_body_set()[*handle].position();
+
+            let transform = Matrix4::from_translation(Vector3::new(
+                pos.translation.x,
+                pos.translation.y,
+                pos.translation.z,
+            )) * Matrix4::from(Quaternion::new(
+                pos.rotation.w,
+                pos.rotation.i,
+                pos.rotation.j,
+                pos.rotation.k,
+            ));
+
+            object.set_transform(transform);
+            pass.render_object(object);
+        }
+    }
+
+    /// Turns the clusters that lost their support with the removed voxel into rigid bodies
+    fn break_off(
+        &mut self,
+        engine: &Engine,
+        world: &mut impl VoxelWorld,
+        simulation: &mut Simulation,
+        position: Vector3<i32>,
+    ) {
+        for cluster in unsupported(world, position) {
+            for voxel in &cluster {
+                self.fluids.wake(*voxel);
+                self.granular.wake(*voxel);
+            }
+
+            self.debris
+                .push(detach(world, simulation, engine.device().clone(), &cluster));
+        }
+
+        while self.debris.len() > MAX_DEBRIS {
+            let (_, handle) = self.debris.remove(0);
+
+            simulation.remove_rigid_body(handle);
+        }
+    }
+
+    /// Edits the voxel in the center of the view, returns the edited voxel
+    fn edit(
+        &self,
+        engine: &Engine,
+        world: &mut impl VoxelWorld,
+        button: MouseButton,
+    ) -> Option<Vector3<i32>> {
+        let eye = engine.camera().get_eye();
+        let direction = engine.camera().get_look_at() - eye;
+
+        let (hit, before) = raycast(world, eye, direction, REACH)?;
+
+        let (position, voxel) = match button {
+            MouseButton::Left => (hit, None),
+            MouseButton::Right => (before, Some((self.brush.color(), self.brush))),
+            _ => return None,
+        };
+
+        world.set_voxel(position, voxel).then_some(position)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::engine::voxel::generator::noise::{GRADIENT_MAX_HEIGHT, GRADIENT_MIN_HEIGHT};
+use colorgrad::Gradient;
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+/// Color range of the gradient, interpolated between the top of the previous band and `top`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GradientBand {
+    /// Highest world height covered by this band
+    pub top: f32,
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+}
+
+/// Bands going from deep water over sand, grass and mountains up to snow
+pub fn natural_bands() -> Vec<GradientBand> {
+    vec![
+        GradientBand {
+            top: 0.0,
+            from: [0.0, 0.05, 0.2],
+            to: [0.0, 0.2, 0.5],
+        },
+        GradientBand {
+            top: 32.0,
+            from: [0.0, 0.2, 0.5],
+            to: [0.0, 0.6, 1.0],
+        },
+        GradientBand {
+            top: 35.0,
+            from: [0.94, 0.87, 0.73],
+            to: [0.94, 0.87, 0.73],
+        },
+        GradientBand {
+            top: 90.0,
+            from: [0.205, 0.495, 0.1],
+            to: [0.3, 0.4, 0.1],
+        },
+        GradientBand {
+            top: 140.0,
+            from: [0.5, 0.4, 0.3],
+            to: [0.6, 0.5, 0.5],
+        },
+        GradientBand {
+            top: GRADIENT_MAX_HEIGHT as f32,
+            from: [0.9, 0.9, 0.95],
+            to: [1.0, 1.0, 1.05],
+        },
+    ]
+}
+
+/// Height gradient made of bands, with the boundaries between bands slightly distorted by noise
+pub struct NaturalGradient {
+    pub noise: Perlin,
+    pub bands: Vec<GradientBand>,
+}
+
+impl NaturalGradient {
+    pub fn new(seed: u32, bands: Vec<GradientBand>) -> Self {
+        NaturalGradient {
+            noise: Perlin::new(seed),
+            bands,
+        }
+    }
+}
+
+impl Gradient for NaturalGradient {
+    fn at(&self, t: f32) -> colorgrad::Color {
+        let t = t.clamp(0.0, 1.0);
+        let base_height =
+            (GRADIENT_MIN_HEIGHT + t as f64 * (GRADIENT_MAX_HEIGHT - GRADIENT_MIN_HEIGHT)) as f32;
+        let noise = self.noise.get([base_height as f64 * 0.1, 0.0]) as f32 * 4.0;
+
+        let height = base_height + noise;
+
+        let mut bottom = GRADIENT_MIN_HEIGHT as f32;
+
+        for (i, band) in self.bands.iter().enumerate() {
+            if height <= band.top || i == self.bands.len() - 1 {
+                let band_t = if band.top > bottom {
+                    ((height - bottom) / (band.top - bottom)).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+
+                let [r, g, b] =
+                    [0, 1, 2].map(|c| band.from[c] + (band.to[c] - band.from[c]) * band_t);
+
+                return colorgrad::Color::new(r, g, b, 1.0);
+            }
+
+            bottom = band.top;
+        }
+
+        colorgrad::Color::new(0.0, 0.0, 0.0, 1.0)
+    }
+}
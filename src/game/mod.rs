@@ -6,8 +6,14 @@ use scene::Scene;
 use winit::event::WindowEvent;
 
 use crate::engine::core::engine::Engine;
+use clock::WorldClock;
 
+pub mod clock;
+pub mod edit;
+pub mod gradient;
 pub mod input;
+pub mod preset;
+pub mod save;
 pub mod scene;
 pub mod ui;
 
@@ -22,6 +28,7 @@ pub struct Game {
     handler: &'static AtomicCell<InputHandler>,
     changes: VecDeque<Change>,
     events: Receiver<WindowEvent>,
+    clock: WorldClock,
 }
 
 impl Game {
@@ -37,6 +44,7 @@ impl Game {
             handler,
             changes: VecDeque::with_capacity(16),
             events,
+            clock: WorldClock::default(),
         };
 
         scene.on_load(&mut game);
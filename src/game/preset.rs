@@ -0,0 +1,117 @@
+use crate::{
+    engine::voxel::generator::{noise::NoiseParameters, strata::SurfaceLines},
+    game::gradient::natural_bands,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+use super::gradient::GradientBand;
+
+/// Directory the world presets are loaded from
+pub const PRESET_DIRECTORY: &str = "presets";
+
+/// Everything needed to regenerate a world besides its seed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldPreset {
+    pub name: String,
+    pub noise: NoiseParameters,
+    pub bands: Vec<GradientBand>,
+}
+
+impl Default for WorldPreset {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_string(),
+            noise: NoiseParameters::default(),
+            bands: natural_bands(),
+        }
+    }
+}
+
+impl WorldPreset {
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let source = fs::read_to_string(path.as_ref()).ok()?;
+
+        match ron::from_str(&source) {
+            Ok(preset) => Some(preset),
+            Err(err) => {
+                println!("invalid preset {}: {}", path.as_ref().display(), err);
+                None
+            }
+        }
+    }
+
+    /// Loads every `.ron` file of [`PRESET_DIRECTORY`] sorted by name,
+    /// falls back to the built-in default if there is none
+    pub fn load_all() -> Vec<Self> {
+        let mut presets: Vec<Self> = fs::read_dir(PRESET_DIRECTORY)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+            .filter_map(Self::load)
+            .collect();
+
+        if presets.is_empty() {
+            presets.push(Self::default());
+        }
+
+        presets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        presets
+    }
+
+    /// Surface lines at the tops of the shore, grass and rock bands,
+    /// the natural ones if there are too few bands
+    pub fn surface_lines(&self) -> SurfaceLines {
+        match self.bands.as_slice() {
+            [.., sand, grass, rock, _] => SurfaceLines {
+                sand: sand.top as i32,
+                tree: grass.top as i32,
+                snow: rock.top as i32,
+            },
+            _ => SurfaceLines::default(),
+        }
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap()
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let preset = WorldPreset::default();
+
+    assert_eq!(
+        ron::from_str::<WorldPreset>(&preset.to_ron()).unwrap(),
+        preset
+    );
+}
+
+#[test]
+fn test_shipped_presets() {
+    let preset = WorldPreset::load(Path::new(PRESET_DIRECTORY).join("default.ron")).unwrap();
+
+    assert_eq!(preset, WorldPreset::default());
+}
+
+#[test]
+fn test_surface_lines() {
+    assert_eq!(
+        WorldPreset::default().surface_lines(),
+        SurfaceLines::default()
+    );
+
+    let preset = WorldPreset::load(Path::new(PRESET_DIRECTORY).join("highlands.ron")).unwrap();
+
+    assert_eq!(
+        preset.surface_lines(),
+        SurfaceLines {
+            sand: 28,
+            tree: 70,
+            snow: 180,
+        }
+    );
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+use super::{clock::DEFAULT_TIME, preset::WorldPreset};
+
+/// Directory the world saves are stored in
+pub const SAVE_DIRECTORY: &str = "saves";
+
+/// Persistent state of a procedural world, stored as `saves/<seed>.ron`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldSave {
+    pub seed: u32,
+    pub preset: WorldPreset,
+    /// Time of day in hours, see `WorldClock`
+    #[serde(default = "default_time")]
+    pub time: f32,
+}
+
+fn default_time() -> f32 {
+    DEFAULT_TIME
+}
+
+impl WorldSave {
+    pub fn new(seed: u32, preset: WorldPreset) -> Self {
+        Self {
+            seed,
+            preset,
+            time: DEFAULT_TIME,
+        }
+    }
+
+    fn path(seed: u32) -> PathBuf {
+        PathBuf::from(SAVE_DIRECTORY).join(format!("{}.ron", seed))
+    }
+
+    /// Returns the save of the world with `seed`, if it was stored before
+    pub fn load(seed: u32) -> Option<Self> {
+        let source = fs::read_to_string(Self::path(seed)).ok()?;
+
+        ron::from_str(&source).ok()
+    }
+
+    pub fn store(&self) -> std::io::Result<()> {
+        fs::create_dir_all(SAVE_DIRECTORY)?;
+
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(std::io::Error::other)?;
+
+        fs::write(Self::path(self.seed), source)
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    engine::voxel::object::Object,
+    engine::{renderer::sky::Fog, voxel::object::Object},
     game::{input::InputHandler, scene::Scene, ui::menu::pause::PauseMenu, Game},
     stats::{Ranking, Stats},
 };
@@ -38,6 +38,24 @@ impl CustomLevel {
             "ms".to_string(),
             Ranking::High,
         );
+        stats.add_metric(
+            "chunks_drawn".to_string(),
+            "drawn".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "chunks_culled".to_string(),
+            "culled".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "quads".to_string(),
+            "quads".to_string(),
+            "quads".to_string(),
+            Ranking::High,
+        );
 
         Self { object, stats }
     }
@@ -45,6 +63,8 @@ impl CustomLevel {
 
 impl Scene for CustomLevel {
     fn on_current(&mut self, game: &mut Game) {
+        game.engine().renderer().sky().set_fog(Fog::NONE);
+
         game.set_handler(InputHandler::Game);
         game.engine().window().set_grab(true);
         game.engine().window().window().set_cursor_visible(false);
@@ -77,6 +97,9 @@ impl Scene for CustomLevel {
 
         let eye = game.engine().camera().get_eye();
 
+        game.clock.tick();
+        game.clock.apply(game.engine());
+
         let frame = game.engine().start_frame();
 
         let mut scene_pass = frame.start_voxel_render_pass().unwrap();
@@ -85,6 +108,20 @@ impl Scene for CustomLevel {
 
         scene_pass.render_object(&self.object);
 
+        for cascade in 0..game.engine().renderer().shadows().cascade_count() {
+            let mut shadow_pass = frame.start_shadow_pass(cascade);
+
+            shadow_pass.render_object(&self.object);
+
+            frame.finish_voxel_render_pass(shadow_pass);
+        }
+
+        self.stats
+            .push_metric("chunks_drawn", scene_pass.drawn() as f64);
+        self.stats
+            .push_metric("chunks_culled", scene_pass.culled() as f64);
+        self.stats.push_metric("quads", scene_pass.quads() as f64);
+
         ui_pass.render_ui(|ctx| {
             Area::new("stats_display".into())
                 .anchor(Align2::LEFT_TOP, [10.0, 10.0])
@@ -1,7 +1,10 @@
+use crate::game::{save::WorldSave, Game};
+
 pub mod custom;
 pub mod physics;
 pub mod procedural;
 
 pub trait SeededLevel {
-    fn with_seed(seed: u32) -> Self;
+    /// Builds the level, its terrain starts generating right away
+    fn with_world(world: WorldSave, game: &Game) -> Self;
 }
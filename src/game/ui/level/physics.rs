@@ -1,23 +1,26 @@
 use crate::{
     engine::{
         physics::simulation::Simulation,
+        renderer::sky::Fog,
         voxel::{
             chunk::{Chunk, VOXEL_SIZE},
+            generator::noise::NoiseGenerator,
             object::Object,
             terrain::Terrain,
         },
     },
-    game::{input::InputHandler, scene::Scene, ui::menu::pause::PauseMenu, Game},
+    game::{
+        edit::Editor, gradient::NaturalGradient, input::InputHandler, save::WorldSave,
+        scene::Scene, ui::menu::pause::PauseMenu, Game,
+    },
     stats::{Ranking, Stats},
-    TERRAIN_RENDER_DISTANCE,
+    TERRAIN_MEMORY_BUDGET, TERRAIN_RENDER_DISTANCE,
 };
 use cgmath::{Matrix4, Point3, Quaternion, Vector3};
-use colorgrad::Gradient;
 use egui::{Align2, Area, Color32, FontFamily, Frame, RichText};
-use noise::{NoiseFn, Perlin};
 use rand::Rng;
 use rapier3d::prelude::{ColliderBuilder, RigidBodyBuilder, RigidBodyHandle};
-use std::{mem::MaybeUninit, time::Instant};
+use std::time::Instant;
 use winit::{
     event::WindowEvent,
     keyboard::{KeyCode, PhysicalKey},
@@ -31,37 +34,12 @@ pub struct PhysicsLevel {
     stats: Stats,
     last: Instant,
     cubes: Vec<(Object, RigidBodyHandle)>,
-    seed: u32,
+    world: WorldSave,
+    editor: Editor,
 }
 
 impl SeededLevel for PhysicsLevel {
-    fn with_seed(seed: u32) -> Self {
-        let level = MaybeUninit::<PhysicsLevel>::uninit();
-
-        let mut level = unsafe { level.assume_init() };
-
-        level.seed = seed;
-
-        level
-    }
-}
-
-impl Scene for PhysicsLevel {
-    fn on_current(&mut self, game: &mut Game) {
-        game.set_handler(InputHandler::Game);
-        game.engine().window().set_grab(true);
-        game.engine().window().window().set_cursor_visible(false);
-    }
-
-    fn on_load(&mut self, game: &mut Game) {
-        // Setup camera
-        game.engine()
-            .camera()
-            .set_eye(Point3::new(-25.0, 64.0, -25.0));
-        game.engine()
-            .camera()
-            .set_look_at(Point3::new(0.5, 64.0, -0.5));
-
+    fn with_world(mut world: WorldSave, game: &Game) -> Self {
         let mut simulation = Simulation::new(nalgebra::Vector3::new(0.0, -9.81, 0.0));
 
         // Falling cubes
@@ -122,53 +100,19 @@ impl Scene for PhysicsLevel {
             cubes.push((cube, cube_handle));
         }
 
-        pub struct NaturalGradient {
-            pub noise: Perlin,
-        }
-
-        pub fn natural() -> NaturalGradient {
-            NaturalGradient {
-                noise: Perlin::new(1234),
-            }
-        }
-
-        impl Gradient for NaturalGradient {
-            fn at(&self, t: f32) -> colorgrad::Color {
-                let t = t.clamp(0.0, 1.0);
-                let base_height = t * 256.0;
-                let noise = self.noise.get([base_height as f64 * 0.1, 0.0]) as f32 * 4.0;
-
-                let height = t * 256.0 + noise;
-
-                if height <= 32.0 {
-                    let water_t = height / 32.0;
-                    colorgrad::Color::new(0.0, 0.2 + (water_t * 0.4), 0.5 + (water_t * 0.5), 1.0)
-                } else if height <= 35.0 {
-                    colorgrad::Color::new(0.94, 0.87, 0.73, 1.0)
-                } else if height <= 90.0 {
-                    let grass_t = (height - 32.0) / 58.0;
-                    colorgrad::Color::new(0.2 + (grass_t * 0.1), 0.5 - (grass_t * 0.1), 0.1, 1.0)
-                } else if height <= 140.0 {
-                    let mountain_t = (height - 90.0) / 50.0;
-                    colorgrad::Color::new(
-                        0.5 + (mountain_t * 0.1),
-                        0.4 + (mountain_t * 0.1),
-                        0.3 + (mountain_t * 0.2),
-                        1.0,
-                    )
-                } else {
-                    let snow_t = (height - 140.0) / 116.0;
-                    let white = 0.9 + (snow_t * 0.1);
-                    colorgrad::Color::new(white, white, white + 0.05, 1.0)
-                }
-            }
-        }
-
-        let seed: u32 = rng.random();
+        world.seed = rng.random();
+        let seed = world.seed;
         let terrain = Terrain::new(
-            seed,
             TERRAIN_RENDER_DISTANCE,
-            Box::new(natural()),
+            TERRAIN_MEMORY_BUDGET,
+            Box::new(
+                NoiseGenerator::new(
+                    seed,
+                    world.preset.noise.clone(),
+                    Box::new(NaturalGradient::new(1234, world.preset.bands.clone())),
+                )
+                .with_surface(world.preset.surface_lines()),
+            ),
             game.engine(),
         );
 
@@ -182,6 +126,20 @@ impl Scene for PhysicsLevel {
             Ranking::High,
         );
 
+        stats.add_metric(
+            "fluid".to_string(),
+            "fluid".to_string(),
+            "voxels".to_string(),
+            Ranking::High,
+        );
+
+        stats.add_metric(
+            "granular".to_string(),
+            "sand".to_string(),
+            "voxels".to_string(),
+            Ranking::High,
+        );
+
         stats.add_metric(
             "fps".to_string(),
             "FPS".to_string(),
@@ -194,30 +152,120 @@ impl Scene for PhysicsLevel {
             "ms".to_string(),
             Ranking::High,
         );
+        stats.add_metric(
+            "chunks_pending".to_string(),
+            "pending".to_string(),
+            "chunks".to_string(),
+            Ranking::High,
+        );
+        stats.add_metric(
+            "chunks_loaded".to_string(),
+            "loaded".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "chunks_drawn".to_string(),
+            "drawn".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "chunks_culled".to_string(),
+            "culled".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "chunks_occluded".to_string(),
+            "occluded".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "quads".to_string(),
+            "quads".to_string(),
+            "quads".to_string(),
+            Ranking::High,
+        );
+        stats.add_metric(
+            "memory_chunks".to_string(),
+            "chunks".to_string(),
+            "MB".to_string(),
+            Ranking::High,
+        );
+        stats.add_metric(
+            "memory_gpu".to_string(),
+            "GPU".to_string(),
+            "MB".to_string(),
+            Ranking::High,
+        );
+        stats.add_metric(
+            "memory_cache".to_string(),
+            "cache".to_string(),
+            "MB".to_string(),
+            Ranking::High,
+        );
 
         let last = Instant::now();
 
-        unsafe {
-            std::ptr::write(
-                self,
-                PhysicsLevel {
-                    seed,
-                    terrain,
-                    simulation,
-                    stats,
-                    last,
-                    cubes,
-                },
-            );
+        PhysicsLevel {
+            world,
+            terrain,
+            simulation,
+            stats,
+            last,
+            cubes,
+            editor: Editor::default(),
         }
+    }
+}
+
+impl Scene for PhysicsLevel {
+    fn on_current(&mut self, game: &mut Game) {
+        game.engine()
+            .renderer()
+            .sky()
+            .set_fog(Fog::reaching(TERRAIN_RENDER_DISTANCE.radius()));
 
         game.set_handler(InputHandler::Game);
         game.engine().window().set_grab(true);
         game.engine().window().window().set_cursor_visible(false);
     }
 
+    fn on_load(&mut self, game: &mut Game) {
+        game.clock.set_time(self.world.time);
+
+        // Setup camera
+        game.engine()
+            .camera()
+            .set_eye(Point3::new(-25.0, 64.0, -25.0));
+        game.engine()
+            .camera()
+            .set_look_at(Point3::new(0.5, 64.0, -0.5));
+
+        game.set_handler(InputHandler::Game);
+        game.engine().window().set_grab(true);
+        game.engine().window().window().set_cursor_visible(false);
+    }
+
+    fn on_unload(&mut self, game: &mut Game) {
+        self.world.time = game.clock.time();
+
+        if let Err(err) = self.world.store() {
+            println!("couldn't save world {}: {}", self.world.seed, err);
+        }
+    }
+
     fn render(&mut self, game: &mut Game) {
         while let Ok(event) = game.events.try_recv() {
+            self.editor.handle(
+                game.engine(),
+                &mut self.terrain,
+                &mut self.simulation,
+                &event,
+            );
+
             if let WindowEvent::KeyboardInput {
                 device_id: _,
                 event,
@@ -237,6 +285,9 @@ impl Scene for PhysicsLevel {
 
         let eye = game.engine().camera().get_eye();
 
+        game.clock.tick();
+        game.clock.apply(game.engine());
+
         let frame = game.engine().start_frame();
 
         let mut scene_pass = frame.start_voxel_render_pass().unwrap();
@@ -245,14 +296,37 @@ impl Scene for PhysicsLevel {
 
         self.terrain
             .render(game.engine(), &mut scene_pass, &mut self.simulation);
+        self.editor.render(&self.simulation, &mut scene_pass);
+
+        self.stats
+            .push_metric("chunks_pending", self.terrain.pending() as f64);
+        self.stats
+            .push_metric("chunks_loaded", self.terrain.loaded() as f64);
+        self.stats.push_metric(
+            "memory_chunks",
+            self.terrain.chunk_memory() as f64 / (1024.0 * 1024.0),
+        );
+        self.stats.push_metric(
+            "memory_gpu",
+            self.terrain.gpu_memory() as f64 / (1024.0 * 1024.0),
+        );
+        self.stats.push_metric(
+            "memory_cache",
+            self.terrain.cache_memory() as f64 / (1024.0 * 1024.0),
+        );
 
         if self.last.elapsed().as_secs_f64() >= 1.0 / 60.0 {
             self.last = Instant::now();
 
             self.simulation.step();
+            self.editor.step(&mut self.terrain);
 
             self.stats
-                .push_metric("physics", self.last.elapsed().as_secs_f64() * 1000.0)
+                .push_metric("physics", self.last.elapsed().as_secs_f64() * 1000.0);
+            self.stats
+                .push_metric("fluid", self.editor.fluids().active() as f64);
+            self.stats
+                .push_metric("granular", self.editor.granular().active() as f64);
         }
 
         for (cube, handle) in self.cubes.iter_mut() {
@@ -273,6 +347,27 @@ impl Scene for PhysicsLevel {
             scene_pass.render_object(cube);
         }
 
+        for cascade in 0..game.engine().renderer().shadows().cascade_count() {
+            let mut shadow_pass = frame.start_shadow_pass(cascade);
+
+            self.terrain.render_shadow(game.engine(), &mut shadow_pass);
+            self.editor.render(&self.simulation, &mut shadow_pass);
+
+            for (cube, _) in &self.cubes {
+                shadow_pass.render_object(cube);
+            }
+
+            frame.finish_voxel_render_pass(shadow_pass);
+        }
+
+        self.stats
+            .push_metric("chunks_drawn", scene_pass.drawn() as f64);
+        self.stats
+            .push_metric("chunks_culled", scene_pass.culled() as f64);
+        self.stats
+            .push_metric("chunks_occluded", scene_pass.occluded() as f64);
+        self.stats.push_metric("quads", scene_pass.quads() as f64);
+
         ui_pass.render_ui(|ctx| {
             Area::new("stats_display".into())
                 .anchor(Align2::LEFT_TOP, [10.0, 10.0])
@@ -288,6 +383,11 @@ impl Scene for PhysicsLevel {
                         }
                     });
                 });
+            Area::new("crosshair".into())
+                .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(RichText::new("+").color(Color32::WHITE).size(16.0));
+                });
             Area::new("coordinates_display".into())
                 .anchor(Align2::RIGHT_TOP, [-10.0, 10.0])
                 .show(ctx, |ui| {
@@ -303,6 +403,17 @@ impl Scene for PhysicsLevel {
                                     .family(FontFamily::Monospace),
                             );
                         }
+
+                        ui.label(
+                            RichText::new(format!(
+                                "{:?} integrity {}",
+                                self.editor.brush(),
+                                if self.editor.integrity() { "on" } else { "off" }
+                            ))
+                            .color(Color32::WHITE)
+                            .size(12.0)
+                            .family(FontFamily::Monospace),
+                        );
                     });
                 });
         });
@@ -1,14 +1,31 @@
 use crate::{
-    engine::{physics::simulation::Simulation, voxel::terrain::Terrain},
-    game::{input::InputHandler, scene::Scene, ui::menu::pause::PauseMenu, Game},
+    engine::{
+        physics::simulation::Simulation,
+        renderer::sky::Fog,
+        voxel::{
+            generator::{
+                noise::NoiseGenerator,
+                structure::{StructureTemplate, STRUCTURE_DIRECTORY},
+                TerrainGenerator,
+            },
+            terrain::Terrain,
+        },
+    },
+    game::{
+        edit::Editor,
+        gradient::NaturalGradient,
+        input::InputHandler,
+        save::WorldSave,
+        scene::Scene,
+        ui::{menu::pause::PauseMenu, tuning::TuningPanel},
+        Game,
+    },
     stats::{Ranking, Stats},
-    TERRAIN_RENDER_DISTANCE,
+    TERRAIN_MEMORY_BUDGET, TERRAIN_RENDER_DISTANCE,
 };
 use cgmath::Point3;
-use colorgrad::Gradient;
 use egui::{Align2, Area, Color32, FontFamily, Frame, RichText};
-use noise::{NoiseFn, Perlin};
-use std::{mem::MaybeUninit, time::Instant};
+use std::{sync::Arc, time::Instant};
 use winit::{
     event::WindowEvent,
     keyboard::{KeyCode, PhysicalKey},
@@ -19,89 +36,54 @@ use super::SeededLevel;
 pub struct ProceduralLevel {
     terrain: Terrain,
     simulation: Simulation,
+    editor: Editor,
     stats: Stats,
     last: Instant,
-    seed: u32,
+    world: WorldSave,
+    tuning: TuningPanel,
+    structures: Arc<[StructureTemplate]>,
 }
 
-impl SeededLevel for ProceduralLevel {
-    fn with_seed(seed: u32) -> Self {
-        let level = MaybeUninit::<ProceduralLevel>::uninit();
-
-        let mut level = unsafe { level.assume_init() };
+impl ProceduralLevel {
+    fn generator(
+        world: &WorldSave,
+        structures: &Arc<[StructureTemplate]>,
+    ) -> Box<dyn TerrainGenerator> {
+        Box::new(
+            NoiseGenerator::new(
+                world.seed,
+                world.preset.noise.clone(),
+                Box::new(NaturalGradient::new(world.seed, world.preset.bands.clone())),
+            )
+            .with_structures(structures.clone())
+            .with_surface(world.preset.surface_lines()),
+        )
+    }
 
-        level.seed = seed;
+    fn set_tuning(&mut self, game: &mut Game, open: bool) {
+        self.tuning.open = open;
 
-        level
+        game.set_handler(if open {
+            InputHandler::Gui
+        } else {
+            InputHandler::Game
+        });
+        game.engine().window().set_grab(!open);
+        game.engine().window().window().set_cursor_visible(open);
     }
 }
 
-impl Scene for ProceduralLevel {
-    fn on_current(&mut self, game: &mut Game) {
-        game.set_handler(InputHandler::Game);
-        game.engine().window().set_grab(true);
-        game.engine().window().window().set_cursor_visible(false);
-    }
-
-    fn on_load(&mut self, game: &mut Game) {
-        let seed = self.seed;
-
-        // Setup camera
-        game.engine()
-            .camera()
-            .set_eye(Point3::new(-25.0, 64.0, -25.0));
-        game.engine()
-            .camera()
-            .set_look_at(Point3::new(0.5, 64.0, -0.5));
-
+impl SeededLevel for ProceduralLevel {
+    fn with_world(world: WorldSave, game: &Game) -> Self {
         let simulation = Simulation::new(nalgebra::Vector3::new(0.0, -9.81, 0.0));
 
-        pub struct NaturalGradient {
-            pub noise: Perlin,
-        }
-
-        pub fn natural(seed: u32) -> NaturalGradient {
-            NaturalGradient {
-                noise: Perlin::new(seed),
-            }
-        }
-
-        impl Gradient for NaturalGradient {
-            fn at(&self, t: f32) -> colorgrad::Color {
-                let t = t.clamp(0.0, 1.0);
-                let base_height = t * 256.0;
-                let noise = self.noise.get([base_height as f64 * 0.1, 0.0]) as f32 * 4.0;
-
-                let height = t * 256.0 + noise;
-
-                if height <= 32.0 {
-                    let water_t = height / 32.0;
-                    colorgrad::Color::new(0.0, 0.2 + (water_t * 0.4), 0.5 + (water_t * 0.5), 1.0)
-                } else if height <= 35.0 {
-                    colorgrad::Color::new(0.94, 0.87, 0.73, 1.0)
-                } else if height <= 90.0 {
-                    let grass_t = (height - 32.0) / 58.0;
-                    colorgrad::Color::new(0.2 + (grass_t * 0.1), 0.5 - (grass_t * 0.1), 0.1, 1.0)
-                } else if height <= 140.0 {
-                    let mountain_t = (height - 90.0) / 50.0;
-                    colorgrad::Color::new(
-                        0.5 + (mountain_t * 0.1),
-                        0.4 + (mountain_t * 0.1),
-                        0.3 + (mountain_t * 0.2),
-                        1.0,
-                    )
-                } else {
-                    let snow_t = (height - 140.0) / 116.0;
-                    let white = 0.9 + (snow_t * 0.1);
-                    colorgrad::Color::new(white, white, white + 0.05, 1.0)
-                }
-            }
-        }
+        let structures: Arc<[StructureTemplate]> =
+            StructureTemplate::load_all(STRUCTURE_DIRECTORY).into();
 
         let terrain = Terrain::new(
-            seed,
             TERRAIN_RENDER_DISTANCE,
-            Box::new(natural(seed)),
+            TERRAIN_MEMORY_BUDGET,
+            Self::generator(&world, &structures),
             game.engine(),
         );
 
@@ -115,6 +97,20 @@ impl Scene for ProceduralLevel {
             Ranking::High,
         );
 
+        stats.add_metric(
+            "fluid".to_string(),
+            "fluid".to_string(),
+            "voxels".to_string(),
+            Ranking::High,
+        );
+
+        stats.add_metric(
+            "granular".to_string(),
+            "sand".to_string(),
+            "voxels".to_string(),
+            Ranking::High,
+        );
+
         stats.add_metric(
             "fps".to_string(),
             "FPS".to_string(),
@@ -127,41 +123,142 @@ impl Scene for ProceduralLevel {
             "ms".to_string(),
             Ranking::High,
         );
+        stats.add_metric(
+            "chunks_pending".to_string(),
+            "pending".to_string(),
+            "chunks".to_string(),
+            Ranking::High,
+        );
+        stats.add_metric(
+            "chunks_loaded".to_string(),
+            "loaded".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "chunks_drawn".to_string(),
+            "drawn".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "chunks_culled".to_string(),
+            "culled".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "chunks_occluded".to_string(),
+            "occluded".to_string(),
+            "chunks".to_string(),
+            Ranking::Low,
+        );
+        stats.add_metric(
+            "quads".to_string(),
+            "quads".to_string(),
+            "quads".to_string(),
+            Ranking::High,
+        );
+        stats.add_metric(
+            "memory_chunks".to_string(),
+            "chunks".to_string(),
+            "MB".to_string(),
+            Ranking::High,
+        );
+        stats.add_metric(
+            "memory_gpu".to_string(),
+            "GPU".to_string(),
+            "MB".to_string(),
+            Ranking::High,
+        );
+        stats.add_metric(
+            "memory_cache".to_string(),
+            "cache".to_string(),
+            "MB".to_string(),
+            Ranking::High,
+        );
 
         let last = Instant::now();
 
-        unsafe {
-            std::ptr::write(
-                self,
-                ProceduralLevel {
-                    seed,
-                    terrain,
-                    simulation,
-                    stats,
-                    last,
-                },
-            );
+        let tuning = TuningPanel::new(world.preset.clone());
+
+        ProceduralLevel {
+            world,
+            tuning,
+            structures,
+            terrain,
+            simulation,
+            editor: Editor::default(),
+            stats,
+            last,
         }
+    }
+}
+
+impl Scene for ProceduralLevel {
+    fn on_current(&mut self, game: &mut Game) {
+        self.set_tuning(game, self.tuning.open);
+
+        // Hide the edge of the loaded terrain
+        game.engine()
+            .renderer()
+            .sky()
+            .set_fog(Fog::reaching(TERRAIN_RENDER_DISTANCE.radius()));
+    }
+
+    fn on_load(&mut self, game: &mut Game) {
+        game.clock.set_time(self.world.time);
+
+        // Setup camera
+        game.engine()
+            .camera()
+            .set_eye(Point3::new(-25.0, 64.0, -25.0));
+        game.engine()
+            .camera()
+            .set_look_at(Point3::new(0.5, 64.0, -0.5));
 
         game.set_handler(InputHandler::Game);
         game.engine().window().set_grab(true);
         game.engine().window().window().set_cursor_visible(false);
     }
 
+    fn on_unload(&mut self, game: &mut Game) {
+        self.world.time = game.clock.time();
+
+        if let Err(err) = self.world.store() {
+            println!("couldn't save world {}: {}", self.world.seed, err);
+        }
+    }
+
     fn render(&mut self, game: &mut Game) {
         // Handle events
         while let Ok(event) = game.events.try_recv() {
+            if !self.tuning.open {
+                self.editor.handle(
+                    game.engine(),
+                    &mut self.terrain,
+                    &mut self.simulation,
+                    &event,
+                );
+            }
+
             if let WindowEvent::KeyboardInput {
                 device_id: _,
                 event,
                 is_synthetic: _,
             } = event
             {
-                if let PhysicalKey::Code(KeyCode::Escape) = event.physical_key {
-                    if !event.state.is_pressed() {
-                        game.push_scene(Box::new(PauseMenu::new()));
+                match event.physical_key {
+                    PhysicalKey::Code(KeyCode::Escape) => {
+                        if !event.state.is_pressed() {
+                            game.push_scene(Box::new(PauseMenu::new()));
+                        }
+                        return;
+                    }
+                    PhysicalKey::Code(KeyCode::F1) if !event.state.is_pressed() => {
+                        self.set_tuning(game, !self.tuning.open);
                     }
-                    return;
+                    _ => {}
                 }
             }
         }
@@ -169,6 +266,10 @@ impl Scene for ProceduralLevel {
         let start = Instant::now();
 
         let eye = game.engine().camera().get_eye();
+        let time = game.clock.to_string();
+
+        game.clock.tick();
+        game.clock.apply(game.engine());
 
         let frame = game.engine().start_frame();
 
@@ -178,17 +279,62 @@ impl Scene for ProceduralLevel {
 
         self.terrain
             .render(game.engine(), &mut scene_pass, &mut self.simulation);
+        self.editor.render(&self.simulation, &mut scene_pass);
+
+        for cascade in 0..game.engine().renderer().shadows().cascade_count() {
+            let mut shadow_pass = frame.start_shadow_pass(cascade);
+
+            self.terrain.render_shadow(game.engine(), &mut shadow_pass);
+            self.editor.render(&self.simulation, &mut shadow_pass);
+
+            frame.finish_voxel_render_pass(shadow_pass);
+        }
+
+        self.stats
+            .push_metric("chunks_pending", self.terrain.pending() as f64);
+        self.stats
+            .push_metric("chunks_loaded", self.terrain.loaded() as f64);
+        self.stats.push_metric(
+            "memory_chunks",
+            self.terrain.chunk_memory() as f64 / (1024.0 * 1024.0),
+        );
+        self.stats.push_metric(
+            "memory_gpu",
+            self.terrain.gpu_memory() as f64 / (1024.0 * 1024.0),
+        );
+        self.stats.push_metric(
+            "memory_cache",
+            self.terrain.cache_memory() as f64 / (1024.0 * 1024.0),
+        );
 
         if self.last.elapsed().as_secs_f64() >= 1.0 / 60.0 {
             self.last = Instant::now();
 
             self.simulation.step();
+            self.editor.step(&mut self.terrain);
 
             self.stats
-                .push_metric("physics", self.last.elapsed().as_secs_f64() * 1000.0)
+                .push_metric("physics", self.last.elapsed().as_secs_f64() * 1000.0);
+            self.stats
+                .push_metric("fluid", self.editor.fluids().active() as f64);
+            self.stats
+                .push_metric("granular", self.editor.granular().active() as f64);
         }
 
+        let mut tuned = false;
+        let tuning_open = self.tuning.open;
+
+        self.stats
+            .push_metric("chunks_drawn", scene_pass.drawn() as f64);
+        self.stats
+            .push_metric("chunks_culled", scene_pass.culled() as f64);
+        self.stats
+            .push_metric("chunks_occluded", scene_pass.occluded() as f64);
+        self.stats.push_metric("quads", scene_pass.quads() as f64);
+
         ui_pass.render_ui(|ctx| {
+            tuned = self.tuning.show(ctx);
+
             Area::new("stats_display".into())
                 .anchor(Align2::LEFT_TOP, [10.0, 10.0])
                 .show(ctx, |ui| {
@@ -203,6 +349,11 @@ impl Scene for ProceduralLevel {
                         }
                     });
                 });
+            Area::new("crosshair".into())
+                .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(RichText::new("+").color(Color32::WHITE).size(16.0));
+                });
             Area::new("coordinates_display".into())
                 .anchor(Align2::RIGHT_TOP, [-10.0, 10.0])
                 .show(ctx, |ui| {
@@ -218,6 +369,24 @@ impl Scene for ProceduralLevel {
                                     .family(FontFamily::Monospace),
                             );
                         }
+
+                        ui.label(
+                            RichText::new(format!("Time {}", time))
+                                .color(Color32::WHITE)
+                                .size(12.0)
+                                .family(FontFamily::Monospace),
+                        );
+
+                        ui.label(
+                            RichText::new(format!(
+                                "{:?} integrity {}",
+                                self.editor.brush(),
+                                if self.editor.integrity() { "on" } else { "off" }
+                            ))
+                            .color(Color32::WHITE)
+                            .size(12.0)
+                            .family(FontFamily::Monospace),
+                        );
                     });
                 });
         });
@@ -227,6 +396,20 @@ impl Scene for ProceduralLevel {
 
         game.engine().finish_frame(frame);
 
+        if tuned {
+            self.world.preset = self.tuning.preset.clone();
+
+            self.terrain.set_generator(
+                Self::generator(&self.world, &self.structures),
+                &mut self.simulation,
+            );
+        }
+
+        // The panel was closed with its close button
+        if tuning_open && !self.tuning.open {
+            self.set_tuning(game, false);
+        }
+
         let end = Instant::now();
 
         self.stats
@@ -1,7 +1,7 @@
-use egui::{Align2, Area, Button, Color32, Frame, RichText};
+use egui::{Align2, Area, Button, Checkbox, Color32, Frame, RichText, Slider};
 use winit::window::CursorGrabMode;
 
-use crate::game::{input::InputHandler, scene::Scene, Game};
+use crate::game::{clock::DAY_LENGTH, input::InputHandler, scene::Scene, Game};
 
 #[derive(Default)]
 pub struct PauseMenu {}
@@ -78,6 +78,52 @@ impl Scene for PauseMenu {
                             }
 
                             ui.add_space(12.5);
+
+                            let mut time = game.clock.time();
+
+                            if ui
+                                .add(
+                                    Slider::new(&mut time, 0.0..=DAY_LENGTH)
+                                        .text(format!("time {}", game.clock)),
+                                )
+                                .changed()
+                            {
+                                game.clock.set_time(time);
+                            }
+
+                            // Up to a day per minute
+                            ui.add(
+                                Slider::new(&mut game.clock.speed, 0.0..=DAY_LENGTH / 60.0)
+                                    .text("hours per second"),
+                            );
+
+                            ui.add_space(12.5);
+
+                            let renderer = game.engine().renderer();
+                            let mut anti_aliasing = renderer.anti_aliasing();
+
+                            ui.horizontal(|ui| {
+                                ui.label("MSAA");
+
+                                for samples in [1, 2, 4, 8] {
+                                    ui.selectable_value(
+                                        &mut anti_aliasing.msaa,
+                                        samples,
+                                        match samples {
+                                            1 => "Off".to_string(),
+                                            _ => format!("{samples}x"),
+                                        },
+                                    );
+                                }
+                            });
+
+                            ui.add(Checkbox::new(&mut anti_aliasing.fxaa, "FXAA"));
+
+                            if anti_aliasing != renderer.anti_aliasing() {
+                                renderer.set_anti_aliasing(anti_aliasing);
+                            }
+
+                            ui.add_space(12.5);
                         });
                     });
                 });
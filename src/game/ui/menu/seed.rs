@@ -1,12 +1,16 @@
 use std::marker::PhantomData;
 
-use crate::game::{input::InputHandler, scene::Scene, ui::level::SeededLevel, Game};
-use egui::{Align2, Area, Button, Color32, Frame, RichText, TextEdit};
+use crate::game::{
+    input::InputHandler, preset::WorldPreset, save::WorldSave, scene::Scene,
+    ui::level::SeededLevel, Game,
+};
+use egui::{Align2, Area, Button, Color32, ComboBox, Frame, RichText, TextEdit};
 use winit::window::CursorGrabMode;
 
-#[derive(Default)]
 pub struct SeedMenu<T: Scene + SeededLevel + 'static> {
     buffer: String,
+    presets: Vec<WorldPreset>,
+    selected: usize,
     t: PhantomData<T>,
 }
 
@@ -14,11 +18,20 @@ impl<T: Scene + SeededLevel> SeedMenu<T> {
     pub fn new() -> Self {
         SeedMenu {
             buffer: String::new(),
+            presets: WorldPreset::load_all(),
+            selected: 0,
             t: PhantomData,
         }
     }
 }
 
+// Not derived, the menu indexes into the presets and needs at least one
+impl<T: Scene + SeededLevel> Default for SeedMenu<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Scene + SeededLevel> Scene for SeedMenu<T> {
     fn on_load(&mut self, game: &mut Game) {
         game.engine()
@@ -45,6 +58,12 @@ impl<T: Scene + SeededLevel> Scene for SeedMenu<T> {
                         ui.vertical_centered(|ui| {
                             ui.add(TextEdit::singleline(&mut self.buffer).hint_text("Seed"));
 
+                            ComboBox::from_id_salt("preset")
+                                .selected_text(&self.presets[self.selected].name)
+                                .show_index(ui, &mut self.selected, self.presets.len(), |i| {
+                                    &self.presets[i].name
+                                });
+
                             ui.add_space(12.5);
 
                             ui.horizontal_centered(|ui| {
@@ -72,8 +91,18 @@ impl<T: Scene + SeededLevel> Scene for SeedMenu<T> {
                                     .clicked()
                                 {
                                     if let Ok(seed) = self.buffer.parse::<u32>() {
+                                        // Existing worlds keep the preset they were created with
+                                        let world = WorldSave::load(seed).unwrap_or_else(|| {
+                                            WorldSave::new(
+                                                seed,
+                                                self.presets[self.selected].clone(),
+                                            )
+                                        });
+
                                         game.pop_scene();
-                                        game.push_scene(Box::new(T::with_seed(seed)))
+                                        let level = T::with_world(world, game);
+
+                                        game.push_scene(Box::new(level))
                                     }
                                 }
                             });
@@ -0,0 +1,160 @@
+use crate::game::preset::WorldPreset;
+use egui::{Color32, Context, DragValue, Grid, RichText, ScrollArea, Slider, Window};
+
+/// Developer panel editing the preset of the current world
+pub struct TuningPanel {
+    pub open: bool,
+    pub preset: WorldPreset,
+}
+
+impl TuningPanel {
+    pub fn new(preset: WorldPreset) -> Self {
+        Self {
+            open: false,
+            preset,
+        }
+    }
+
+    /// Shows the panel if it is open, returns if the preset changed
+    pub fn show(&mut self, ctx: &Context) -> bool {
+        let mut changed = false;
+
+        let mut open = self.open;
+
+        Window::new("Terrain tuning")
+            .open(&mut open)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ScrollArea::vertical().show(ui, |ui| {
+                    let noise = &mut self.preset.noise;
+
+                    ui.label(RichText::new("Noise").color(Color32::WHITE));
+
+                    changed |= ui
+                        .add(
+                            Slider::new(&mut noise.scale, 0.0001..=0.01)
+                                .logarithmic(true)
+                                .text("scale"),
+                        )
+                        .changed();
+                    changed |= ui
+                        .add(
+                            Slider::new(&mut noise.height_multiplier, 0.0..=500.0)
+                                .text("height multiplier"),
+                        )
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut noise.octaves, 1..=8).text("octaves"))
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut noise.persistence, 0.0..=1.0).text("persistence"))
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut noise.detail_scale, 0.0..=16.0).text("detail scale"))
+                        .changed();
+                    changed |= ui
+                        .add(
+                            Slider::new(&mut noise.noise_intensity, 0.0..=16.0)
+                                .text("noise intensity"),
+                        )
+                        .changed();
+
+                    changed |= ui
+                        .add(Slider::new(&mut noise.sea_level, -64..=128).text("sea level"))
+                        .changed();
+
+                    changed |= ui
+                        .add(Slider::new(&mut noise.rivers, 0.0..=1.0).text("rivers"))
+                        .changed();
+
+                    changed |= ui
+                        .add(Slider::new(&mut noise.vegetation, 0.0..=1.0).text("vegetation"))
+                        .changed();
+
+                    ui.separator();
+
+                    let erosion = &mut noise.erosion;
+
+                    ui.label(RichText::new("Erosion").color(Color32::WHITE));
+
+                    changed |= ui
+                        .add(Slider::new(&mut erosion.iterations, 0..=60).text("iterations"))
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut erosion.rain, 0.0..=2.0).text("rain"))
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut erosion.solubility, 0.0..=0.5).text("solubility"))
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut erosion.evaporation, 0.0..=1.0).text("evaporation"))
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut erosion.capacity, 0.0..=1.0).text("capacity"))
+                        .changed();
+                    changed |= ui
+                        .add(
+                            Slider::new(&mut erosion.thermal_iterations, 0..=30)
+                                .text("thermal iterations"),
+                        )
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut erosion.talus, 0.0..=8.0).text("talus"))
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut erosion.thermal_rate, 0.0..=1.0).text("thermal rate"))
+                        .changed();
+
+                    ui.separator();
+
+                    let strata = &mut noise.strata;
+
+                    ui.label(RichText::new("Strata").color(Color32::WHITE));
+
+                    changed |= ui
+                        .add(Slider::new(&mut strata.dirt_depth, 0..=16).text("dirt depth"))
+                        .changed();
+                    changed |= ui
+                        .add(Slider::new(&mut strata.stone_depth, 0..=256).text("stone depth"))
+                        .changed();
+
+                    for ore in &mut strata.ores {
+                        changed |= ui
+                            .add(
+                                Slider::new(&mut ore.threshold, 0.0..=1.0)
+                                    .text(format!("{:?} threshold", ore.material)),
+                            )
+                            .changed();
+                    }
+
+                    ui.separator();
+
+                    ui.label(RichText::new("Gradient").color(Color32::WHITE));
+
+                    Grid::new("gradient_bands").show(ui, |ui| {
+                        ui.label("top");
+                        ui.label("from");
+                        ui.label("to");
+                        ui.end_row();
+
+                        for band in &mut self.preset.bands {
+                            changed |= ui.add(DragValue::new(&mut band.top)).changed();
+                            changed |= ui.color_edit_button_rgb(&mut band.from).changed();
+                            changed |= ui.color_edit_button_rgb(&mut band.to).changed();
+                            ui.end_row();
+                        }
+                    });
+
+                    ui.separator();
+
+                    if ui.button("Copy as preset").clicked() {
+                        ctx.copy_text(self.preset.to_ron());
+                    }
+                });
+            });
+
+        self.open = open;
+
+        changed
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     fs::File,
-    io::{BufReader, Read},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
@@ -46,6 +46,51 @@ pub fn load_voxels<P: AsRef<Path>>(path: P) -> Vec<([i32; 3], [u8; 4])> {
     voxels
 }
 
+/// Writes a VOXELSRS file batch by batch, the voxel count in the header is filled in by
+/// `finish`
+pub struct VoxelWriter {
+    writer: BufWriter<File>,
+    count: u64,
+}
+
+impl VoxelWriter {
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all("VOXELSRS".as_bytes())?;
+        writer.write_all(&0u64.to_le_bytes())?;
+
+        Ok(Self { writer, count: 0 })
+    }
+
+    pub fn write(&mut self, voxels: &[([i32; 3], [u8; 4])]) -> std::io::Result<()> {
+        for (position, color) in voxels {
+            for coord in position {
+                self.writer.write_all(&coord.to_le_bytes())?;
+            }
+
+            // Colors are stored in sRGB, the inverse of `gamma_correction`
+            self.writer.write_all(&[
+                (linear_to_srgb(color[0] as f32 / 255.0) * 255.0).round() as u8,
+                (linear_to_srgb(color[1] as f32 / 255.0) * 255.0).round() as u8,
+                (linear_to_srgb(color[2] as f32 / 255.0) * 255.0).round() as u8,
+                color[3],
+            ])?;
+        }
+
+        self.count += voxels.len() as u64;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.writer.seek(SeekFrom::Start(8))?;
+        self.writer.write_all(&self.count.to_le_bytes())?;
+
+        self.writer.flush()
+    }
+}
+
 fn gamma_correction(pixel: &mut [u8; 4]) {
     *pixel = [
         (srgb_to_linear(pixel[0] as f32 / 255.0) * 255.0) as u8,
@@ -62,3 +107,30 @@ fn srgb_to_linear(srgb: f32) -> f32 {
         ((srgb + 0.055) / 1.055).powf(2.4)
     }
 }
+
+fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[test]
+fn test_store_voxels() {
+    let path = std::env::temp_dir().join("test_store_voxels.voxels");
+
+    let voxels = vec![
+        ([1, -2, 3], [255, 0, 0, 255]),
+        ([-4, 5, -6], [0, 0, 255, 255]),
+    ];
+
+    let mut writer = VoxelWriter::create(&path).unwrap();
+    writer.write(&voxels[..1]).unwrap();
+    writer.write(&voxels[1..]).unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(load_voxels(&path), voxels);
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -2,9 +2,12 @@ use crossbeam::{
     atomic::AtomicCell,
     channel::{unbounded, Receiver},
 };
+use engine::voxel::terrain::{MemoryBudget, TerrainDistance};
 use engine::{
     core::{engine::Engine, window::window::Window},
-    renderer::backend::Backend,
+    renderer::{
+        antialiasing::AntiAliasing, backend::Backend, shadow::ShadowSettings, ssao::SsaoQuality,
+    },
 };
 use game::{
     input::{EventHandler, InputHandler},
@@ -13,6 +16,7 @@ use game::{
 };
 use winit::{event::WindowEvent, window::WindowAttributes};
 
+pub mod cli;
 pub mod engine;
 pub mod game;
 pub mod io;
@@ -27,11 +31,41 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 static GLOBAL: tracy_client::ProfiledAllocator<std::alloc::System> =
     tracy_client::ProfiledAllocator::new(std::alloc::System, 100);
 
-pub const TERRAIN_RENDER_DISTANCE: u32 = 12;
+pub const TERRAIN_RENDER_DISTANCE: TerrainDistance = TerrainDistance {
+    horizontal: 12,
+    below: 4,
+    above: 8,
+};
+
+pub const TERRAIN_MEMORY_BUDGET: MemoryBudget = MemoryBudget {
+    caches: 64 * 1024 * 1024,
+    chunks: 1024 * 1024 * 1024,
+};
+
+pub const SHADOW_SETTINGS: ShadowSettings = ShadowSettings {
+    cascades: 3,
+    resolution: 2048,
+    distance: 256.0,
+};
+
+pub const SSAO_QUALITY: SsaoQuality = SsaoQuality::Medium;
+
+pub const ANTI_ALIASING: AntiAliasing = AntiAliasing {
+    msaa: 4,
+    fxaa: false,
+};
 
 pub fn main() {
     env_logger::init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Headless subcommands run without a window or device
+    if args.first().is_some_and(|arg| arg == "gen") {
+        cli::gen(&args[1..]);
+        return;
+    }
+
     #[cfg(feature = "profiling")]
     {
         use tracy_client::Client;
@@ -44,7 +78,13 @@ pub fn main() {
 
     let backend = pollster::block_on(Backend::new(window));
 
-    let engine: &'static Engine = Box::leak(Box::new(Engine::new(window, backend)));
+    let engine: &'static Engine = Box::leak(Box::new(Engine::new(
+        window,
+        backend,
+        SHADOW_SETTINGS,
+        SSAO_QUALITY,
+        ANTI_ALIASING,
+    )));
 
     events.handler_mut().set_engine(engine);
 